@@ -0,0 +1,54 @@
+//! Reconnect delays: exponential backoff with jitter, so a broker restart
+//! doesn't have every client at the space knocking at the same moment.
+
+use embassy_time::Duration;
+
+pub struct Backoff {
+    min: Duration,
+    max: Duration,
+    current: Duration,
+    /// xorshift32 state for the jitter, never zero.
+    rng: u32,
+}
+
+impl Backoff {
+    /// Delays start at `min` and double up to `max`. `seed` only varies the
+    /// jitter, any value will do.
+    pub const fn new(min: Duration, max: Duration, seed: u32) -> Self {
+        Self {
+            min,
+            max,
+            current: min,
+            rng: if seed == 0 { 0x9e37_79b9 } else { seed },
+        }
+    }
+
+    /// How long to wait before the next attempt: the current step plus up to
+    /// half of it again at random.
+    pub fn next_delay(&mut self) -> Duration {
+        let step = self.current;
+        self.current = (self.current * 2).min(self.max);
+
+        let jitter_range = step.as_ticks() / 2;
+        let jitter = if jitter_range == 0 {
+            0
+        } else {
+            u64::from(self.random()) % (jitter_range + 1)
+        };
+        step + Duration::from_ticks(jitter)
+    }
+
+    /// Start over at `min`, after a connection that worked.
+    pub fn reset(&mut self) {
+        self.current = self.min;
+    }
+
+    fn random(&mut self) -> u32 {
+        let mut x = self.rng;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.rng = x;
+        x
+    }
+}
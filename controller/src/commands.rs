@@ -0,0 +1,186 @@
+//! Command topics: what a message on `<device>/cmd/<name>` does.
+//!
+//! Every entity in [`ENTITIES`] with a [`Command`] gets a command topic named
+//! after it, plus the unannounced `cmd/raw`. The MQTT front-end subscribes
+//! from and dispatches through [`find`], so an entity announced to Home
+//! Assistant can't end up without a handler.
+
+use alloc::string::{String, ToString};
+use core::fmt::Write;
+
+use projector_protocol::command::{Input, Volume};
+use projector_protocol::ProjectorCommand;
+use serde_json::{json, Value};
+
+use crate::entities::ENTITIES;
+use crate::poller;
+use crate::projector::{ProjectorError, RawPayload, Request, Requester, Response};
+
+/// Sends its payload to the projector as is, for commands without an entity.
+pub const RAW: &str = "raw";
+
+/// What to do with a command message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action<'a> {
+    Execute(ProjectorCommand),
+    /// Send the payload as is, see [`RAW`].
+    Raw(&'a [u8]),
+}
+
+/// The payload doesn't fit the topic, e.g. `HDMI9` on `cmd/input`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InvalidPayload;
+
+/// How to turn the payload of a command topic into an [`Action`].
+#[derive(Debug, Clone, Copy)]
+pub enum Command {
+    /// `ON` or `OFF`.
+    Switch(fn(bool) -> ProjectorCommand),
+    /// Any payload, Home Assistant sends `PRESS`.
+    Button(ProjectorCommand),
+    /// An input name, e.g. `HDMI1`.
+    Input,
+    /// `0` to `63`, Home Assistant's number entity may add `.0`.
+    Volume,
+    Raw,
+}
+
+/// The command behind `cmd/<name>`.
+pub fn find(name: &str) -> Option<Command> {
+    if name == RAW {
+        return Some(Command::Raw);
+    }
+    ENTITIES
+        .iter()
+        .find(|entity| entity.id == name)
+        .and_then(|entity| entity.command)
+}
+
+/// Names of all command topics, the part after `cmd/`.
+pub fn names() -> impl Iterator<Item = &'static str> {
+    ENTITIES
+        .iter()
+        .filter(|entity| entity.command.is_some())
+        .map(|entity| entity.id)
+        .chain([RAW])
+}
+
+impl Command {
+    pub fn parse<'a>(&self, payload: &'a [u8]) -> Result<Action<'a>, InvalidPayload> {
+        let text = core::str::from_utf8(payload).map_err(|_| InvalidPayload);
+        let command = match *self {
+            Command::Switch(command) => match text? {
+                "ON" => command(true),
+                "OFF" => command(false),
+                _ => return Err(InvalidPayload),
+            },
+            Command::Button(command) => command,
+            Command::Input => {
+                ProjectorCommand::SelectInput(text?.parse::<Input>().map_err(|_| InvalidPayload)?)
+            }
+            Command::Volume => ProjectorCommand::Volume(parse_volume(text?).ok_or(InvalidPayload)?),
+            Command::Raw => return Ok(Action::Raw(payload)),
+        };
+        Ok(Action::Execute(command))
+    }
+}
+
+fn parse_volume(text: &str) -> Option<Volume> {
+    let whole = text.split_once('.').map_or(text, |(whole, _)| whole);
+    Volume::new(whole.parse().ok()?).ok()
+}
+
+/// How a command went, published on `result/<name>` next to its topic.
+#[derive(Debug)]
+pub enum Outcome {
+    Done,
+    /// Held back until the lamp has warmed up or cooled down.
+    Deferred,
+    /// The projector's answer to a raw command.
+    Raw {
+        command: RawPayload,
+        reply: RawPayload,
+    },
+    InvalidPayload,
+    Failed(ProjectorError),
+}
+
+impl Outcome {
+    /// `OK`, `DEFERRED` or the reply to a raw command, else what went wrong.
+    pub fn result(&self) -> Result<String, String> {
+        match self {
+            Outcome::Done => Ok("OK".into()),
+            Outcome::Deferred => Ok("DEFERRED".into()),
+            Outcome::Raw { reply, .. } => Ok(ascii(reply)),
+            Outcome::InvalidPayload => Err("INVALID".into()),
+            Outcome::Failed(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// What `stat/raw` shows for a raw command and its reply: both as text,
+/// with `.` for unprintable bytes, and the reply in hex as well.
+pub fn raw_report(command: &[u8], reply: &[u8]) -> Value {
+    let hex = reply.iter().fold(String::new(), |mut hex, b| {
+        // writing to a String can't fail
+        let _ = write!(hex, "{:02X}", b);
+        hex
+    });
+    json!({
+        "command": ascii(command),
+        "ascii": ascii(reply),
+        "hex": hex,
+    })
+}
+
+fn ascii(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|b| {
+            if b.is_ascii_graphic() || *b == b' ' {
+                char::from(*b)
+            } else {
+                '.'
+            }
+        })
+        .collect()
+}
+
+/// Run the command behind `cmd/<name>` with `payload`, `None` if there is
+/// no such command.
+pub async fn run(projector: &mut Requester, name: &str, payload: &[u8]) -> Option<Outcome> {
+    let outcome = match find(name)?.parse(payload) {
+        Ok(action) => execute(projector, action).await,
+        Err(InvalidPayload) => {
+            warn!("Invalid payload on cmd/{}: {:?}", name, payload);
+            Outcome::InvalidPayload
+        }
+    };
+    Some(outcome)
+}
+
+async fn execute(projector: &mut Requester, action: Action<'_>) -> Outcome {
+    match action {
+        Action::Execute(command) => {
+            let result = projector.submit(Request::Execute(command)).await;
+            if result.is_ok() {
+                info!("Sent {:?}", command);
+            }
+            poller::POLL_NOW.signal(());
+            match result {
+                Ok(Response::Deferred) => Outcome::Deferred,
+                Ok(_) => Outcome::Done,
+                Err(e) => Outcome::Failed(e),
+            }
+        }
+        Action::Raw(data) => match projector.send_raw(data).await {
+            Ok(reply) => Outcome::Raw {
+                // the queue took it, so it fits
+                command: RawPayload::from_slice(data).unwrap_or_default(),
+                reply,
+            },
+            Err(e) => Outcome::Failed(e),
+        },
+    }
+}
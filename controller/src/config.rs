@@ -0,0 +1,288 @@
+//! Settings the firmware keeps in flash, so Wi-Fi and broker can change
+//! without a rebuild.
+//!
+//! A record is a small header, the settings as JSON and a CRC:
+//!
+//! ```text
+//! version: u16 LE | length: u16 LE | payload | CRC-32 of all before: u32 LE
+//! ```
+//!
+//! The platform only stores and loads the bytes. Records from older
+//! firmware are brought up to date by `MIGRATIONS` when they are read;
+//! fields that are merely added get a serde default instead of a new
+//! version.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::net::{IpAddr, Ipv4Addr};
+
+use embassy_time::Duration;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Version of the records [`Config::encode`] writes.
+pub const VERSION: u16 = 2;
+
+/// Largest record, header and CRC included.
+pub const MAX_RECORD_SIZE: usize = 2048;
+
+/// Most networks kept, which makes sure they fit in a record.
+pub const MAX_NETWORKS: usize = 5;
+
+const HEADER_SIZE: usize = 4;
+const CRC_SIZE: usize = 4;
+
+/// Upgrades from older versions: `MIGRATIONS[n]` turns a version `n + 1`
+/// payload into a version `n + 2` one.
+const MIGRATIONS: [fn(&mut Value); VERSION as usize - 1] = [wifi_to_networks];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Config {
+    /// Networks to join, see [`crate::wifi::candidates`].
+    pub networks: Vec<WifiNetwork>,
+    pub mqtt: MqttConfig,
+    #[serde(default)]
+    pub ip: IpConfig,
+    /// Topic prefix and Home Assistant node id, `None` for the one derived
+    /// from the MAC address.
+    pub device_id: Option<String>,
+    #[serde(default = "default_poll_interval")]
+    pub poll_interval_secs: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WifiNetwork {
+    pub ssid: String,
+    /// Empty for an open network.
+    pub password: String,
+    /// Higher is tried first.
+    #[serde(default)]
+    pub priority: u8,
+    /// Only ever join this access point.
+    #[serde(default)]
+    pub bssid: Option<[u8; 6]>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MqttConfig {
+    /// Name or address of the broker, `.local` names included. Empty to
+    /// take the first one advertising [`crate::mdns::MQTT`].
+    pub host: String,
+    /// Unused for a discovered broker, which advertises its port.
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// `None` for the device id.
+    pub client_id: Option<String>,
+    /// Name the broker's TLS certificate is checked against, see
+    /// [`MqttConfig::server_name`].
+    #[serde(default)]
+    pub tls_server_name: Option<String>,
+}
+
+/// How the board gets its addresses.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IpConfig {
+    /// `None` for DHCP, which also tells the router the device id as host
+    /// name.
+    #[serde(default)]
+    pub ipv4: Option<StaticIpv4>,
+    /// IPv6 with SLAAC next to IPv4.
+    #[serde(default)]
+    pub ipv6: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StaticIpv4 {
+    pub address: Ipv4Addr,
+    pub prefix_len: u8,
+    pub gateway: Option<Ipv4Addr>,
+    /// Up to [`MAX_DNS_SERVERS`] name servers.
+    #[serde(default)]
+    pub dns_servers: Vec<Ipv4Addr>,
+}
+
+/// Name servers the network stack takes.
+pub const MAX_DNS_SERVERS: usize = 3;
+
+impl StaticIpv4 {
+    /// From an address with prefix length (`10.20.0.42/24`), an optional
+    /// gateway and comma or space separated name servers, as in `.env` and
+    /// on the setup page. `None` if any of them does not parse.
+    pub fn parse(address: &str, gateway: &str, dns_servers: &str) -> Option<Self> {
+        let (address, prefix_len) = address.trim().split_once('/')?;
+        let prefix_len = prefix_len.parse().ok().filter(|len| *len <= 32)?;
+        let gateway = match gateway.trim() {
+            "" => None,
+            gateway => Some(gateway.parse().ok()?),
+        };
+        let dns_servers = dns_servers
+            .split([',', ' '])
+            .filter(|server| !server.is_empty())
+            .map(|server| server.parse().ok())
+            .collect::<Option<Vec<_>>>()?;
+        if dns_servers.len() > MAX_DNS_SERVERS {
+            return None;
+        }
+        Some(Self {
+            address: address.parse().ok()?,
+            prefix_len,
+            gateway,
+            dns_servers,
+        })
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            networks: Vec::new(),
+            mqtt: MqttConfig::default(),
+            ip: IpConfig::default(),
+            device_id: None,
+            poll_interval_secs: default_poll_interval(),
+        }
+    }
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        Self {
+            host: String::new(),
+            port: 1883,
+            username: None,
+            password: None,
+            client_id: None,
+            tls_server_name: None,
+        }
+    }
+}
+
+impl MqttConfig {
+    /// The name to check the TLS certificate of the broker at `host` (the
+    /// configured or the discovered one) against: the stored server name,
+    /// or `host` itself. `None` if `host` is an address and no name is
+    /// stored.
+    pub fn server_name<'a>(&'a self, host: &'a str) -> Option<&'a str> {
+        match &self.tls_server_name {
+            Some(name) => Some(name),
+            None if host.parse::<IpAddr>().is_ok() => None,
+            None => Some(host),
+        }
+    }
+}
+
+fn default_poll_interval() -> u32 {
+    10
+}
+
+/// Why a record could not be read or written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigError {
+    /// Shorter than its header says.
+    Truncated,
+    /// The CRC does not match, the record is damaged.
+    Checksum,
+    /// Written by newer firmware.
+    UnsupportedVersion(u16),
+    /// Intact, but not settings of its version.
+    Invalid,
+    /// The settings do not fit in [`MAX_RECORD_SIZE`].
+    TooLarge,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "config record truncated"),
+            Self::Checksum => write!(f, "config record checksum mismatch"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "config record version {} not supported", version)
+            }
+            Self::Invalid => write!(f, "config record invalid"),
+            Self::TooLarge => write!(f, "config larger than {} bytes", MAX_RECORD_SIZE),
+        }
+    }
+}
+
+impl Config {
+    /// How often the poller asks the projector, at least once a second.
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.poll_interval_secs.max(1).into())
+    }
+
+    /// The record to store, at the current [`VERSION`].
+    pub fn encode(&self) -> Result<Vec<u8>, ConfigError> {
+        let payload = serde_json::to_vec(self).map_err(|_| ConfigError::Invalid)?;
+        if HEADER_SIZE + payload.len() + CRC_SIZE > MAX_RECORD_SIZE {
+            return Err(ConfigError::TooLarge);
+        }
+
+        let mut record = Vec::with_capacity(HEADER_SIZE + payload.len() + CRC_SIZE);
+        record.extend_from_slice(&VERSION.to_le_bytes());
+        record.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        record.extend_from_slice(&payload);
+        record.extend_from_slice(&crc32(&record).to_le_bytes());
+        Ok(record)
+    }
+
+    /// Read a record of this or any older version. Anything after the CRC
+    /// is ignored, so `record` may be a whole flash buffer.
+    ///
+    /// Re-encoding a migrated record gives different bytes, which is how
+    /// the platform knows to store it again.
+    pub fn decode(record: &[u8]) -> Result<Self, ConfigError> {
+        let header = record.get(..HEADER_SIZE).ok_or(ConfigError::Truncated)?;
+        let version = u16::from_le_bytes([header[0], header[1]]);
+        let len = usize::from(u16::from_le_bytes([header[2], header[3]]));
+
+        let end = HEADER_SIZE + len;
+        let crc = record
+            .get(end..end + CRC_SIZE)
+            .ok_or(ConfigError::Truncated)?;
+        if crc32(&record[..end]).to_le_bytes() != crc {
+            return Err(ConfigError::Checksum);
+        }
+        if version == 0 || version > VERSION {
+            return Err(ConfigError::UnsupportedVersion(version));
+        }
+
+        let mut payload: Value =
+            serde_json::from_slice(&record[HEADER_SIZE..end]).map_err(|_| ConfigError::Invalid)?;
+        for migrate in &MIGRATIONS[usize::from(version) - 1..] {
+            migrate(&mut payload);
+        }
+        serde_json::from_value(payload).map_err(|_| ConfigError::Invalid)
+    }
+}
+
+/// Version 1 had a single network, `wifi`, left empty when not set up.
+fn wifi_to_networks(payload: &mut Value) {
+    let Some(fields) = payload.as_object_mut() else {
+        return;
+    };
+    let networks = match fields.remove("wifi") {
+        Some(wifi) if wifi["ssid"].as_str().is_some_and(|ssid| !ssid.is_empty()) => vec![wifi],
+        _ => vec![],
+    };
+    fields.insert("networks".into(), Value::Array(networks));
+}
+
+/// CRC-32 (IEEE 802.3), as zlib and Ethernet compute it.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
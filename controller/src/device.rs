@@ -0,0 +1,110 @@
+//! Who this controller is on the broker and in Home Assistant.
+//!
+//! The device id is the prefix of every topic, the default client id and
+//! the node id in the discovery topics, so several controllers (one per
+//! room) can share a broker.
+
+use alloc::format;
+use alloc::string::String;
+use core::fmt;
+
+/// Longest device id, leaves room for the topic suffixes.
+pub const MAX_ID_LEN: usize = 32;
+
+/// Discovery prefix Home Assistant listens on.
+const DISCOVERY_PREFIX: &str = "homeassistant";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    id: String,
+    mac: Option<[u8; 6]>,
+}
+
+/// A device id has to be usable as a topic level and as a Home Assistant
+/// node id: 1 to [`MAX_ID_LEN`] ASCII letters, digits, `-` or `_`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct InvalidDeviceId;
+
+impl fmt::Display for InvalidDeviceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "device id must be 1 to {} letters, digits, '-' or '_'",
+            MAX_ID_LEN
+        )
+    }
+}
+
+impl Device {
+    pub fn new(id: &str) -> Result<Self, InvalidDeviceId> {
+        let valid = (1..=MAX_ID_LEN).contains(&id.len())
+            && id
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+        if !valid {
+            return Err(InvalidDeviceId);
+        }
+        Ok(Self {
+            id: id.into(),
+            mac: None,
+        })
+    }
+
+    /// `projector-controller-` and the last three bytes of `mac`, unique per
+    /// board without any setup.
+    pub fn from_mac(mac: [u8; 6]) -> Self {
+        Self {
+            id: format!(
+                "projector-controller-{:02x}{:02x}{:02x}",
+                mac[3], mac[4], mac[5]
+            ),
+            mac: Some(mac),
+        }
+    }
+
+    /// Tell Home Assistant which network interface this is.
+    pub fn with_mac(self, mac: [u8; 6]) -> Self {
+        Self {
+            mac: Some(mac),
+            ..self
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn mac(&self) -> Option<[u8; 6]> {
+        self.mac
+    }
+
+    /// `suffix` below the device's prefix, e.g. `stat/power`.
+    pub fn topic(&self, suffix: &str) -> String {
+        format!("{}/{}", self.id, suffix)
+    }
+
+    /// If `topic` is below the device's prefix, the rest of it.
+    pub fn strip_prefix<'t>(&self, topic: &'t str) -> Option<&'t str> {
+        topic.strip_prefix(self.id.as_str())?.strip_prefix('/')
+    }
+
+    /// Retained `online` while connected, `offline` otherwise.
+    pub fn availability_topic(&self) -> String {
+        self.topic("availability")
+    }
+
+    /// Home Assistant's unique id of one of our entities.
+    pub fn unique_id(&self, entity: &str) -> String {
+        format!("{}_{}", self.id, entity)
+    }
+
+    /// Where to announce `entity` of the given component (`switch`,
+    /// `sensor`...).
+    pub fn discovery_topic(&self, component: &str, entity: &str) -> String {
+        format!(
+            "{}/{}/{}/{}/config",
+            DISCOVERY_PREFIX, component, self.id, entity
+        )
+    }
+}
@@ -0,0 +1,202 @@
+//! A DHCP server for the few phones that join the setup access point.
+//!
+//! Addresses are handed out from a small pool after the board's own, the
+//! board is router and DNS server (see [`crate::dns`]) and is announced as
+//! captive portal (RFC 8910), so phones open the setup page by themselves.
+
+use alloc::format;
+use alloc::vec::Vec;
+use core::net::Ipv4Addr;
+
+/// Clients served at the same time, the oldest lease makes room after
+/// that.
+pub const POOL_SIZE: usize = 8;
+
+/// Lease time in seconds, setup does not take longer.
+const LEASE_TIME: u32 = 3600;
+
+/// Fixed part of a BOOTP message, up to and including the magic cookie.
+const FIXED_SIZE: usize = 240;
+/// Smallest reply BOOTP clients are required to accept.
+const MIN_REPLY_SIZE: usize = 300;
+const MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+
+const BOOTREQUEST: u8 = 1;
+const BOOTREPLY: u8 = 2;
+
+const DISCOVER: u8 = 1;
+const OFFER: u8 = 2;
+const REQUEST: u8 = 3;
+const ACK: u8 = 5;
+const NAK: u8 = 6;
+const RELEASE: u8 = 7;
+
+const OPTION_PAD: u8 = 0;
+const OPTION_SUBNET_MASK: u8 = 1;
+const OPTION_ROUTER: u8 = 3;
+const OPTION_DNS_SERVER: u8 = 6;
+const OPTION_REQUESTED_ADDRESS: u8 = 50;
+const OPTION_LEASE_TIME: u8 = 51;
+const OPTION_MESSAGE_TYPE: u8 = 53;
+const OPTION_SERVER_ID: u8 = 54;
+const OPTION_CAPTIVE_PORTAL: u8 = 114;
+const OPTION_END: u8 = 255;
+
+pub struct DhcpServer {
+    address: Ipv4Addr,
+    /// Client hardware address per pool slot, slot `n` is `address + 1 + n`.
+    leases: [Option<[u8; 6]>; POOL_SIZE],
+    /// Slot to give away next when the pool is full.
+    next_evicted: usize,
+}
+
+impl DhcpServer {
+    /// Serve the /24 around `address`, which is the board's.
+    pub const fn new(address: Ipv4Addr) -> Self {
+        Self {
+            address,
+            leases: [None; POOL_SIZE],
+            next_evicted: 0,
+        }
+    }
+
+    /// The reply to a client message, to be broadcast to port 68. `None`
+    /// for messages that need no answer or are not DHCP at all.
+    pub fn handle(&mut self, message: &[u8]) -> Option<Vec<u8>> {
+        let fixed = message.get(..FIXED_SIZE)?;
+        // Ethernet addresses only
+        if fixed[0] != BOOTREQUEST || fixed[1] != 1 || fixed[2] != 6 {
+            return None;
+        }
+        if fixed[236..] != MAGIC_COOKIE {
+            return None;
+        }
+        let options = &message[FIXED_SIZE..];
+        let mut client = [0; 6];
+        client.copy_from_slice(&fixed[28..34]);
+
+        match option(options, OPTION_MESSAGE_TYPE)? {
+            [DISCOVER] => {
+                let offered = self.lease(client);
+                Some(self.reply(fixed, OFFER, offered))
+            }
+            [REQUEST] => {
+                if option(options, OPTION_SERVER_ID).is_some_and(|id| id != self.address.octets()) {
+                    // the client took another server's offer
+                    self.release(client);
+                    return None;
+                }
+                // REQUESTING and INIT-REBOOT name the address in an option,
+                // RENEWING and REBINDING in ciaddr
+                let requested = match option(options, OPTION_REQUESTED_ADDRESS) {
+                    Some(&[a, b, c, d]) => Ipv4Addr::new(a, b, c, d),
+                    _ => Ipv4Addr::new(fixed[12], fixed[13], fixed[14], fixed[15]),
+                };
+                match self.leased(client) {
+                    Some(leased) if leased == requested => Some(self.reply(fixed, ACK, leased)),
+                    _ => Some(self.reply(fixed, NAK, Ipv4Addr::UNSPECIFIED)),
+                }
+            }
+            [RELEASE] => {
+                self.release(client);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// The address `client` has, or a new one.
+    fn lease(&mut self, client: [u8; 6]) -> Ipv4Addr {
+        if let Some(address) = self.leased(client) {
+            return address;
+        }
+        let slot = match self.leases.iter().position(Option::is_none) {
+            Some(free) => free,
+            None => {
+                let evicted = self.next_evicted;
+                self.next_evicted = (evicted + 1) % POOL_SIZE;
+                evicted
+            }
+        };
+        self.leases[slot] = Some(client);
+        self.slot_address(slot)
+    }
+
+    fn leased(&self, client: [u8; 6]) -> Option<Ipv4Addr> {
+        let slot = self.leases.iter().position(|l| *l == Some(client))?;
+        Some(self.slot_address(slot))
+    }
+
+    fn release(&mut self, client: [u8; 6]) {
+        for lease in &mut self.leases {
+            if *lease == Some(client) {
+                *lease = None;
+            }
+        }
+    }
+
+    fn slot_address(&self, slot: usize) -> Ipv4Addr {
+        let [a, b, c, d] = self.address.octets();
+        Ipv4Addr::new(a, b, c, d.wrapping_add(1 + slot as u8))
+    }
+
+    fn reply(&self, request: &[u8], kind: u8, yiaddr: Ipv4Addr) -> Vec<u8> {
+        let address = self.address.octets();
+        let mut reply = Vec::with_capacity(MIN_REPLY_SIZE);
+        reply.extend_from_slice(&[BOOTREPLY, 1, 6, 0]);
+        // xid, secs (zeroed) and flags
+        reply.extend_from_slice(&request[4..8]);
+        reply.extend_from_slice(&[0, 0]);
+        reply.extend_from_slice(&request[10..12]);
+        // ciaddr
+        reply.extend_from_slice(&[0; 4]);
+        reply.extend_from_slice(&yiaddr.octets());
+        // siaddr
+        reply.extend_from_slice(&address);
+        // giaddr and chaddr
+        reply.extend_from_slice(&request[24..44]);
+        // sname and file
+        reply.resize(236, 0);
+        reply.extend_from_slice(&MAGIC_COOKIE);
+
+        push_option(&mut reply, OPTION_MESSAGE_TYPE, &[kind]);
+        push_option(&mut reply, OPTION_SERVER_ID, &address);
+        if kind != NAK {
+            let portal = format!("http://{}/", self.address);
+            push_option(&mut reply, OPTION_LEASE_TIME, &LEASE_TIME.to_be_bytes());
+            push_option(&mut reply, OPTION_SUBNET_MASK, &[255, 255, 255, 0]);
+            push_option(&mut reply, OPTION_ROUTER, &address);
+            push_option(&mut reply, OPTION_DNS_SERVER, &address);
+            push_option(&mut reply, OPTION_CAPTIVE_PORTAL, portal.as_bytes());
+        }
+        reply.push(OPTION_END);
+        if reply.len() < MIN_REPLY_SIZE {
+            reply.resize(MIN_REPLY_SIZE, OPTION_PAD);
+        }
+        reply
+    }
+}
+
+/// Value of the first option `code`.
+fn option(mut options: &[u8], code: u8) -> Option<&[u8]> {
+    loop {
+        match *options {
+            [OPTION_END, ..] | [] => return None,
+            [OPTION_PAD, ref rest @ ..] => options = rest,
+            [found, len, ref rest @ ..] => {
+                let (value, rest) = rest.split_at_checked(usize::from(len))?;
+                if found == code {
+                    return Some(value);
+                }
+                options = rest;
+            }
+            [_] => return None,
+        }
+    }
+}
+
+fn push_option(message: &mut Vec<u8>, code: u8, value: &[u8]) {
+    message.push(code);
+    message.push(value.len() as u8);
+    message.extend_from_slice(value);
+}
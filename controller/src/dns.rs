@@ -0,0 +1,70 @@
+//! Just enough DNS for the setup access point: every name resolves to the
+//! board, so whatever page a phone opens lands on the portal.
+
+use alloc::vec::Vec;
+use core::net::Ipv4Addr;
+
+const HEADER_SIZE: usize = 12;
+
+const TYPE_A: u16 = 1;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+
+/// Seconds clients may cache the answer, short so they let go of the board
+/// soon after it left setup.
+const TTL: u32 = 60;
+
+/// Answer the standard query in `query` with `address` for an A (or ANY)
+/// question and an empty answer for anything else, so clients do not wait
+/// for AAAA records. `None` for anything that is not a query.
+pub fn catch_all_reply(query: &[u8], address: Ipv4Addr) -> Option<Vec<u8>> {
+    let header = query.get(..HEADER_SIZE)?;
+    let flags = u16::from_be_bytes([header[2], header[3]]);
+    let opcode = (flags >> 11) & 0xf;
+    let questions = u16::from_be_bytes([header[4], header[5]]);
+    if flags & 0x8000 != 0 || opcode != 0 || questions == 0 {
+        return None;
+    }
+
+    // only the first question is answered, nobody sends more
+    let name_end = name_end(query, HEADER_SIZE)?;
+    let question = query.get(HEADER_SIZE..name_end + 4)?;
+    let qtype = u16::from_be_bytes([query[name_end], query[name_end + 1]]);
+    let qclass = u16::from_be_bytes([query[name_end + 2], query[name_end + 3]]);
+    let answer = matches!(qtype, TYPE_A | TYPE_ANY) && qclass == CLASS_IN;
+
+    let mut reply = Vec::with_capacity(HEADER_SIZE + question.len() + 16);
+    reply.extend_from_slice(&header[..2]);
+    // response, authoritative, recursion desired copied, no error
+    reply.extend_from_slice(&(0x8400 | (flags & 0x0100)).to_be_bytes());
+    reply.extend_from_slice(&1u16.to_be_bytes());
+    reply.extend_from_slice(&u16::from(answer).to_be_bytes());
+    reply.extend_from_slice(&[0, 0, 0, 0]);
+    reply.extend_from_slice(question);
+    if answer {
+        // name: pointer to the question's
+        reply.extend_from_slice(&[0xc0, HEADER_SIZE as u8]);
+        reply.extend_from_slice(&TYPE_A.to_be_bytes());
+        reply.extend_from_slice(&CLASS_IN.to_be_bytes());
+        reply.extend_from_slice(&TTL.to_be_bytes());
+        reply.extend_from_slice(&4u16.to_be_bytes());
+        reply.extend_from_slice(&address.octets());
+    }
+    Some(reply)
+}
+
+/// Offset just past the uncompressed name starting at `start`.
+fn name_end(message: &[u8], start: usize) -> Option<usize> {
+    let mut at = start;
+    loop {
+        let len = usize::from(*message.get(at)?);
+        if len == 0 {
+            return Some(at + 1);
+        }
+        // queries have nothing to point back to
+        if len & 0xc0 != 0 {
+            return None;
+        }
+        at += 1 + len;
+    }
+}
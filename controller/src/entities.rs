@@ -0,0 +1,451 @@
+//! The Home Assistant entities of one controller.
+//!
+//! [`ENTITIES`] is the only place an entity is described. Discovery configs,
+//! subscriptions, command dispatch and state topics all follow from it, so
+//! adding an entity is one more line there. Ids are checked for clashes at
+//! compile time; `tests/entities.rs` checks every config fits [`CONFIG_SIZE`]
+//! and every state fits [`STATE_SIZE`].
+
+use alloc::format;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+use projector_protocol::command::{Input, MenuKey, Volume};
+use projector_protocol::query::TemperatureSensor;
+use projector_protocol::ProjectorCommand;
+use serde_json::{json, Value};
+
+use crate::commands::{Command, RAW};
+use crate::device::Device;
+use crate::mqtt::MAX_SUBSCRIPTIONS;
+use crate::poller::StateUpdate;
+use crate::projector::PowerPhase;
+
+/// Buffer for one serialized discovery config.
+pub const CONFIG_SIZE: usize = 1024;
+
+/// Buffer for one state payload.
+pub const STATE_SIZE: usize = 16;
+
+pub type StatePayload = heapless::String<STATE_SIZE>;
+
+/// Home Assistant integration an entity belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Component {
+    Switch,
+    Button,
+    Select,
+    Number,
+    BinarySensor,
+    Sensor,
+}
+
+impl Component {
+    /// Name in the discovery topic.
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Switch => "switch",
+            Self::Button => "button",
+            Self::Select => "select",
+            Self::Number => "number",
+            Self::BinarySensor => "binary_sensor",
+            Self::Sensor => "sensor",
+        }
+    }
+}
+
+/// Which [`StateUpdate`] an entity shows, published on `stat/<id>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Source {
+    /// `ON` while lit or warming up.
+    Power,
+    PowerPhase,
+    Input,
+    /// `ON` while closed.
+    Shutter,
+    Freeze,
+    Volume,
+    LampHours,
+    Temperature(TemperatureSensor),
+    /// The error register in hex.
+    ErrorStatus,
+}
+
+impl Source {
+    /// The state payload, if `update` is about this source.
+    pub fn format(&self, update: &StateUpdate) -> Option<StatePayload> {
+        let mut payload = StatePayload::new();
+        let written = match (self, update) {
+            (Source::Power, StateUpdate::Power(state)) => {
+                write!(payload, "{}", on_off(state.is_on()))
+            }
+            (Source::PowerPhase, StateUpdate::PowerPhase(phase)) => {
+                write!(payload, "{}", phase.name())
+            }
+            (Source::Input, StateUpdate::Input(input)) => write!(payload, "{}", input.name()),
+            (Source::Shutter, StateUpdate::Shutter(closed)) => {
+                write!(payload, "{}", on_off(*closed))
+            }
+            (Source::Freeze, StateUpdate::Freeze(frozen)) => write!(payload, "{}", on_off(*frozen)),
+            (Source::Volume, StateUpdate::Volume(volume)) => {
+                write!(payload, "{}", volume.get())
+            }
+            (Source::LampHours, StateUpdate::LampRuntime(runtime)) => {
+                write!(payload, "{}", runtime.hours)
+            }
+            (Source::Temperature(sensor), StateUpdate::Temperature(from, temperature))
+                if sensor == from =>
+            {
+                write!(payload, "{}", temperature.celsius)
+            }
+            (Source::ErrorStatus, StateUpdate::ErrorStatus(status)) => {
+                write!(payload, "{:04X}", status.0)
+            }
+            // Home Assistant shows "None" as unknown
+            (Source::Power | Source::PowerPhase, StateUpdate::Unreachable) => {
+                write!(payload, "None")
+            }
+            _ => return None,
+        };
+        // STATE_SIZE is checked against the longest values in the tests
+        written.ok()?;
+        Some(payload)
+    }
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "ON"
+    } else {
+        "OFF"
+    }
+}
+
+/// Fixed set of values for a select or an enum sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Options {
+    Inputs,
+    PowerPhases,
+}
+
+impl Options {
+    fn names(&self) -> Vec<&'static str> {
+        match self {
+            Self::Inputs => Input::ALL.iter().map(|i| i.name()).collect(),
+            Self::PowerPhases => PowerPhase::ALL.iter().map(|p| p.name()).collect(),
+        }
+    }
+}
+
+/// One entity: what it is, where its commands go and what it shows.
+#[derive(Debug, Clone, Copy)]
+pub struct Entity {
+    pub component: Component,
+    /// Part of the unique id, discovery topic and `cmd/` and `stat/` topics.
+    pub id: &'static str,
+    /// Shown after "Projector ".
+    pub name: &'static str,
+    pub command: Option<Command>,
+    pub state: Option<Source>,
+    device_class: Option<&'static str>,
+    unit: Option<&'static str>,
+    state_class: Option<&'static str>,
+    options: Option<Options>,
+    range: Option<(u8, u8)>,
+    diagnostic: bool,
+}
+
+impl Entity {
+    pub const fn new(component: Component, id: &'static str, name: &'static str) -> Self {
+        Self {
+            component,
+            id,
+            name,
+            command: None,
+            state: None,
+            device_class: None,
+            unit: None,
+            state_class: None,
+            options: None,
+            range: None,
+            diagnostic: false,
+        }
+    }
+
+    /// `ON`/`OFF` switch that shows `state`.
+    pub const fn switch(
+        id: &'static str,
+        name: &'static str,
+        command: fn(bool) -> ProjectorCommand,
+        state: Source,
+    ) -> Self {
+        Self::new(Component::Switch, id, name)
+            .command(Command::Switch(command))
+            .state(state)
+    }
+
+    /// Button for a key on the remote.
+    pub const fn button(id: &'static str, name: &'static str, key: MenuKey) -> Self {
+        Self::new(Component::Button, id, name).command(Command::Button(ProjectorCommand::Menu(key)))
+    }
+
+    pub const fn sensor(id: &'static str, name: &'static str, state: Source) -> Self {
+        Self::new(Component::Sensor, id, name).state(state)
+    }
+
+    pub const fn command(self, command: Command) -> Self {
+        Self {
+            command: Some(command),
+            ..self
+        }
+    }
+
+    pub const fn state(self, state: Source) -> Self {
+        Self {
+            state: Some(state),
+            ..self
+        }
+    }
+
+    pub const fn device_class(self, device_class: &'static str) -> Self {
+        Self {
+            device_class: Some(device_class),
+            ..self
+        }
+    }
+
+    pub const fn unit(self, unit: &'static str) -> Self {
+        Self {
+            unit: Some(unit),
+            ..self
+        }
+    }
+
+    pub const fn state_class(self, state_class: &'static str) -> Self {
+        Self {
+            state_class: Some(state_class),
+            ..self
+        }
+    }
+
+    pub const fn options(self, options: Options) -> Self {
+        Self {
+            options: Some(options),
+            ..self
+        }
+    }
+
+    /// Bounds of a number, the step is 1.
+    pub const fn range(self, min: u8, max: u8) -> Self {
+        Self {
+            range: Some((min, max)),
+            ..self
+        }
+    }
+
+    /// Listed under diagnostics rather than with the controls.
+    pub const fn diagnostic(self) -> Self {
+        Self {
+            diagnostic: true,
+            ..self
+        }
+    }
+
+    pub fn discovery_topic(&self, device: &Device) -> alloc::string::String {
+        device.discovery_topic(self.component.name(), self.id)
+    }
+
+    /// The discovery config, device block included.
+    pub fn config(&self, device: &Device) -> Value {
+        let mut config = json!({
+            "name": format!("Projector {}", self.name),
+            "unique_id": device.unique_id(self.id),
+            "availability_topic": device.availability_topic(),
+            "device": device_info(device),
+        });
+        if self.command.is_some() {
+            config["command_topic"] = device.topic(&format!("cmd/{}", self.id)).into();
+        }
+        if self.state.is_some() {
+            config["state_topic"] = device.topic(&format!("stat/{}", self.id)).into();
+        }
+        match self.component {
+            Component::Switch => {
+                config["payload_on"] = "ON".into();
+                config["payload_off"] = "OFF".into();
+                config["state_on"] = "ON".into();
+                config["state_off"] = "OFF".into();
+                config["optimistic"] = false.into();
+            }
+            Component::BinarySensor => {
+                config["payload_on"] = "ON".into();
+                config["payload_off"] = "OFF".into();
+            }
+            _ => {}
+        }
+        if let Some(options) = self.options {
+            config["options"] = options.names().into();
+        }
+        if let Some((min, max)) = self.range {
+            config["min"] = min.into();
+            config["max"] = max.into();
+            config["step"] = 1.into();
+        }
+        if let Some(device_class) = self.device_class {
+            config["device_class"] = device_class.into();
+        }
+        if let Some(unit) = self.unit {
+            config["unit_of_measurement"] = unit.into();
+        }
+        if let Some(state_class) = self.state_class {
+            config["state_class"] = state_class.into();
+        }
+        if self.diagnostic {
+            config["entity_category"] = "diagnostic".into();
+        }
+        config
+    }
+}
+
+fn power(on: bool) -> ProjectorCommand {
+    if on {
+        ProjectorCommand::PowerOn
+    } else {
+        ProjectorCommand::PowerOff
+    }
+}
+
+pub const ENTITIES: &[Entity] = &[
+    Entity::switch("power", "Power", power, Source::Power),
+    Entity::switch(
+        "shutter",
+        "Shutter",
+        ProjectorCommand::Shutter,
+        Source::Shutter,
+    ),
+    Entity::switch("freeze", "Freeze", ProjectorCommand::Freeze, Source::Freeze),
+    Entity::button("menu", "Menu", MenuKey::Menu),
+    Entity::button("enter", "Enter", MenuKey::Enter),
+    Entity::button("up", "Up", MenuKey::Up),
+    Entity::button("down", "Down", MenuKey::Down),
+    Entity::button("left", "Left", MenuKey::Left),
+    Entity::button("right", "Right", MenuKey::Right),
+    Entity::button("back", "Back", MenuKey::Back),
+    Entity::new(Component::Select, "input", "Input")
+        .command(Command::Input)
+        .state(Source::Input)
+        .options(Options::Inputs),
+    Entity::new(Component::Number, "volume", "Volume")
+        .command(Command::Volume)
+        .state(Source::Volume)
+        .range(0, Volume::MAX),
+    // actual power state, unlike the switch it can't be flipped
+    Entity::new(Component::BinarySensor, "status", "Status").state(Source::Power),
+    // shows warm-up and cool-down
+    Entity::sensor("power_state", "Power State", Source::PowerPhase)
+        .device_class("enum")
+        .options(Options::PowerPhases),
+    Entity::sensor("lamp_hours", "Lamp Hours", Source::LampHours)
+        .device_class("duration")
+        .unit("h")
+        .state_class("total_increasing"),
+    Entity::sensor(
+        "intake_temperature",
+        "Intake Temperature",
+        Source::Temperature(TemperatureSensor::Intake),
+    )
+    .device_class("temperature")
+    .unit("°C")
+    .state_class("measurement")
+    .diagnostic(),
+    Entity::sensor(
+        "exhaust_temperature",
+        "Exhaust Temperature",
+        Source::Temperature(TemperatureSensor::Exhaust),
+    )
+    .device_class("temperature")
+    .unit("°C")
+    .state_class("measurement")
+    .diagnostic(),
+    // error and warning register
+    Entity::sensor("error_status", "Error Status", Source::ErrorStatus).diagnostic(),
+];
+
+const _: () = check(ENTITIES);
+
+/// Fails the build if two entities share an id, an id clashes with the raw
+/// command topic, an entity lacks the command or state its component needs,
+/// or there are more command topics than a session can subscribe to.
+const fn check(entities: &[Entity]) {
+    // cmd/raw and cmd/json, see `commands::names` and `mqtt::command_topics`
+    let mut command_topics = 2;
+    let mut i = 0;
+    while i < entities.len() {
+        let entity = &entities[i];
+        if entity.command.is_some() {
+            command_topics += 1;
+        }
+        assert!(!str_eq(entity.id, RAW), "entity id clashes with cmd/raw");
+        let needs_command = matches!(
+            entity.component,
+            Component::Switch | Component::Button | Component::Select | Component::Number
+        );
+        assert!(
+            entity.command.is_some() == needs_command,
+            "only switches, buttons, selects and numbers take commands"
+        );
+        let needs_state = !matches!(entity.component, Component::Button);
+        assert!(
+            entity.state.is_some() == needs_state,
+            "every entity but buttons shows a state"
+        );
+        let mut j = i + 1;
+        while j < entities.len() {
+            assert!(!str_eq(entity.id, entities[j].id), "duplicate entity id");
+            j += 1;
+        }
+        i += 1;
+    }
+    assert!(
+        command_topics <= MAX_SUBSCRIPTIONS,
+        "more command topics than mqtt::MAX_SUBSCRIPTIONS"
+    );
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// The `device` block that groups all entities into one device in Home
+/// Assistant.
+fn device_info(device: &Device) -> Value {
+    let mut info = json!({
+        "identifiers": [device.id()],
+        "name": device.id(),
+        "manufacturer": "Panasonic",
+        "model": "PT-AH1000E",
+        "sw_version": env!("CARGO_PKG_VERSION"),
+    });
+    if let Some(mac) = device.mac() {
+        let mac = format!(
+            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+            mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+        );
+        info["connections"] = json!([["mac", mac]]);
+    }
+    info
+}
@@ -0,0 +1,82 @@
+//! Logging macros that go to defmt on the firmware and to `log` on the host.
+//!
+//! Only `{}` and `{:?}` placeholders work with both; wrap values that are
+//! `Debug` but not `defmt::Format` in [`as_debug`].
+#![allow(unused_macros)]
+
+#[cfg(all(feature = "defmt", feature = "log"))]
+compile_error!("features `defmt` and `log` are mutually exclusive");
+
+macro_rules! trace {
+    ($($x:tt)*) => {{
+        #[cfg(feature = "defmt")]
+        ::defmt::trace!($($x)*);
+        #[cfg(feature = "log")]
+        ::log::trace!($($x)*);
+        #[cfg(not(any(feature = "defmt", feature = "log")))]
+        discard!($($x)*);
+    }};
+}
+
+macro_rules! debug {
+    ($($x:tt)*) => {{
+        #[cfg(feature = "defmt")]
+        ::defmt::debug!($($x)*);
+        #[cfg(feature = "log")]
+        ::log::debug!($($x)*);
+        #[cfg(not(any(feature = "defmt", feature = "log")))]
+        discard!($($x)*);
+    }};
+}
+
+macro_rules! info {
+    ($($x:tt)*) => {{
+        #[cfg(feature = "defmt")]
+        ::defmt::info!($($x)*);
+        #[cfg(feature = "log")]
+        ::log::info!($($x)*);
+        #[cfg(not(any(feature = "defmt", feature = "log")))]
+        discard!($($x)*);
+    }};
+}
+
+macro_rules! warn {
+    ($($x:tt)*) => {{
+        #[cfg(feature = "defmt")]
+        ::defmt::warn!($($x)*);
+        #[cfg(feature = "log")]
+        ::log::warn!($($x)*);
+        #[cfg(not(any(feature = "defmt", feature = "log")))]
+        discard!($($x)*);
+    }};
+}
+
+macro_rules! error {
+    ($($x:tt)*) => {{
+        #[cfg(feature = "defmt")]
+        ::defmt::error!($($x)*);
+        #[cfg(feature = "log")]
+        ::log::error!($($x)*);
+        #[cfg(not(any(feature = "defmt", feature = "log")))]
+        discard!($($x)*);
+    }};
+}
+
+/// Evaluate the arguments so they count as used.
+macro_rules! discard {
+    ($fmt:literal $(, $x:expr)* $(,)?) => {{
+        let _ = ($(&$x),*);
+    }};
+}
+
+/// Log a value through its `Debug` impl.
+#[cfg(feature = "defmt")]
+pub(crate) fn as_debug<T: core::fmt::Debug + ?Sized>(value: &T) -> defmt::Debug2Format<'_, T> {
+    defmt::Debug2Format(value)
+}
+
+/// Log a value through its `Debug` impl.
+#[cfg(not(feature = "defmt"))]
+pub(crate) fn as_debug<T: core::fmt::Debug + ?Sized>(value: &T) -> &T {
+    value
+}
@@ -0,0 +1,129 @@
+//! `cmd/json`: commands and queries for scripts, answered in JSON.
+//!
+//! A request either names a command topic and the payload for it, or an
+//! entity whose state to ask the projector for right away:
+//!
+//! ```text
+//! {"id": 1, "command": "input", "value": "HDMI1"}
+//! {"id": 2, "query": "lamp_hours"}
+//! ```
+//!
+//! The response echoes `id` and carries the result or what went wrong:
+//!
+//! ```text
+//! {"id": 1, "ok": true, "result": "OK"}
+//! {"id": 2, "ok": true, "result": "1200"}
+//! {"id": 3, "ok": false, "error": "unknown command"}
+//! ```
+//!
+//! Results are the payloads of the `result/<name>` and `stat/<name>`
+//! topics. The response goes to the request's MQTT v5 response topic, with
+//! its correlation data, or to `result/json` without one.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use projector_protocol::query::{
+    ErrorStatusQuery, FreezeQuery, InputQuery, Lamp, LampRuntimeQuery, PowerQuery, ShutterQuery,
+    TemperatureQuery, VolumeQuery,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::commands::{self, Outcome};
+use crate::entities::{Source, ENTITIES};
+use crate::poller::StateUpdate;
+use crate::projector::{PowerPhase, ProjectorError, Requester};
+
+/// Name of the command topic, `cmd/json`.
+pub const NAME: &str = "json";
+
+#[derive(Deserialize)]
+struct JsonRequest {
+    #[serde(default)]
+    id: Value,
+    command: Option<String>,
+    /// Payload for `command`, strings as they are, numbers as digits and
+    /// booleans as `ON`/`OFF`.
+    #[serde(default)]
+    value: Value,
+    query: Option<String>,
+}
+
+/// Carry out the request in `payload`. Returns the response, and the
+/// outcome if it was a command.
+pub async fn handle(projector: &mut Requester, payload: &[u8]) -> (Value, Option<Outcome>) {
+    let Ok(request) = serde_json::from_slice::<JsonRequest>(payload) else {
+        warn!("Invalid JSON request: {:?}", payload);
+        return (failure(Value::Null, "invalid request".into()), None);
+    };
+    let id = request.id;
+
+    match (request.command, request.query) {
+        (Some(command), None) => {
+            let value = match request.value {
+                Value::Null => Vec::new(),
+                Value::String(text) => text.into_bytes(),
+                Value::Bool(on) => (if on { "ON" } else { "OFF" }).into(),
+                other => other.to_string().into_bytes(),
+            };
+            let Some(outcome) = commands::run(projector, &command, &value).await else {
+                return (failure(id, "unknown command".into()), None);
+            };
+            let response = match outcome.result() {
+                Ok(result) => success(id, result),
+                Err(error) => failure(id, error),
+            };
+            (response, Some(outcome))
+        }
+        (None, Some(entity)) => {
+            let Some(source) = ENTITIES
+                .iter()
+                .find(|e| e.id == entity)
+                .and_then(|e| e.state)
+            else {
+                return (failure(id, "unknown query".into()), None);
+            };
+            let response = match query(projector, source).await {
+                Ok(update) => match source.format(&update) {
+                    Some(state) => success(id, state.as_str().into()),
+                    None => failure(id, "unexpected reply".into()),
+                },
+                Err(e) => failure(id, e.to_string()),
+            };
+            (response, None)
+        }
+        _ => (failure(id, "expected one of command or query".into()), None),
+    }
+}
+
+fn success(id: Value, result: String) -> Value {
+    json!({ "id": id, "ok": true, "result": result })
+}
+
+fn failure(id: Value, error: String) -> Value {
+    json!({ "id": id, "ok": false, "error": error })
+}
+
+/// Ask the projector for `source` now, rather than waiting for the poller.
+async fn query(projector: &mut Requester, source: Source) -> Result<StateUpdate, ProjectorError> {
+    Ok(match source {
+        Source::Power => StateUpdate::Power(projector.query(PowerQuery).await?),
+        Source::PowerPhase => {
+            let power = projector.query(PowerQuery).await?;
+            let errors = projector.query(ErrorStatusQuery).await.ok();
+            StateUpdate::PowerPhase(PowerPhase::from_status(power, errors))
+        }
+        Source::Input => StateUpdate::Input(projector.query(InputQuery).await?),
+        Source::Shutter => StateUpdate::Shutter(projector.query(ShutterQuery).await?),
+        Source::Freeze => StateUpdate::Freeze(projector.query(FreezeQuery).await?),
+        Source::Volume => StateUpdate::Volume(projector.query(VolumeQuery).await?),
+        Source::LampHours => {
+            StateUpdate::LampRuntime(projector.query(LampRuntimeQuery(Lamp::Lamp1)).await?)
+        }
+        Source::Temperature(sensor) => {
+            StateUpdate::Temperature(sensor, projector.query(TemperatureQuery(sensor)).await?)
+        }
+        Source::ErrorStatus => StateUpdate::ErrorStatus(projector.query(ErrorStatusQuery).await?),
+    })
+}
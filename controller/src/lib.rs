@@ -0,0 +1,37 @@
+//! Projector controller application logic.
+//!
+//! Everything between the broker and the serial port that does not depend on
+//! the platform: the projector request queue, state polling and the MQTT /
+//! Home Assistant front-end. The ESP32 firmware and the Linux host build each
+//! plug in their serial port (any `embedded_io_async` port) and broker
+//! connection ([`mqtt::Connector`]) and run the same code.
+//!
+//! The firmware's stored settings ([`config`]) and its setup portal
+//! ([`portal`], [`dhcp`], [`dns`]) are here too, as are [`mdns`] and its
+//! MQTT packets ([`mqtt_packet`]), so they can be tested on the host.
+//!
+//! The async functions here never return and are meant to be spawned as
+//! tasks by the platform.
+#![no_std]
+
+extern crate alloc;
+
+#[macro_use]
+mod fmt;
+
+pub mod backoff;
+pub mod commands;
+pub mod config;
+pub mod device;
+pub mod dhcp;
+pub mod dns;
+pub mod entities;
+pub mod json;
+pub mod mdns;
+pub mod mqtt;
+pub mod mqtt_packet;
+pub mod outbox;
+pub mod poller;
+pub mod portal;
+pub mod projector;
+pub mod wifi;
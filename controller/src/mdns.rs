@@ -0,0 +1,413 @@
+//! Multicast DNS (RFC 6762) and DNS-SD (RFC 6763), enough to be found as
+//! `<device id>.local` with the services the board offers, and to find an
+//! MQTT broker that advertises `_mqtt._tcp` when none is configured.
+//!
+//! Only IPv4 records, the board's one address and the first answer are
+//! handled. No probing for conflicts either, the device id is meant to be
+//! unique on its network already.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::net::Ipv4Addr;
+
+/// Port every mDNS responder listens on.
+pub const PORT: u16 = 5353;
+
+/// Group queries and answers are sent to.
+pub const GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+
+/// Service type brokers advertise.
+pub const MQTT: &str = "_mqtt._tcp";
+
+const HEADER_SIZE: usize = 12;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+const CLASS_ANY: u16 = 255;
+/// Set on the class of records only this responder owns.
+const CACHE_FLUSH: u16 = 0x8000;
+
+/// TTLs RFC 6762 recommends: records with a host name short, the others
+/// long.
+const HOST_TTL: u32 = 120;
+const OTHER_TTL: u32 = 75 * 60;
+/// Most a legacy (one-shot) querier may cache.
+const LEGACY_TTL: u32 = 10;
+
+/// Lists the service types on the network.
+const SERVICES: &str = "_services._dns-sd._udp.local";
+
+/// A service the board offers, advertised under the device id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Service<'a> {
+    /// Type and protocol, `_http._tcp`.
+    pub kind: &'a str,
+    pub port: u16,
+    /// `key=value` pairs.
+    pub txt: &'a [&'a str],
+}
+
+/// Answers queries for the host name `<host>.local` and `services`.
+#[derive(Debug, Clone, Copy)]
+pub struct Responder<'a> {
+    host: &'a str,
+    services: &'a [Service<'a>],
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Record<'a> {
+    Address,
+    ServiceTypes(&'a Service<'a>),
+    Instance(&'a Service<'a>),
+    Srv(&'a Service<'a>),
+    Txt(&'a Service<'a>),
+}
+
+impl<'a> Responder<'a> {
+    /// `host` is the device id, a valid DNS label.
+    pub const fn new(host: &'a str, services: &'a [Service<'a>]) -> Self {
+        Self { host, services }
+    }
+
+    /// Every record, sent unasked when the board joins a network so caches
+    /// pick up a new address.
+    pub fn announcement(&self, address: Ipv4Addr) -> Vec<u8> {
+        let mut answers = Vec::from([Record::Address]);
+        for service in self.services {
+            answers.extend([
+                Record::ServiceTypes(service),
+                Record::Instance(service),
+                Record::Srv(service),
+                Record::Txt(service),
+            ]);
+        }
+        self.response(0, &[], &answers, &[], address, false)
+    }
+
+    /// The response to `query`, if it asks for any of our records.
+    ///
+    /// `legacy` is for queries that did not come from port [`PORT`]: they
+    /// are from a plain resolver that expects its id and question back, and
+    /// the response goes to it alone instead of to [`GROUP`].
+    pub fn reply(&self, query: &[u8], address: Ipv4Addr, legacy: bool) -> Option<Vec<u8>> {
+        let header = query.get(..HEADER_SIZE)?;
+        let flags = u16::from_be_bytes([header[2], header[3]]);
+        // responses, and anything but standard queries
+        if flags & 0xf800 != 0 {
+            return None;
+        }
+        let questions = u16::from_be_bytes([header[4], header[5]]);
+
+        let mut answers = Vec::new();
+        let mut at = HEADER_SIZE;
+        for _ in 0..questions {
+            let (name, end) = read_name(query, at)?;
+            let fields = query.get(end..end + 4)?;
+            let qtype = u16::from_be_bytes([fields[0], fields[1]]);
+            // the top bit asks for a unicast response, multicast is fine too
+            let qclass = u16::from_be_bytes([fields[2], fields[3]]) & !CACHE_FLUSH;
+            at = end + 4;
+            if qclass == CLASS_IN || qclass == CLASS_ANY {
+                self.matching(&name, qtype, &mut answers);
+            }
+        }
+        if answers.is_empty() {
+            return None;
+        }
+
+        // what the querier will ask next anyway
+        let mut additional = Vec::new();
+        for answer in &answers {
+            let follow: &[Record] = match answer {
+                Record::Instance(service) => &[Record::Srv(service), Record::Txt(service)],
+                Record::Srv(_) => &[Record::Address],
+                _ => &[],
+            };
+            for record in follow {
+                if !answers.contains(record) && !additional.contains(record) {
+                    additional.push(record.clone());
+                }
+            }
+        }
+        if additional.iter().any(|r| matches!(r, Record::Srv(_)))
+            && !answers.contains(&Record::Address)
+            && !additional.contains(&Record::Address)
+        {
+            additional.push(Record::Address);
+        }
+
+        let (id, question) = if legacy {
+            (u16::from_be_bytes([header[0], header[1]]), &query[..at])
+        } else {
+            (0, &[][..])
+        };
+        Some(self.response(id, question, &answers, &additional, address, legacy))
+    }
+
+    fn matching(&self, name: &str, qtype: u16, answers: &mut Vec<Record<'a>>) {
+        let mut add = |record: Record<'a>, rtype: u16| {
+            if (qtype == rtype || qtype == TYPE_ANY) && !answers.contains(&record) {
+                answers.push(record);
+            }
+        };
+
+        if name.eq_ignore_ascii_case(&self.host_name()) {
+            add(Record::Address, TYPE_A);
+        }
+        for service in self.services {
+            if name.eq_ignore_ascii_case(SERVICES) {
+                add(Record::ServiceTypes(service), TYPE_PTR);
+            }
+            if name.eq_ignore_ascii_case(&type_name(service)) {
+                add(Record::Instance(service), TYPE_PTR);
+            }
+            if name.eq_ignore_ascii_case(&self.instance_name(service)) {
+                add(Record::Srv(service), TYPE_SRV);
+                add(Record::Txt(service), TYPE_TXT);
+            }
+        }
+    }
+
+    fn response(
+        &self,
+        id: u16,
+        question: &[u8],
+        answers: &[Record],
+        additional: &[Record],
+        address: Ipv4Addr,
+        legacy: bool,
+    ) -> Vec<u8> {
+        let questions = if question.is_empty() {
+            0
+        } else {
+            u16::from_be_bytes([question[4], question[5]])
+        };
+        let mut message = Vec::with_capacity(512);
+        message.extend_from_slice(&id.to_be_bytes());
+        // response, authoritative
+        message.extend_from_slice(&0x8400u16.to_be_bytes());
+        message.extend_from_slice(&questions.to_be_bytes());
+        message.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+        message.extend_from_slice(&0u16.to_be_bytes());
+        message.extend_from_slice(&(additional.len() as u16).to_be_bytes());
+        message.extend_from_slice(question.get(HEADER_SIZE..).unwrap_or_default());
+        for record in answers.iter().chain(additional) {
+            self.write_record(&mut message, record, address, legacy);
+        }
+        message
+    }
+
+    fn write_record(
+        &self,
+        message: &mut Vec<u8>,
+        record: &Record,
+        address: Ipv4Addr,
+        legacy: bool,
+    ) {
+        let (name, rtype, ttl, unique) = match record {
+            Record::Address => (self.host_name(), TYPE_A, HOST_TTL, true),
+            Record::ServiceTypes(_) => (SERVICES.into(), TYPE_PTR, OTHER_TTL, false),
+            Record::Instance(service) => (type_name(service), TYPE_PTR, OTHER_TTL, false),
+            Record::Srv(service) => (self.instance_name(service), TYPE_SRV, HOST_TTL, true),
+            Record::Txt(service) => (self.instance_name(service), TYPE_TXT, OTHER_TTL, true),
+        };
+        write_name(message, &name);
+        message.extend_from_slice(&rtype.to_be_bytes());
+        // legacy resolvers do not know the cache flush bit
+        let class = if unique && !legacy {
+            CLASS_IN | CACHE_FLUSH
+        } else {
+            CLASS_IN
+        };
+        message.extend_from_slice(&class.to_be_bytes());
+        let ttl = if legacy { ttl.min(LEGACY_TTL) } else { ttl };
+        message.extend_from_slice(&ttl.to_be_bytes());
+
+        let mut data = Vec::new();
+        match record {
+            Record::Address => data.extend_from_slice(&address.octets()),
+            Record::ServiceTypes(service) => write_name(&mut data, &type_name(service)),
+            Record::Instance(service) => write_name(&mut data, &self.instance_name(service)),
+            Record::Srv(service) => {
+                // priority, weight
+                data.extend_from_slice(&[0, 0, 0, 0]);
+                data.extend_from_slice(&service.port.to_be_bytes());
+                write_name(&mut data, &self.host_name());
+            }
+            Record::Txt(service) => {
+                for entry in service.txt {
+                    data.push(entry.len().min(255) as u8);
+                    data.extend_from_slice(&entry.as_bytes()[..entry.len().min(255)]);
+                }
+                // an empty TXT record still has one (empty) string
+                if service.txt.is_empty() {
+                    data.push(0);
+                }
+            }
+        }
+        message.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        message.extend_from_slice(&data);
+    }
+
+    fn host_name(&self) -> String {
+        alloc::format!("{}.local", self.host)
+    }
+
+    fn instance_name(&self, service: &Service) -> String {
+        alloc::format!("{}.{}.local", self.host, service.kind)
+    }
+}
+
+fn type_name(service: &Service) -> String {
+    alloc::format!("{}.local", service.kind)
+}
+
+/// A broker found by [`browse_query`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Found {
+    /// Host name of the broker, `.local` included.
+    pub host: String,
+    pub port: u16,
+    /// The addresses sent along, if any; ask [`address_query`] otherwise.
+    pub addresses: Vec<Ipv4Addr>,
+}
+
+/// A one-shot query for instances of `kind` (`_mqtt._tcp`). Sent from any
+/// port but [`PORT`], responders answer the sender directly.
+pub fn browse_query(kind: &str, id: u16) -> Vec<u8> {
+    query(&alloc::format!("{}.local", kind), TYPE_PTR, id)
+}
+
+/// A one-shot query for the addresses of `host`, a `.local` name.
+pub fn address_query(host: &str, id: u16) -> Vec<u8> {
+    query(host, TYPE_A, id)
+}
+
+fn query(name: &str, qtype: u16, id: u16) -> Vec<u8> {
+    let mut query = Vec::with_capacity(HEADER_SIZE + name.len() + 6);
+    query.extend_from_slice(&id.to_be_bytes());
+    query.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 0, 0, 0]);
+    write_name(&mut query, name);
+    query.extend_from_slice(&qtype.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    query
+}
+
+/// The first instance of `kind` in `response` to a [`browse_query`], with
+/// its SRV record. `None` if the response does not say where it is.
+pub fn parse_browse(response: &[u8], kind: &str) -> Option<Found> {
+    let records = records(response)?;
+    let type_name = alloc::format!("{}.local", kind);
+    let instance = records.iter().find_map(|record| {
+        (record.rtype == TYPE_PTR && record.name.eq_ignore_ascii_case(&type_name))
+            .then(|| read_name(response, record.data).map(|(name, _)| name))
+            .flatten()
+    })?;
+    let (host, port) = records.iter().find_map(|record| {
+        if record.rtype != TYPE_SRV || !record.name.eq_ignore_ascii_case(&instance) {
+            return None;
+        }
+        let fields = response.get(record.data..record.data + 6)?;
+        let (host, _) = read_name(response, record.data + 6)?;
+        Some((host, u16::from_be_bytes([fields[4], fields[5]])))
+    })?;
+    let addresses = addresses(response, &host);
+    Some(Found {
+        host,
+        port,
+        addresses,
+    })
+}
+
+/// The A records for `host` in `response`, in the order sent.
+pub fn addresses(response: &[u8], host: &str) -> Vec<Ipv4Addr> {
+    records(response)
+        .unwrap_or_default()
+        .iter()
+        .filter(|record| record.rtype == TYPE_A && record.name.eq_ignore_ascii_case(host))
+        .filter_map(|record| {
+            let octets: [u8; 4] = response
+                .get(record.data..record.data + 4)?
+                .try_into()
+                .ok()?;
+            Some(Ipv4Addr::from(octets))
+        })
+        .collect()
+}
+
+struct Parsed {
+    name: String,
+    rtype: u16,
+    /// Offset of the record data in the message.
+    data: usize,
+}
+
+/// All resource records of a response, answers and additional alike.
+fn records(response: &[u8]) -> Option<Vec<Parsed>> {
+    let header = response.get(..HEADER_SIZE)?;
+    if header[2] & 0x80 == 0 {
+        return None;
+    }
+    let count = |at: usize| usize::from(u16::from_be_bytes([header[at], header[at + 1]]));
+
+    let mut at = HEADER_SIZE;
+    for _ in 0..count(4) {
+        at = read_name(response, at)?.1 + 4;
+    }
+    let mut records = Vec::new();
+    for _ in 0..count(6) + count(8) + count(10) {
+        let (name, end) = read_name(response, at)?;
+        let fields = response.get(end..end + 10)?;
+        let len = usize::from(u16::from_be_bytes([fields[8], fields[9]]));
+        records.push(Parsed {
+            name,
+            rtype: u16::from_be_bytes([fields[0], fields[1]]),
+            data: end + 10,
+        });
+        at = end + 10 + len;
+        response.get(..at)?;
+    }
+    Some(records)
+}
+
+/// The dotted name starting at `start`, following compression pointers,
+/// and the offset just past it.
+fn read_name(message: &[u8], start: usize) -> Option<(String, usize)> {
+    let mut name = String::new();
+    let mut at = start;
+    let mut end = None;
+    // pointers only ever go back; this many cannot all be real
+    for _ in 0..64 {
+        let len = usize::from(*message.get(at)?);
+        match len & 0xc0 {
+            0 if len == 0 => return Some((name, end.unwrap_or(at + 1))),
+            0 => {
+                let label = message.get(at + 1..at + 1 + len)?;
+                if !name.is_empty() {
+                    name.push('.');
+                }
+                name.push_str(core::str::from_utf8(label).ok()?);
+                at += 1 + len;
+            }
+            0xc0 => {
+                let pointer = ((len & 0x3f) << 8) | usize::from(*message.get(at + 1)?);
+                end.get_or_insert(at + 2);
+                at = pointer;
+            }
+            _ => return None,
+        }
+    }
+    None
+}
+
+fn write_name(message: &mut Vec<u8>, name: &str) {
+    for label in name.split('.').filter(|label| !label.is_empty()) {
+        message.push(label.len() as u8);
+        message.extend_from_slice(label.as_bytes());
+    }
+    message.push(0);
+}
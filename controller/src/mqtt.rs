@@ -0,0 +1,495 @@
+//! MQTT front-end: Home Assistant discovery, command topics and state
+//! publishing.
+//!
+//! The broker connection is hidden behind [`Connector`] and [`Session`], so
+//! the same logic runs over embassy-net and [`mqtt_packet`](crate::mqtt_packet)
+//! on the ESP32 and over a std MQTT client in the host build.
+//!
+//! [`run`] never gives up on the broker: a failed or lost connection is
+//! dropped and retried with a growing, jittered delay, and every new session
+//! announces the entities and subscribes again.
+//!
+//! Topics, client id and Home Assistant ids all derive from the
+//! [`Device`] id, so several controllers can share a broker.
+//!
+//! Availability is left to the broker: connectors register a retained Last
+//! Will of [`OFFLINE`] on [`Device::availability_topic`] and ask for
+//! [`KEEP_ALIVE`], so a controller that crashes or drops off the network
+//! turns `offline` without any help. [`SHUTDOWN`] says goodbye properly
+//! before a reboot.
+//!
+//! States, availability and discovery go out with QoS 1 and commands are
+//! subscribed with QoS 1. States pass through the [`Outbox`], so changes
+//! while the broker is unreachable are published on the next session.
+
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use embassy_futures::select::{select3, select4, Either3, Either4};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Ticker, Timer};
+use serde_json_core::to_slice;
+
+use crate::backoff::Backoff;
+use crate::commands::{self, Outcome};
+use crate::device::Device;
+use crate::entities::{Entity, CONFIG_SIZE, ENTITIES};
+use crate::fmt::as_debug;
+use crate::json;
+use crate::outbox::Outbox;
+use crate::poller::{self, StateUpdate};
+use crate::projector::{ProjectorError, ReplySignal, Requester};
+
+pub const ONLINE: &[u8] = b"online";
+
+/// Published on shutdown and registered as the Last Will.
+pub const OFFLINE: &[u8] = b"offline";
+
+/// Keep-alive interval to connect with. The broker publishes the will when
+/// it hears nothing for one and a half times this.
+pub const KEEP_ALIVE: Duration = Duration::from_secs(60);
+
+/// Most topics a session subscribes to, see [`command_topics`]. Platforms
+/// with fixed-size subscribe buffers size them from this; `entities` checks
+/// at compile time that the command topics fit.
+pub const MAX_SUBSCRIPTIONS: usize = 16;
+
+/// Go `offline`, disconnect and return from [`run`], e.g. before a reboot.
+pub static SHUTDOWN: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Delivery guarantee of a publish.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum QoS {
+    /// Fire and forget: replies and reports nobody asks for again.
+    AtMostOnce,
+    /// Acknowledged by the broker: states, availability and discovery.
+    AtLeastOnce,
+}
+
+/// A message received on a subscribed topic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Message<'a> {
+    pub topic: &'a str,
+    pub payload: &'a [u8],
+    /// MQTT v5 response topic, where the sender wants the reply.
+    pub response_topic: Option<&'a str>,
+    /// MQTT v5 correlation data, handed back with the reply.
+    pub correlation_data: Option<&'a [u8]>,
+}
+
+impl<'a> Message<'a> {
+    pub fn new(topic: &'a str, payload: &'a [u8]) -> Self {
+        Self {
+            topic,
+            payload,
+            response_topic: None,
+            correlation_data: None,
+        }
+    }
+
+    /// Add the MQTT v5 request/response properties.
+    pub fn with_response(
+        self,
+        response_topic: Option<&'a str>,
+        correlation_data: Option<&'a [u8]>,
+    ) -> Self {
+        Self {
+            response_topic,
+            correlation_data,
+            ..self
+        }
+    }
+}
+
+/// An established connection to the broker.
+#[allow(async_fn_in_trait)]
+pub trait Session {
+    type Error: core::fmt::Debug;
+
+    /// Publish `payload` on `topic`, to be kept by the broker if `retain`.
+    async fn publish(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        qos: QoS,
+        retain: bool,
+    ) -> Result<(), Self::Error>;
+
+    /// Publish the reply to a request, not retained, with the request's
+    /// MQTT v5 correlation data. Clients without v5 properties leave it out.
+    async fn reply(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        correlation_data: Option<&[u8]>,
+    ) -> Result<(), Self::Error> {
+        let _ = correlation_data;
+        self.publish(topic, payload, QoS::AtMostOnce, false).await
+    }
+
+    /// Subscribe to `topics` with QoS 1, commands are not to get lost.
+    async fn subscribe(&mut self, topics: &[&str]) -> Result<(), Self::Error>;
+
+    /// Wait for the next message on one of the subscribed topics.
+    async fn receive(&mut self) -> Result<Message<'_>, Self::Error>;
+
+    /// Called every half [`KEEP_ALIVE`]. Clients that keep the connection
+    /// alive on their own have nothing to do here.
+    async fn ping(&mut self) -> Result<(), Self::Error>;
+
+    /// Close the session cleanly, so the broker does not publish the will.
+    async fn disconnect(&mut self) -> Result<(), Self::Error>;
+}
+
+/// Opens sessions to the broker: name lookup, TCP and MQTT connect, with
+/// [`KEEP_ALIVE`] and a retained will of [`OFFLINE`] on the device's
+/// [availability topic](Device::availability_topic).
+#[allow(async_fn_in_trait)]
+pub trait Connector {
+    type Error: core::fmt::Debug;
+    type Session<'a>: Session<Error = Self::Error>
+    where
+        Self: 'a;
+
+    async fn connect(&mut self) -> Result<Self::Session<'_>, Self::Error>;
+}
+
+/// Status lights, or whatever the platform has instead.
+#[allow(async_fn_in_trait)]
+pub trait Indicator {
+    /// The session is up and the entities are announced.
+    async fn connected(&mut self) {}
+
+    /// The connection failed or was lost, a reconnect is pending.
+    async fn disconnected(&mut self) {}
+
+    /// A message arrived on a command topic.
+    async fn activity(&mut self) {}
+}
+
+impl Indicator for () {}
+
+/// First reconnect delay, doubling on every failed attempt.
+const RECONNECT_MIN: Duration = Duration::from_secs(1);
+
+/// Reconnect delays stop growing here.
+const RECONNECT_MAX: Duration = Duration::from_secs(60);
+
+/// Keep a session to the broker: connect, announce the entities to Home
+/// Assistant and serve the command topics, and start over with a fresh
+/// connection whenever that fails. Only returns on [`SHUTDOWN`].
+pub async fn run<C: Connector, I: Indicator>(
+    connector: &mut C,
+    indicator: &mut I,
+    device: &Device,
+) {
+    static REPLY: ReplySignal = Signal::new();
+    let mut projector = Requester::new(&REPLY);
+    let mut outbox = Outbox::new();
+
+    let mut backoff = Backoff::new(
+        RECONNECT_MIN,
+        RECONNECT_MAX,
+        Instant::now().as_ticks() as u32,
+    );
+
+    loop {
+        let result = serve(
+            connector,
+            indicator,
+            device,
+            &mut projector,
+            &mut backoff,
+            &mut outbox,
+        )
+        .await;
+        indicator.disconnected().await;
+        let Err(e) = result else {
+            info!("Disconnected from MQTT server");
+            return;
+        };
+
+        let delay = backoff.next_delay();
+        warn!(
+            "MQTT connection failed: {:?}, reconnecting in {} ms",
+            as_debug(&e),
+            delay.as_millis()
+        );
+        // keep taking states meanwhile, so the poller doesn't stall and the
+        // next session publishes the latest ones
+        let retry = Instant::now() + delay;
+        loop {
+            match select3(
+                Timer::at(retry),
+                SHUTDOWN.wait(),
+                poller::STATE_UPDATES.receive(),
+            )
+            .await
+            {
+                Either3::First(()) => break,
+                Either3::Second(()) => return,
+                Either3::Third(update) => queue_state_update(&mut outbox, device, update),
+            }
+        }
+
+        // the broker may have lost the retained states, queue them all up
+        // again for the next session
+        poller::REFRESH.signal(());
+    }
+}
+
+/// One session, from connecting until it fails or [`SHUTDOWN`]. The session
+/// (and with it the socket) is dropped on the way out.
+async fn serve<C: Connector, I: Indicator>(
+    connector: &mut C,
+    indicator: &mut I,
+    device: &Device,
+    projector: &mut Requester,
+    backoff: &mut Backoff,
+    outbox: &mut Outbox,
+) -> Result<(), C::Error> {
+    info!("Connecting to broker...");
+    let mut session = connector.connect().await?;
+    info!("Connected to MQTT server!");
+
+    homassistant_initialization(&mut session, device).await?;
+    info!("Sent discovery packet");
+
+    if !outbox.is_empty() {
+        info!("Publishing {} queued states", outbox.len());
+        flush(&mut session, outbox).await?;
+    }
+
+    backoff.reset();
+    indicator.connected().await;
+
+    let mut keep_alive = Ticker::every(KEEP_ALIVE / 2);
+    loop {
+        match select4(
+            session.receive(),
+            poller::STATE_UPDATES.receive(),
+            keep_alive.next(),
+            SHUTDOWN.wait(),
+        )
+        .await
+        {
+            Either4::First(message) => {
+                let message = message?;
+                info!("Received on topic {}: {:?}", message.topic, message.payload);
+
+                indicator.activity().await;
+
+                if let Some(reply) = dispatch(projector, device, &message).await {
+                    acknowledge(&mut session, device, reply).await?;
+                }
+            }
+            Either4::Second(update) => {
+                queue_state_update(outbox, device, update);
+                flush(&mut session, outbox).await?;
+            }
+            Either4::Third(()) => {
+                session.ping().await?;
+            }
+            Either4::Fourth(()) => {
+                info!("Shutting down, going offline");
+                session
+                    .publish(
+                        &device.availability_topic(),
+                        OFFLINE,
+                        QoS::AtLeastOnce,
+                        true,
+                    )
+                    .await?;
+                return session.disconnect().await;
+            }
+        }
+    }
+}
+
+/// What to publish once a command message has been handled.
+struct Reply {
+    topic: String,
+    payload: Vec<u8>,
+    correlation_data: Option<Vec<u8>>,
+    /// Failures are reported on `stat/error` as well, raw replies on
+    /// `stat/raw`.
+    outcome: Option<Outcome>,
+}
+
+/// Act on a message on one of the command topics, `None` for topics
+/// without a handler.
+async fn dispatch(
+    projector: &mut Requester,
+    device: &Device,
+    message: &Message<'_>,
+) -> Option<Reply> {
+    let name = device
+        .strip_prefix(message.topic)
+        .and_then(|rest| rest.strip_prefix("cmd/"));
+
+    if name == Some(json::NAME) {
+        let (response, outcome) = json::handle(projector, message.payload).await;
+        let topic = message
+            .response_topic
+            .map_or_else(|| device.topic("result/json"), Into::into);
+        return Some(Reply {
+            topic,
+            payload: response.to_string().into_bytes(),
+            correlation_data: message.correlation_data.map(Into::into),
+            outcome,
+        });
+    }
+
+    let outcome = match name {
+        Some(name) => commands::run(projector, name, message.payload).await,
+        None => None,
+    };
+    let (Some(name), Some(outcome)) = (name, outcome) else {
+        info!("Unknown topic: {}", message.topic);
+        return None;
+    };
+    let payload = match outcome.result() {
+        Ok(result) => result,
+        Err(error) => error,
+    };
+    Some(Reply {
+        topic: device.topic(&format!("result/{}", name)),
+        payload: payload.into_bytes(),
+        correlation_data: None,
+        outcome: Some(outcome),
+    })
+}
+
+/// Publish the reply, plus failures on `stat/error` and raw replies on
+/// `stat/raw`.
+async fn acknowledge<S: Session>(
+    session: &mut S,
+    device: &Device,
+    reply: Reply,
+) -> Result<(), S::Error> {
+    match &reply.outcome {
+        Some(Outcome::Failed(e)) => {
+            error!("Failed to handle command: {:?}", e);
+            publish_error(session, device, e).await?;
+        }
+        Some(Outcome::Raw { command, reply }) => {
+            let report = commands::raw_report(command, reply).to_string();
+            session
+                .publish(
+                    &device.topic("stat/raw"),
+                    report.as_bytes(),
+                    QoS::AtMostOnce,
+                    false,
+                )
+                .await?;
+        }
+        _ => {}
+    }
+    session
+        .reply(
+            &reply.topic,
+            &reply.payload,
+            reply.correlation_data.as_deref(),
+        )
+        .await
+}
+
+/// send Home Assistant MQTT discovery packets and subscribe to command topics
+pub async fn homassistant_initialization<S: Session>(
+    client: &mut S,
+    device: &Device,
+) -> Result<(), S::Error> {
+    for entity in ENTITIES {
+        publish_config(client, device, entity).await?;
+        debug!("Published {} config", entity.id);
+    }
+
+    // Device availability, the will takes care of offline
+    client
+        .publish(&device.availability_topic(), ONLINE, QoS::AtLeastOnce, true)
+        .await?;
+
+    debug!("Published availability online");
+
+    // Subscribe to command topics
+    let topics = command_topics(device);
+    let topics: Vec<&str> = topics.iter().map(String::as_str).collect();
+    client.subscribe(&topics).await?;
+
+    debug!("Subscribed to topics {:?}", topics.as_slice());
+    Ok(())
+}
+
+/// Every topic [`dispatch`] has a handler for.
+pub fn command_topics(device: &Device) -> Vec<String> {
+    commands::names()
+        .chain([json::NAME])
+        .map(|name| device.topic(&format!("cmd/{}", name)))
+        .collect()
+}
+
+/// Queue a changed projector state for the state topic of every entity
+/// showing it.
+fn queue_state_update(outbox: &mut Outbox, device: &Device, update: StateUpdate) {
+    for entity in ENTITIES {
+        if let Some(payload) = entity.state.and_then(|source| source.format(&update)) {
+            outbox.push(device.topic(&format!("stat/{}", entity.id)), payload);
+        }
+    }
+}
+
+/// Publish the queued states, retained and with QoS 1. Whatever is not
+/// through when the session fails stays queued for the next one.
+async fn flush<S: Session>(client: &mut S, outbox: &mut Outbox) -> Result<(), S::Error> {
+    while let Some((topic, payload)) = outbox.front() {
+        client
+            .publish(topic, payload.as_bytes(), QoS::AtLeastOnce, true)
+            .await?;
+        debug!("Published {}: {}", topic, payload.as_str());
+        outbox.pop();
+    }
+    Ok(())
+}
+
+/// Report a failed projector command on the error topic
+async fn publish_error<S: Session>(
+    client: &mut S,
+    device: &Device,
+    error: &ProjectorError,
+) -> Result<(), S::Error> {
+    let message = error.to_string();
+    client
+        .publish(
+            &device.topic("stat/error"),
+            message.as_bytes(),
+            QoS::AtMostOnce,
+            false,
+        )
+        .await
+}
+
+/// Serialize the entity's config into a fixed buffer and publish it. A
+/// config that doesn't fit is logged and skipped, `tests/entities.rs` makes
+/// sure none of ours are.
+async fn publish_config<S: Session>(
+    client: &mut S,
+    device: &Device,
+    entity: &Entity,
+) -> Result<(), S::Error> {
+    let mut buf = [0u8; CONFIG_SIZE];
+    let Ok(used) = to_slice(&entity.config(device), &mut buf) else {
+        error!("{} config exceeds {} bytes", entity.id, CONFIG_SIZE);
+        return Ok(());
+    };
+    client
+        .publish(
+            &entity.discovery_topic(device),
+            &buf[..used],
+            QoS::AtLeastOnce,
+            true,
+        )
+        .await
+}
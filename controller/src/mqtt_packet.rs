@@ -0,0 +1,369 @@
+//! Just enough MQTT v5 for the firmware's broker connection: the packets a
+//! client sends and the ones it gets back, with the request/response
+//! properties of a publish.
+//!
+//! The platform moves the bytes; [`packet_len`] tells it where a packet
+//! ends in what it has read so far, and [`decode`] takes it apart.
+
+use alloc::vec::Vec;
+
+use crate::mqtt::QoS;
+
+const CONNECT: u8 = 0x10;
+const CONNACK: u8 = 0x20;
+const PUBLISH: u8 = 0x30;
+const PUBACK: u8 = 0x40;
+const SUBSCRIBE: u8 = 0x82;
+const SUBACK: u8 = 0x90;
+const PINGREQ: u8 = 0xc0;
+const PINGRESP: u8 = 0xd0;
+const DISCONNECT: u8 = 0xe0;
+
+const PAYLOAD_FORMAT: u8 = 0x01;
+const MESSAGE_EXPIRY: u8 = 0x02;
+const CONTENT_TYPE: u8 = 0x03;
+const RESPONSE_TOPIC: u8 = 0x08;
+const CORRELATION_DATA: u8 = 0x09;
+const SUBSCRIPTION_ID: u8 = 0x0b;
+const TOPIC_ALIAS: u8 = 0x23;
+const USER_PROPERTY: u8 = 0x26;
+const MAXIMUM_PACKET_SIZE: u8 = 0x27;
+
+/// Reason codes from here on are failures.
+pub const FAILURE: u8 = 0x80;
+
+/// What a client needs to open a session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Connect<'a> {
+    pub client_id: &'a str,
+    pub keep_alive_secs: u16,
+    pub username: Option<&'a str>,
+    pub password: Option<&'a str>,
+    /// Topic and payload of a retained will, published with QoS 1.
+    pub will: Option<(&'a str, &'a [u8])>,
+    /// Largest packet the client takes, the broker drops bigger messages
+    /// for it.
+    pub max_packet_size: u32,
+}
+
+/// A packet from the broker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Packet<'a> {
+    ConnAck {
+        reason: u8,
+    },
+    Publish(Publish<'a>),
+    PubAck {
+        id: u16,
+        reason: u8,
+    },
+    /// One reason code per topic, in the order subscribed.
+    SubAck {
+        id: u16,
+        reasons: &'a [u8],
+    },
+    PingResp,
+    Disconnect {
+        reason: u8,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Publish<'a> {
+    pub topic: &'a str,
+    pub payload: &'a [u8],
+    pub qos: QoS,
+    pub retain: bool,
+    /// Set for QoS 1, to acknowledge with [`puback`].
+    pub id: Option<u16>,
+    pub response_topic: Option<&'a str>,
+    pub correlation_data: Option<&'a [u8]>,
+}
+
+pub fn connect(connect: &Connect<'_>) -> Vec<u8> {
+    let mut body = Vec::new();
+    put_str(&mut body, "MQTT");
+    body.push(5);
+    let mut flags = 0x02; // clean start
+    if connect.username.is_some() {
+        flags |= 0x80;
+    }
+    if connect.password.is_some() {
+        flags |= 0x40;
+    }
+    if connect.will.is_some() {
+        // retained, QoS 1
+        flags |= 0x20 | 0x08 | 0x04;
+    }
+    body.push(flags);
+    body.extend_from_slice(&connect.keep_alive_secs.to_be_bytes());
+    put_len(&mut body, 5);
+    body.push(MAXIMUM_PACKET_SIZE);
+    body.extend_from_slice(&connect.max_packet_size.to_be_bytes());
+
+    put_str(&mut body, connect.client_id);
+    if let Some((topic, payload)) = connect.will {
+        // no will properties
+        put_len(&mut body, 0);
+        put_str(&mut body, topic);
+        put_bytes(&mut body, payload);
+    }
+    if let Some(username) = connect.username {
+        put_str(&mut body, username);
+    }
+    if let Some(password) = connect.password {
+        put_bytes(&mut body, password.as_bytes());
+    }
+    packet(CONNECT, &body)
+}
+
+/// `id` is needed for QoS 1 and ignored for QoS 0.
+pub fn publish(
+    topic: &str,
+    payload: &[u8],
+    qos: QoS,
+    retain: bool,
+    id: u16,
+    correlation_data: Option<&[u8]>,
+) -> Vec<u8> {
+    let mut body = Vec::with_capacity(topic.len() + payload.len() + 8);
+    put_str(&mut body, topic);
+    if qos == QoS::AtLeastOnce {
+        body.extend_from_slice(&id.to_be_bytes());
+    }
+    match correlation_data {
+        Some(data) => {
+            put_len(&mut body, 3 + data.len());
+            body.push(CORRELATION_DATA);
+            put_bytes(&mut body, data);
+        }
+        None => put_len(&mut body, 0),
+    }
+    body.extend_from_slice(payload);
+
+    let mut first = PUBLISH | u8::from(retain);
+    if qos == QoS::AtLeastOnce {
+        first |= 0x02;
+    }
+    packet(first, &body)
+}
+
+/// Acknowledge a QoS 1 publish, success without properties.
+pub fn puback(id: u16) -> Vec<u8> {
+    packet(PUBACK, &id.to_be_bytes())
+}
+
+/// Subscribe to every topic with QoS 1.
+pub fn subscribe(id: u16, topics: &[&str]) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&id.to_be_bytes());
+    put_len(&mut body, 0);
+    for topic in topics {
+        put_str(&mut body, topic);
+        body.push(0x01);
+    }
+    packet(SUBSCRIBE, &body)
+}
+
+pub fn pingreq() -> Vec<u8> {
+    packet(PINGREQ, &[])
+}
+
+/// Normal disconnection, the broker discards the will.
+pub fn disconnect() -> Vec<u8> {
+    packet(DISCONNECT, &[])
+}
+
+/// The remaining length of a packet is not encoded properly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Malformed;
+
+/// Length of the packet at the start of `buffer`, header included, once
+/// enough of it is there to tell.
+pub fn packet_len(buffer: &[u8]) -> Result<Option<usize>, Malformed> {
+    match read_len(buffer.get(1..).unwrap_or_default()) {
+        Some((len, used)) => Ok(Some(1 + used + len)),
+        // four length bytes are the most there can be
+        None if buffer.len() > 4 => Err(Malformed),
+        None => Ok(None),
+    }
+}
+
+/// The whole packet in `packet`, `None` if it is malformed or of a kind a
+/// client does not receive.
+pub fn decode(packet: &[u8]) -> Option<Packet<'_>> {
+    let first = *packet.first()?;
+    let (len, used) = read_len(&packet[1..])?;
+    let body = packet.get(1 + used..)?;
+    if body.len() != len {
+        return None;
+    }
+    let mut reader = Reader(body);
+
+    match first & 0xf0 {
+        CONNACK => {
+            reader.byte()?;
+            Some(Packet::ConnAck {
+                reason: reader.byte()?,
+            })
+        }
+        PUBLISH => {
+            let qos = match (first >> 1) & 0x03 {
+                0 => QoS::AtMostOnce,
+                1 => QoS::AtLeastOnce,
+                // not subscribed with QoS 2
+                _ => return None,
+            };
+            let topic = reader.str()?;
+            let id = match qos {
+                QoS::AtLeastOnce => Some(reader.u16()?),
+                QoS::AtMostOnce => None,
+            };
+            let mut publish = Publish {
+                topic,
+                payload: &[],
+                qos,
+                retain: first & 0x01 != 0,
+                id,
+                response_topic: None,
+                correlation_data: None,
+            };
+            let mut properties = reader.properties()?;
+            while !properties.0.is_empty() {
+                match properties.byte()? {
+                    RESPONSE_TOPIC => publish.response_topic = Some(properties.str()?),
+                    CORRELATION_DATA => publish.correlation_data = Some(properties.bytes()?),
+                    // the rest is skipped
+                    PAYLOAD_FORMAT => {
+                        properties.byte()?;
+                    }
+                    MESSAGE_EXPIRY => {
+                        properties.take(4)?;
+                    }
+                    CONTENT_TYPE => {
+                        properties.str()?;
+                    }
+                    SUBSCRIPTION_ID => {
+                        properties.len()?;
+                    }
+                    TOPIC_ALIAS => {
+                        properties.u16()?;
+                    }
+                    USER_PROPERTY => {
+                        properties.str()?;
+                        properties.str()?;
+                    }
+                    _ => return None,
+                }
+            }
+            publish.payload = reader.0;
+            Some(Packet::Publish(publish))
+        }
+        PUBACK => Some(Packet::PubAck {
+            id: reader.u16()?,
+            // left out for success
+            reason: reader.byte().unwrap_or(0),
+        }),
+        SUBACK => {
+            let id = reader.u16()?;
+            reader.properties()?;
+            Some(Packet::SubAck {
+                id,
+                reasons: reader.0,
+            })
+        }
+        PINGRESP => Some(Packet::PingResp),
+        DISCONNECT => Some(Packet::Disconnect {
+            reason: reader.byte().unwrap_or(0),
+        }),
+        _ => None,
+    }
+}
+
+fn packet(first: u8, body: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(body.len() + 5);
+    packet.push(first);
+    put_len(&mut packet, body.len());
+    packet.extend_from_slice(body);
+    packet
+}
+
+/// Variable byte integer.
+fn put_len(out: &mut Vec<u8>, mut len: usize) {
+    loop {
+        let byte = (len % 128) as u8;
+        len /= 128;
+        if len == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn put_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    out.extend_from_slice(bytes);
+}
+
+fn put_str(out: &mut Vec<u8>, text: &str) {
+    put_bytes(out, text.as_bytes());
+}
+
+/// A variable byte integer at the start of `bytes` and how many bytes it
+/// took.
+fn read_len(bytes: &[u8]) -> Option<(usize, usize)> {
+    let mut len = 0;
+    for (i, &byte) in bytes.iter().take(4).enumerate() {
+        len |= usize::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((len, i + 1));
+        }
+    }
+    None
+}
+
+/// Takes fields off the front of a packet body.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (taken, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Some(taken)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        Some(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.take(2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn len(&mut self) -> Option<usize> {
+        let (len, used) = read_len(self.0)?;
+        self.take(used)?;
+        Some(len)
+    }
+
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.u16()?;
+        self.take(usize::from(len))
+    }
+
+    fn str(&mut self) -> Option<&'a str> {
+        core::str::from_utf8(self.bytes()?).ok()
+    }
+
+    /// The property block, to be read on its own.
+    fn properties(&mut self) -> Option<Reader<'a>> {
+        let len = self.len()?;
+        Some(Reader(self.take(len)?))
+    }
+}
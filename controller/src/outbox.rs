@@ -0,0 +1,62 @@
+//! State publishes waiting for the broker.
+//!
+//! Every state goes through the [`Outbox`] and only leaves it once the
+//! session took it, so values that change while the broker is out of reach
+//! are published as soon as it is back. Only the latest value per topic is
+//! kept, which bounds the outbox by the number of state topics; the
+//! [`OUTBOX_CAPACITY`] limit is a backstop that drops the oldest entry.
+//!
+//! The outbox lives in RAM. After a reboot the first poll publishes every
+//! state anyway.
+
+use alloc::collections::VecDeque;
+use alloc::string::String;
+
+use crate::entities::StatePayload;
+
+/// Most topics kept, well above the number of state topics.
+pub const OUTBOX_CAPACITY: usize = 32;
+
+#[derive(Debug, Default)]
+pub struct Outbox {
+    entries: VecDeque<(String, StatePayload)>,
+}
+
+impl Outbox {
+    pub const fn new() -> Self {
+        Self {
+            entries: VecDeque::new(),
+        }
+    }
+
+    /// Queue `payload` for `topic`, replacing whatever was pending for it.
+    pub fn push(&mut self, topic: String, payload: StatePayload) {
+        self.entries.retain(|(pending, _)| *pending != topic);
+        if self.entries.len() == OUTBOX_CAPACITY {
+            if let Some((dropped, _)) = self.entries.pop_front() {
+                warn!("Outbox full, dropping {}", dropped.as_str());
+            }
+        }
+        self.entries.push_back((topic, payload));
+    }
+
+    /// The entry to publish next, left in place until [`Outbox::pop`].
+    pub fn front(&self) -> Option<(&str, &StatePayload)> {
+        self.entries
+            .front()
+            .map(|(topic, payload)| (topic.as_str(), payload))
+    }
+
+    /// Drop the entry [`Outbox::front`] returned, once it is published.
+    pub fn pop(&mut self) {
+        self.entries.pop_front();
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
@@ -0,0 +1,195 @@
+//! Periodically asks the projector what it is doing and reports changes, so
+//! the state topics follow the projector even when the IR remote is used.
+
+use embassy_futures::select::{select3, Either3};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Timer};
+use projector_protocol::command::{Input, Volume};
+use projector_protocol::power::PowerPhase;
+use projector_protocol::query::{
+    ErrorStatus, ErrorStatusQuery, FreezeQuery, InputQuery, Lamp, LampRuntime, LampRuntimeQuery,
+    PowerQuery, PowerState, ShutterQuery, Temperature, TemperatureQuery, TemperatureSensor,
+    VolumeQuery,
+};
+
+use crate::projector::{ProjectorError, ReplySignal, Requester};
+
+/// A value that changed since the last poll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum StateUpdate {
+    Power(PowerState),
+    PowerPhase(PowerPhase),
+    Input(Input),
+    /// `true` when closed.
+    Shutter(bool),
+    Freeze(bool),
+    Volume(Volume),
+    LampRuntime(LampRuntime),
+    Temperature(TemperatureSensor, Temperature),
+    ErrorStatus(ErrorStatus),
+    /// The projector stopped answering, power and phase are unknown.
+    Unreachable,
+}
+
+/// Changes for the MQTT task to publish.
+pub static STATE_UPDATES: Channel<CriticalSectionRawMutex, StateUpdate, 8> = Channel::new();
+
+/// Poll right away instead of at the next interval, e.g. after a command.
+pub static POLL_NOW: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Poll right away and report every value, changed or not, e.g. after
+/// reconnecting to the broker.
+pub static REFRESH: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Failed polls in a row before the projector is reported
+/// [unreachable](StateUpdate::Unreachable).
+const UNREACHABLE_AFTER: u8 = 3;
+
+/// Last known state, `None` until the first successful query.
+#[derive(Default)]
+struct ProjectorState {
+    power: Option<PowerState>,
+    phase: Option<PowerPhase>,
+    input: Option<Input>,
+    shutter: Option<bool>,
+    freeze: Option<bool>,
+    volume: Option<Volume>,
+    lamp: Option<LampRuntime>,
+    intake: Option<Temperature>,
+    exhaust: Option<Temperature>,
+    errors: Option<ErrorStatus>,
+}
+
+/// Poll every `interval` (or on [`POLL_NOW`] and [`REFRESH`]) and send
+/// changes to [`STATE_UPDATES`].
+pub async fn run(interval: Duration) -> ! {
+    static REPLY: ReplySignal = Signal::new();
+    let mut projector = Requester::new(&REPLY);
+    let mut state = ProjectorState::default();
+    let mut failures = 0u8;
+
+    loop {
+        if poll(&mut projector, &mut state).await {
+            failures = 0;
+        } else {
+            failures = failures.saturating_add(1);
+            if failures == UNREACHABLE_AFTER {
+                warn!("Projector unreachable");
+                // everything is reported again once it answers
+                state = ProjectorState::default();
+                STATE_UPDATES.send(StateUpdate::Unreachable).await;
+            }
+        }
+        match select3(Timer::after(interval), POLL_NOW.wait(), REFRESH.wait()).await {
+            Either3::Third(()) => {
+                state = ProjectorState::default();
+                failures = 0;
+            }
+            Either3::First(()) | Either3::Second(()) => {}
+        }
+    }
+}
+
+/// Query everything and report the changes, `false` if the projector did
+/// not answer.
+async fn poll(projector: &mut Requester, state: &mut ProjectorState) -> bool {
+    debug!("Polling projector");
+
+    // without an answer to this one, the rest would only time out as well
+    let power = match projector.query(PowerQuery).await {
+        Ok(power) => power,
+        Err(e) => {
+            warn!("Poll failed: {:?}", e);
+            return false;
+        }
+    };
+    update(&mut state.power, Ok(power), StateUpdate::Power).await;
+
+    // picture and sound settings can only be queried with the lamp on
+    if state.power.is_some_and(|p| p == PowerState::On) {
+        update(
+            &mut state.input,
+            projector.query(InputQuery).await,
+            StateUpdate::Input,
+        )
+        .await;
+
+        update(
+            &mut state.shutter,
+            projector.query(ShutterQuery).await,
+            StateUpdate::Shutter,
+        )
+        .await;
+
+        update(
+            &mut state.freeze,
+            projector.query(FreezeQuery).await,
+            StateUpdate::Freeze,
+        )
+        .await;
+
+        update(
+            &mut state.volume,
+            projector.query(VolumeQuery).await,
+            StateUpdate::Volume,
+        )
+        .await;
+    }
+
+    update(
+        &mut state.lamp,
+        projector.query(LampRuntimeQuery(Lamp::Lamp1)).await,
+        StateUpdate::LampRuntime,
+    )
+    .await;
+
+    update(
+        &mut state.intake,
+        projector
+            .query(TemperatureQuery(TemperatureSensor::Intake))
+            .await,
+        |t| StateUpdate::Temperature(TemperatureSensor::Intake, t),
+    )
+    .await;
+
+    update(
+        &mut state.exhaust,
+        projector
+            .query(TemperatureQuery(TemperatureSensor::Exhaust))
+            .await,
+        |t| StateUpdate::Temperature(TemperatureSensor::Exhaust, t),
+    )
+    .await;
+
+    update(
+        &mut state.errors,
+        projector.query(ErrorStatusQuery).await,
+        StateUpdate::ErrorStatus,
+    )
+    .await;
+
+    if let Some(power) = state.power {
+        let phase = PowerPhase::from_status(power, state.errors);
+        update(&mut state.phase, Ok(phase), StateUpdate::PowerPhase).await;
+    }
+    true
+}
+
+/// Store `result` and report it if it differs from what we had.
+async fn update<T: Copy + PartialEq>(
+    last: &mut Option<T>,
+    result: Result<T, ProjectorError>,
+    wrap: fn(T) -> StateUpdate,
+) {
+    match result {
+        Ok(value) if *last != Some(value) => {
+            *last = Some(value);
+            STATE_UPDATES.send(wrap(value)).await;
+        }
+        Ok(_) => {}
+        Err(e) => warn!("Poll failed: {:?}", e),
+    }
+}
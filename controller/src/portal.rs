@@ -0,0 +1,414 @@
+//! The setup page the board serves on its own access point when it cannot
+//! join the stored network: pick a network, enter the broker, save.
+//!
+//! This is the platform independent part, HTTP parsing, the page and the
+//! form; the firmware runs the access point and the sockets (together with
+//! [`crate::dhcp`] and [`crate::dns`]) and stores what [`handle`] returns.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::fmt::{self, Write};
+use core::net::Ipv4Addr;
+
+use crate::config::{Config, StaticIpv4, WifiNetwork, MAX_DNS_SERVERS, MAX_NETWORKS};
+use crate::device::Device;
+
+/// Largest request read, the form is well below.
+pub const MAX_REQUEST_SIZE: usize = 2048;
+
+/// A network found by the Wi-Fi scan, offered on the page.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Network {
+    pub ssid: String,
+    /// dBm.
+    pub rssi: i8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Request<'a> {
+    pub method: &'a str,
+    /// Without the query string.
+    pub path: &'a str,
+    pub body: &'a [u8],
+}
+
+/// How far [`parse`] got with what was read so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parsed<'a> {
+    /// Headers or body still incomplete, read more.
+    Incomplete,
+    Invalid,
+    Complete(Request<'a>),
+}
+
+/// Parse the HTTP/1.x request at the start of `buffer`.
+pub fn parse(buffer: &[u8]) -> Parsed<'_> {
+    let Some(header_end) = buffer.windows(4).position(|w| w == b"\r\n\r\n") else {
+        return if buffer.len() >= MAX_REQUEST_SIZE {
+            Parsed::Invalid
+        } else {
+            Parsed::Incomplete
+        };
+    };
+    let Ok(head) = core::str::from_utf8(&buffer[..header_end]) else {
+        return Parsed::Invalid;
+    };
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let (Some(method), Some(target), Some(version)) = (
+        request_line.next(),
+        request_line.next(),
+        request_line.next(),
+    ) else {
+        return Parsed::Invalid;
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Parsed::Invalid;
+    }
+
+    let mut content_length = 0;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            return Parsed::Invalid;
+        };
+        if name.eq_ignore_ascii_case("content-length") {
+            match value.trim().parse() {
+                Ok(len) => content_length = len,
+                Err(_) => return Parsed::Invalid,
+            }
+        }
+    }
+
+    let body_start = header_end + 4;
+    // the length is the client's word, so no adding to it
+    if content_length > MAX_REQUEST_SIZE.saturating_sub(body_start) {
+        return Parsed::Invalid;
+    }
+    let Some(body) = buffer.get(body_start..body_start + content_length) else {
+        return Parsed::Incomplete;
+    };
+    Parsed::Complete(Request {
+        method,
+        path: target.split('?').next().unwrap_or_default(),
+        body,
+    })
+}
+
+/// What was wrong with the submitted form, shown above it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormError {
+    Ssid,
+    WifiPassword,
+    Broker,
+    Port,
+    ServerName,
+    DeviceId,
+    Address,
+}
+
+impl fmt::Display for FormError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Ssid => write!(f, "The network name must be 1 to 32 bytes."),
+            Self::WifiPassword => write!(f, "The Wi-Fi password must be 8 to 63 characters."),
+            Self::Broker => write!(
+                f,
+                "The broker must be a host name or address, without scheme or port."
+            ),
+            Self::Port => write!(f, "The broker port must be a number from 1 to 65535."),
+            Self::ServerName => write!(f, "The TLS server name must be a host name."),
+            Self::DeviceId => write!(f, "The device id must be letters, digits, '-' or '_'."),
+            Self::Address => write!(
+                f,
+                "The address must look like 10.20.0.42/24, with at most {} name servers.",
+                MAX_DNS_SERVERS
+            ),
+        }
+    }
+}
+
+/// `config` with the submitted form applied.
+///
+/// The network from the form goes ahead of the known ones, replacing its
+/// entry if it has one; past [`MAX_NETWORKS`] the least preferred one is
+/// dropped. Empty password fields keep the stored password of that network
+/// (or broker user); the page never shows them.
+pub fn apply_form(config: &Config, form: &[u8]) -> Result<Config, FormError> {
+    let fields = form_fields(form);
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.trim())
+            .unwrap_or_default()
+    };
+    let optional = |name: &str| {
+        Some(field(name))
+            .filter(|v| !v.is_empty())
+            .map(String::from)
+    };
+
+    let mut updated = config.clone();
+
+    let ssid = field("ssid");
+    if ssid.is_empty() || ssid.len() > 32 {
+        return Err(FormError::Ssid);
+    }
+    let password = fields
+        .iter()
+        .find(|(key, _)| key == "password")
+        .map(|(_, value)| value.as_str())
+        .unwrap_or_default();
+    if !password.is_empty() && !(8..=63).contains(&password.len()) {
+        return Err(FormError::WifiPassword);
+    }
+    let known = updated.networks.iter().position(|n| n.ssid == ssid);
+    let password = match known {
+        Some(known) if password.is_empty() => updated.networks.remove(known).password,
+        Some(known) => {
+            updated.networks.remove(known);
+            password.into()
+        }
+        None => password.into(),
+    };
+    let priority = updated
+        .networks
+        .iter()
+        .map(|n| n.priority)
+        .max()
+        .map_or(0, |highest| highest.saturating_add(1));
+    updated
+        .networks
+        .sort_by_key(|n| core::cmp::Reverse(n.priority));
+    updated.networks.truncate(MAX_NETWORKS - 1);
+    updated.networks.insert(
+        0,
+        WifiNetwork {
+            ssid: ssid.into(),
+            password,
+            priority,
+            // re-entered because the pinned access point is gone, most
+            // likely
+            bssid: None,
+        },
+    );
+
+    // empty to look for one on the network
+    let host = field("broker");
+    if host.contains(|c: char| c.is_whitespace() || c == ':' || c == '/') {
+        return Err(FormError::Broker);
+    }
+    updated.mqtt.host = host.into();
+    updated.mqtt.port = match field("port").parse() {
+        Ok(0) | Err(_) => return Err(FormError::Port),
+        Ok(port) => port,
+    };
+    let username = optional("username");
+    match optional("mqtt_password") {
+        Some(password) => updated.mqtt.password = Some(password),
+        None if username != config.mqtt.username => updated.mqtt.password = None,
+        None => {}
+    }
+    updated.mqtt.username = username;
+    // empty to check the certificate against the broker's name
+    updated.mqtt.tls_server_name = optional("tls_server_name");
+    if let Some(name) = &updated.mqtt.tls_server_name {
+        if name.contains(|c: char| c.is_whitespace() || c == ':' || c == '/') {
+            return Err(FormError::ServerName);
+        }
+    }
+
+    updated.device_id = optional("device_id");
+    if let Some(id) = &updated.device_id {
+        Device::new(id).map_err(|_| FormError::DeviceId)?;
+    }
+
+    updated.ip.ipv4 = match field("address") {
+        "" => None,
+        address => Some(
+            StaticIpv4::parse(address, field("gateway"), field("dns_servers"))
+                .ok_or(FormError::Address)?,
+        ),
+    };
+    // checkboxes are only sent when ticked
+    updated.ip.ipv6 = !field("ipv6").is_empty();
+    Ok(updated)
+}
+
+/// Answer `request`: the form on `/`, saving on `POST /`, and a redirect to
+/// the form for everything else, which is what makes phones show the
+/// portal. Returns the response and, once the form was accepted, the new
+/// settings to store.
+pub fn handle(
+    request: &Request<'_>,
+    config: &Config,
+    networks: &[Network],
+    address: Ipv4Addr,
+) -> (Vec<u8>, Option<Config>) {
+    match (request.method, request.path) {
+        ("GET", "/") => (response("200 OK", "", &page(config, networks, None)), None),
+        ("POST", "/") => match apply_form(config, request.body) {
+            Ok(updated) => (response("200 OK", "", SAVED_PAGE), Some(updated)),
+            Err(e) => {
+                let page = page(config, networks, Some(e));
+                (response("400 Bad Request", "", &page), None)
+            }
+        },
+        _ => {
+            let location = format!("Location: http://{}/\r\n", address);
+            (response("302 Found", &location, ""), None)
+        }
+    }
+}
+
+const SAVED_PAGE: &str = "<!DOCTYPE html><meta name=viewport content=\"width=device-width\">\
+    <title>Projector setup</title><p>Saved, the projector controller restarts and \
+    joins the network.";
+
+/// `headers` are extra header lines, each ending in CRLF.
+fn response(status: &str, headers: &str, body: &str) -> Vec<u8> {
+    let mut response = format!(
+        "HTTP/1.1 {}\r\n{}Content-Type: text/html; charset=utf-8\r\n\
+         Content-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        headers,
+        body.len()
+    )
+    .into_bytes();
+    response.extend_from_slice(body.as_bytes());
+    response
+}
+
+fn page(config: &Config, networks: &[Network], error: Option<FormError>) -> String {
+    let mut page = String::from(
+        "<!DOCTYPE html><meta name=viewport content=\"width=device-width\">\
+         <title>Projector setup</title><h1>Projector setup</h1>",
+    );
+    if let Some(error) = error {
+        let _ = write!(page, "<p><strong>{}</strong>", error);
+    }
+    page.push_str("<form method=post action=/><h2>Wi-Fi</h2>");
+    let _ = write!(
+        page,
+        "<p><label>Network <input name=ssid list=networks required value=\"{}\"></label>\
+         <datalist id=networks>",
+        Escaped(
+            config
+                .networks
+                .iter()
+                .max_by_key(|n| n.priority)
+                .map_or("", |n| n.ssid.as_str())
+        )
+    );
+    for network in networks {
+        let _ = write!(
+            page,
+            "<option value=\"{}\">{} dBm</option>",
+            Escaped(&network.ssid),
+            network.rssi
+        );
+    }
+    page.push_str(
+        "</datalist><p><label>Password <input name=password type=password \
+         placeholder=unchanged></label><h2>MQTT</h2>",
+    );
+    let _ = write!(
+        page,
+        "<p><label>Broker <input name=broker value=\"{}\" \
+         placeholder=\"found by mDNS\"></label>\
+         <p><label>Port <input name=port type=number min=1 max=65535 value=\"{}\"></label>\
+         <p><label>User <input name=username value=\"{}\"></label>\
+         <p><label>Password <input name=mqtt_password type=password \
+         placeholder=unchanged></label>\
+         <p><label>TLS server name <input name=tls_server_name value=\"{}\" \
+         placeholder=\"the broker's\"></label>\
+         <p><label>Device id <input name=device_id value=\"{}\" \
+         placeholder=\"from the MAC address\"></label>",
+        Escaped(&config.mqtt.host),
+        config.mqtt.port,
+        Escaped(config.mqtt.username.as_deref().unwrap_or_default()),
+        Escaped(config.mqtt.tls_server_name.as_deref().unwrap_or_default()),
+        Escaped(config.device_id.as_deref().unwrap_or_default()),
+    );
+
+    let ipv4 = config.ip.ipv4.as_ref();
+    let address = ipv4.map(|ipv4| format!("{}/{}", ipv4.address, ipv4.prefix_len));
+    let gateway = ipv4.and_then(|ipv4| ipv4.gateway).map(|g| g.to_string());
+    let dns_servers = ipv4.map(|ipv4| {
+        let servers: Vec<String> = ipv4.dns_servers.iter().map(|s| s.to_string()).collect();
+        servers.join(", ")
+    });
+    let _ = write!(
+        page,
+        "<h2>Network</h2>\
+         <p><label>Address <input name=address value=\"{}\" placeholder=DHCP></label>\
+         <p><label>Gateway <input name=gateway value=\"{}\"></label>\
+         <p><label>Name servers <input name=dns_servers value=\"{}\"></label>\
+         <p><label><input name=ipv6 type=checkbox{}> IPv6</label>\
+         <p><button>Save and restart</button></form>",
+        address.unwrap_or_default(),
+        gateway.unwrap_or_default(),
+        dns_servers.unwrap_or_default(),
+        if config.ip.ipv6 { " checked" } else { "" },
+    );
+    page
+}
+
+/// Text safe to put in HTML content and quoted attributes.
+struct Escaped<'a>(&'a str);
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '&' => f.write_str("&amp;")?,
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '"' => f.write_str("&quot;")?,
+                '\'' => f.write_str("&#39;")?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decoded `application/x-www-form-urlencoded` pairs.
+fn form_fields(form: &[u8]) -> Vec<(String, String)> {
+    form.split(|b| *b == b'&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let mut parts = pair.splitn(2, |b| *b == b'=');
+            let key = url_decode(parts.next().unwrap_or_default());
+            let value = url_decode(parts.next().unwrap_or_default());
+            (key, value)
+        })
+        .collect()
+}
+
+fn url_decode(encoded: &[u8]) -> String {
+    let mut decoded = vec![];
+    let mut bytes = encoded.iter();
+    while let Some(&b) = bytes.next() {
+        match b {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let hex = bytes
+                    .as_slice()
+                    .get(..2)
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                    .and_then(|hex| u8::from_str_radix(core::str::from_utf8(hex).ok()?, 16).ok());
+                match hex {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        bytes.nth(1);
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            b => decoded.push(b),
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,333 @@
+//! Serialised access to the projector.
+//!
+//! Framing, commands and reply parsing live in the `projector-protocol`
+//! crate; this module puts a deadline on every transaction and serialises
+//! access through [`run`], which owns the port and keeps commands from
+//! clashing with lamp warm-up and cool-down. The port is anything speaking
+//! `embedded_io_async`: the UART on the ESP32, a serial device or the
+//! simulator's PTY on the host.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_sync::signal::Signal;
+use embassy_time::{with_timeout, Duration, Instant, Timer};
+use embedded_io_async::{Read, ReadReady, Write};
+use projector_protocol::asynch::Projector;
+use projector_protocol::error::command_name;
+use projector_protocol::frame::{FrameError, MAX_PAYLOAD_LEN};
+use projector_protocol::power::{Decision, PowerStateMachine};
+use projector_protocol::query::{IntoQuery, ParseError, Query, QueryResponse};
+
+pub use projector_protocol::power::PowerPhase;
+pub use projector_protocol::{ProjectorCommand, ProjectorError, ProjectorQuery};
+
+use crate::fmt::as_debug;
+
+/// Longest the projector may take to answer a command or query. At 9600 baud
+/// a full reply takes well under 100 ms, the rest is the projector thinking.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Pause between the end of one transaction and the start of the next, the
+/// projector ignores commands that follow a reply too closely.
+const MIN_COMMAND_GAP: Duration = Duration::from_millis(200);
+
+/// Payload of a [`Request::Raw`].
+pub type RawPayload = heapless::Vec<u8, MAX_PAYLOAD_LEN>;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Request {
+    Execute(ProjectorCommand),
+    Query(Query),
+    /// Send an arbitrary payload and read back whatever frame comes back,
+    /// error replies included.
+    Raw(RawPayload),
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Response {
+    Done,
+    /// Held back until the projector has finished warming up or cooling
+    /// down, see [`PowerStateMachine`].
+    Deferred,
+    Query(QueryResponse),
+    /// Payload of the reply to a [`Request::Raw`].
+    Raw(RawPayload),
+}
+
+/// Where [`run`] puts the result of a request.
+pub type ReplySignal = Signal<CriticalSectionRawMutex, Result<Response, ProjectorError>>;
+
+struct Job {
+    request: Request,
+    reply: &'static ReplySignal,
+}
+
+static REQUESTS: Channel<CriticalSectionRawMutex, Job, 8> = Channel::new();
+
+/// Owns the projector and works through the requests one at a time.
+pub async fn run<P: Read + Write + ReadReady>(mut link: ProjectorLink<P>) -> ! {
+    let mut next_transaction = Instant::now();
+    loop {
+        let job = REQUESTS.receive().await;
+        debug!("Projector request: {:?}", job.request);
+
+        Timer::at(next_transaction).await;
+        let result = link.handle(job.request).await;
+        next_transaction = Instant::now() + MIN_COMMAND_GAP;
+
+        job.reply.signal(result);
+
+        // a poll may have ended the transition a command was waiting for
+        match link.power.take_released() {
+            Some(Ok(command)) => {
+                Timer::at(next_transaction).await;
+                info!("Sending deferred {:?}", command);
+                if let Err(e) = link.execute_tracked(command).await {
+                    warn!("Deferred {:?} failed: {:?}", command, e);
+                }
+                next_transaction = Instant::now() + MIN_COMMAND_GAP;
+            }
+            Some(Err(e)) => warn!("Deferred command dropped: {:?}", e),
+            None => {}
+        }
+    }
+}
+
+/// Handle for submitting requests to [`run`].
+///
+/// Every front-end (MQTT, HTTP, buttons, ...) owns one with its own static
+/// [`ReplySignal`]; the `&mut self` receivers keep a requester from having
+/// two requests in flight.
+pub struct Requester {
+    reply: &'static ReplySignal,
+}
+
+impl Requester {
+    pub const fn new(reply: &'static ReplySignal) -> Self {
+        Self { reply }
+    }
+
+    /// Queue `request` and wait for the result.
+    pub async fn submit(&mut self, request: Request) -> Result<Response, ProjectorError> {
+        self.reply.reset();
+        REQUESTS
+            .send(Job {
+                request,
+                reply: self.reply,
+            })
+            .await;
+        self.reply.wait().await
+    }
+
+    pub async fn execute(&mut self, command: ProjectorCommand) -> Result<(), ProjectorError> {
+        self.submit(Request::Execute(command)).await.map(|_| ())
+    }
+
+    pub async fn query<Q: IntoQuery>(&mut self, query: Q) -> Result<Q::Response, ProjectorError> {
+        let command = query.encode();
+        match self.submit(Request::Query(query.into())).await? {
+            Response::Query(response) => Q::from_response(response),
+            Response::Done | Response::Deferred | Response::Raw(_) => None,
+        }
+        .ok_or(ProjectorError::ParseError {
+            command,
+            error: ParseError::UnexpectedReply,
+        })
+    }
+
+    /// Send `data` as is and return the payload of the reply.
+    pub async fn send_raw(&mut self, data: &[u8]) -> Result<RawPayload, ProjectorError> {
+        let payload = RawPayload::from_slice(data).map_err(|_| ProjectorError::Framing {
+            command: command_name(data),
+            error: FrameError::Overflow,
+        })?;
+        match self.submit(Request::Raw(payload)).await? {
+            Response::Raw(reply) => Ok(reply),
+            _ => Err(ProjectorError::ParseError {
+                command: command_name(data),
+                error: ParseError::UnexpectedReply,
+            }),
+        }
+    }
+}
+
+/// Which raw payloads may be sent, by command code: the part before the
+/// `:`, e.g. `IIS` for `IIS:HD1`. Everything is allowed by default.
+#[derive(Debug, Clone, Default)]
+pub struct RawFilter {
+    /// Only these codes, if set.
+    allow: Option<Vec<String>>,
+    deny: Vec<String>,
+}
+
+impl RawFilter {
+    /// Allow only the comma separated `codes`.
+    pub fn allow(self, codes: &str) -> Self {
+        Self {
+            allow: Some(split_codes(codes)),
+            ..self
+        }
+    }
+
+    /// Block the comma separated `codes`.
+    pub fn deny(self, codes: &str) -> Self {
+        Self {
+            deny: split_codes(codes),
+            ..self
+        }
+    }
+
+    pub fn permits(&self, payload: &[u8]) -> bool {
+        let code = payload.split(|b| *b == b':').next().unwrap_or_default();
+        let listed = |codes: &[String]| codes.iter().any(|c| c.as_bytes() == code);
+        !listed(&self.deny) && self.allow.as_deref().is_none_or(listed)
+    }
+}
+
+fn split_codes(codes: &str) -> Vec<String> {
+    codes
+        .split(',')
+        .map(str::trim)
+        .filter(|code| !code.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Projector link where no transaction can hang, even with the projector
+/// switched off at the wall or the cable unplugged.
+pub struct ProjectorLink<P> {
+    projector: Projector<P>,
+    power: PowerTracker,
+    raw_filter: RawFilter,
+}
+
+/// [`PowerStateMachine`] plus the deferred command it released, or the
+/// error it dropped it with.
+#[derive(Default)]
+struct PowerTracker {
+    machine: PowerStateMachine,
+    released: Option<Result<ProjectorCommand, ProjectorError>>,
+}
+
+impl PowerTracker {
+    fn take_released(&mut self) -> Option<Result<ProjectorCommand, ProjectorError>> {
+        self.released.take()
+    }
+
+    /// Feed polled state from query replies into the state machine.
+    fn observe(&mut self, response: &QueryResponse) {
+        let released = match response {
+            QueryResponse::Power(power) => self.machine.observe(*power),
+            QueryResponse::ErrorStatus(errors) => self.machine.observe_errors(*errors).map(Err),
+            _ => None,
+        };
+        if released.is_some() {
+            self.released = released;
+        }
+    }
+}
+
+impl<P: Read + Write + ReadReady> ProjectorLink<P> {
+    pub fn new(port: P) -> Self {
+        Self {
+            projector: Projector::new(port),
+            power: PowerTracker::default(),
+            raw_filter: RawFilter::default(),
+        }
+    }
+
+    /// Refuse the raw payloads `filter` doesn't permit.
+    pub fn with_raw_filter(self, filter: RawFilter) -> Self {
+        Self {
+            raw_filter: filter,
+            ..self
+        }
+    }
+
+    /// Send `command` and wait for the acknowledgement.
+    pub async fn execute(&mut self, command: ProjectorCommand) -> Result<(), ProjectorError> {
+        self.drain().await;
+        with_timeout(REPLY_TIMEOUT, self.projector.execute(command))
+            .await
+            .unwrap_or_else(|_| {
+                Err(ProjectorError::Timeout {
+                    command: command.encode(),
+                })
+            })
+    }
+
+    /// Send `query` and wait for the parsed reply.
+    pub async fn query<Q: ProjectorQuery>(
+        &mut self,
+        query: Q,
+    ) -> Result<Q::Response, ProjectorError> {
+        let command = query.encode();
+        self.drain().await;
+        with_timeout(REPLY_TIMEOUT, self.projector.query(query))
+            .await
+            .unwrap_or_else(|_| Err(ProjectorError::Timeout { command }))
+    }
+
+    /// Send an arbitrary payload and wait for the reply, which is returned
+    /// as is, even if it is an error reply.
+    pub async fn transact_raw(&mut self, data: &[u8]) -> Result<RawPayload, ProjectorError> {
+        if !self.raw_filter.permits(data) {
+            return Err(ProjectorError::Forbidden {
+                command: command_name(data),
+            });
+        }
+        self.drain().await;
+        let mut reply = [0u8; MAX_PAYLOAD_LEN];
+        let transaction = async {
+            self.projector.send(data).await?;
+            self.projector.receive(&mut reply).await
+        };
+        let len = with_timeout(REPLY_TIMEOUT, transaction)
+            .await
+            .unwrap_or_else(|_| {
+                Err(ProjectorError::Timeout {
+                    command: command_name(data),
+                })
+            })?;
+        // the buffer is MAX_PAYLOAD_LEN as well
+        Ok(RawPayload::from_slice(&reply[..len]).unwrap_or_default())
+    }
+
+    async fn handle(&mut self, request: Request) -> Result<Response, ProjectorError> {
+        match request {
+            Request::Execute(command) => match self.power.machine.submit(command) {
+                Decision::Send => self.execute_tracked(command).await.map(|_| Response::Done),
+                Decision::AlreadyDone => Ok(Response::Done),
+                Decision::Deferred => Ok(Response::Deferred),
+                Decision::Rejected(e) => Err(e),
+            },
+            Request::Query(query) => {
+                let response = self.query(query).await?;
+                self.power.observe(&response);
+                Ok(Response::Query(response))
+            }
+            Request::Raw(payload) => self.transact_raw(&payload).await.map(Response::Raw),
+        }
+    }
+
+    /// Execute `command` and let the state machine know it went through.
+    async fn execute_tracked(&mut self, command: ProjectorCommand) -> Result<(), ProjectorError> {
+        self.execute(command).await?;
+        self.power.machine.sent(command);
+        Ok(())
+    }
+
+    /// Drop stale bytes so they are not mistaken for the next reply.
+    async fn drain(&mut self) {
+        match self.projector.drain().await {
+            Ok(0) => {}
+            Ok(n) => warn!("Discarded {} stale bytes from projector", n),
+            Err(e) => warn!("Failed to drain projector port: {:?}", as_debug(&e)),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+//! Which of the known networks to join, from what a scan found.
+//!
+//! The firmware scans before every connect, tries the [`candidates`] in
+//! order and gives each [`FAILURES_BEFORE_NEXT`] attempts before moving on,
+//! so the board follows access points being moved, renamed or replaced.
+
+use alloc::vec::Vec;
+
+use crate::config::WifiNetwork;
+
+/// Failed connects to one candidate before the next one gets its turn.
+pub const FAILURES_BEFORE_NEXT: u32 = 3;
+
+/// An access point found by a scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Seen<'a> {
+    pub ssid: &'a str,
+    pub bssid: [u8; 6],
+    pub channel: u8,
+    /// dBm.
+    pub rssi: i8,
+}
+
+/// A known network to try and, if the scan found it, the access point to
+/// join.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candidate<'n> {
+    pub network: &'n WifiNetwork,
+    pub bssid: Option<[u8; 6]>,
+    pub channel: Option<u8>,
+}
+
+/// The known networks in the order to try them: those in `scan` by
+/// priority, then by signal, joining their strongest access point (or the
+/// pinned one); after them the ones the scan missed, which may be hidden.
+/// A pinned network whose access point was not seen is tried blind as
+/// well, never through another access point.
+pub fn candidates<'n>(known: &'n [WifiNetwork], scan: &[Seen<'_>]) -> Vec<Candidate<'n>> {
+    let mut seen = Vec::new();
+    let mut missed = Vec::new();
+    for network in known {
+        let strongest = scan
+            .iter()
+            .filter(|ap| ap.ssid == network.ssid)
+            .filter(|ap| network.bssid.is_none_or(|pinned| pinned == ap.bssid))
+            .max_by_key(|ap| ap.rssi);
+        match strongest {
+            Some(ap) => seen.push((
+                ap.rssi,
+                Candidate {
+                    network,
+                    bssid: Some(ap.bssid),
+                    channel: Some(ap.channel),
+                },
+            )),
+            None => missed.push(Candidate {
+                network,
+                bssid: network.bssid,
+                channel: None,
+            }),
+        }
+    }
+
+    seen.sort_by_key(|(rssi, candidate)| {
+        (
+            core::cmp::Reverse(candidate.network.priority),
+            core::cmp::Reverse(*rssi),
+        )
+    });
+    missed.sort_by_key(|candidate| core::cmp::Reverse(candidate.network.priority));
+    seen.into_iter()
+        .map(|(_, candidate)| candidate)
+        .chain(missed)
+        .collect()
+}
+
+/// `aa:bb:cc:dd:ee:ff` (or with `-`), as access points are usually
+/// labelled.
+pub fn parse_bssid(text: &str) -> Option<[u8; 6]> {
+    let mut bssid = [0; 6];
+    let mut octets = text.trim().split([':', '-']);
+    for octet in &mut bssid {
+        let hex = octets.next()?;
+        if hex.len() != 2 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        *octet = u8::from_str_radix(hex, 16).ok()?;
+    }
+    octets.next().is_none().then_some(bssid)
+}
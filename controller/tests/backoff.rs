@@ -0,0 +1,47 @@
+use embassy_time::Duration;
+use projector_controller::backoff::Backoff;
+
+const MIN: Duration = Duration::from_secs(1);
+const MAX: Duration = Duration::from_secs(60);
+
+fn assert_jittered(delay: Duration, step: Duration) {
+    assert!(
+        delay >= step && delay <= step + step / 2,
+        "{delay:?} not within {step:?} + 50%"
+    );
+}
+
+#[test]
+fn doubles_up_to_max() {
+    let mut backoff = Backoff::new(MIN, MAX, 1);
+    for secs in [1, 2, 4, 8, 16, 32, 60, 60, 60] {
+        assert_jittered(backoff.next_delay(), Duration::from_secs(secs));
+    }
+}
+
+#[test]
+fn reset_starts_over() {
+    let mut backoff = Backoff::new(MIN, MAX, 1);
+    for _ in 0..5 {
+        backoff.next_delay();
+    }
+    backoff.reset();
+    assert_jittered(backoff.next_delay(), MIN);
+}
+
+#[test]
+fn jitter_depends_on_seed() {
+    let delays = |seed| {
+        let mut backoff = Backoff::new(MIN, MAX, seed);
+        [(); 6].map(|_| backoff.next_delay())
+    };
+    assert_ne!(delays(1), delays(2));
+    assert_eq!(delays(3), delays(3));
+}
+
+#[test]
+fn zero_seed_still_jitters() {
+    let mut backoff = Backoff::new(MAX, MAX, 0);
+    let delays = [(); 4].map(|_| backoff.next_delay());
+    assert!(delays.iter().any(|d| *d != MAX));
+}
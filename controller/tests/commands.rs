@@ -0,0 +1,158 @@
+//! The command table against the entities announced to Home Assistant.
+
+use std::convert::Infallible;
+
+use embassy_futures::block_on;
+use projector_controller::commands::{self, Action, InvalidPayload};
+use projector_controller::device::Device;
+use projector_controller::mqtt::{self, Message, QoS, Session};
+use projector_protocol::command::{Input, MenuKey, Volume};
+use projector_protocol::ProjectorCommand;
+use serde_json::Value;
+
+/// Collects discovery configs and subscriptions.
+#[derive(Default)]
+struct Recorder {
+    configs: Vec<Value>,
+    subscriptions: Vec<String>,
+}
+
+impl Session for Recorder {
+    type Error = Infallible;
+
+    async fn publish(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        _qos: QoS,
+        _retain: bool,
+    ) -> Result<(), Infallible> {
+        if topic.starts_with("homeassistant/") {
+            self.configs.push(serde_json::from_slice(payload).unwrap());
+        }
+        Ok(())
+    }
+
+    async fn subscribe(&mut self, topics: &[&str]) -> Result<(), Infallible> {
+        self.subscriptions
+            .extend(topics.iter().map(|t| t.to_string()));
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<Message<'_>, Infallible> {
+        core::future::pending().await
+    }
+
+    async fn ping(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+fn announce(device: &Device) -> Recorder {
+    let mut recorder = Recorder::default();
+    block_on(mqtt::homassistant_initialization(&mut recorder, device)).unwrap();
+    recorder
+}
+
+fn parse(name: &str, payload: &str) -> Result<ProjectorCommand, InvalidPayload> {
+    match commands::find(name).unwrap().parse(payload.as_bytes())? {
+        Action::Execute(command) => Ok(command),
+        Action::Raw(_) => panic!("{name} is not a raw topic"),
+    }
+}
+
+#[test]
+fn every_entity_has_a_handler() {
+    let device = Device::new("living-room").unwrap();
+    let recorder = announce(&device);
+
+    let command_topics: Vec<&str> = recorder
+        .configs
+        .iter()
+        .filter_map(|config| config["command_topic"].as_str())
+        .collect();
+    assert!(!command_topics.is_empty());
+    for topic in command_topics {
+        let name = topic
+            .strip_prefix("living-room/cmd/")
+            .unwrap_or_else(|| panic!("{topic} is not a command topic"));
+        assert!(commands::find(name).is_some(), "no handler for {topic}");
+        assert!(
+            recorder.subscriptions.iter().any(|t| t == topic),
+            "{topic} not subscribed"
+        );
+    }
+}
+
+#[test]
+fn every_handler_is_subscribed() {
+    let device = Device::new("living-room").unwrap();
+    let recorder = announce(&device);
+    assert_eq!(recorder.subscriptions, mqtt::command_topics(&device));
+    for name in commands::names() {
+        assert!(commands::find(name).is_some(), "{name} has no handler");
+    }
+}
+
+#[test]
+fn switches() {
+    assert_eq!(parse("power", "ON"), Ok(ProjectorCommand::PowerOn));
+    assert_eq!(parse("power", "OFF"), Ok(ProjectorCommand::PowerOff));
+    assert_eq!(parse("shutter", "ON"), Ok(ProjectorCommand::Shutter(true)));
+    assert_eq!(parse("freeze", "OFF"), Ok(ProjectorCommand::Freeze(false)));
+    assert_eq!(parse("power", "on"), Err(InvalidPayload));
+}
+
+#[test]
+fn buttons_take_any_payload() {
+    let keys = [
+        ("menu", MenuKey::Menu),
+        ("enter", MenuKey::Enter),
+        ("up", MenuKey::Up),
+        ("down", MenuKey::Down),
+        ("left", MenuKey::Left),
+        ("right", MenuKey::Right),
+        ("back", MenuKey::Back),
+    ];
+    for (name, key) in keys {
+        assert_eq!(parse(name, "PRESS"), Ok(ProjectorCommand::Menu(key)));
+        assert_eq!(parse(name, ""), Ok(ProjectorCommand::Menu(key)));
+    }
+}
+
+#[test]
+fn input_and_volume() {
+    for input in Input::ALL {
+        assert_eq!(
+            parse("input", input.name()),
+            Ok(ProjectorCommand::SelectInput(*input))
+        );
+    }
+    // as forgiving as cmd/json
+    assert_eq!(
+        parse("input", "hdmi2"),
+        Ok(ProjectorCommand::SelectInput(Input::Hdmi2))
+    );
+    assert_eq!(
+        parse("input", "Hdmi1"),
+        Ok(ProjectorCommand::SelectInput(Input::Hdmi1))
+    );
+    assert_eq!(parse("input", "SCART"), Err(InvalidPayload));
+
+    let twelve = ProjectorCommand::Volume(Volume::new(12).unwrap());
+    assert_eq!(parse("volume", "12"), Ok(twelve));
+    assert_eq!(parse("volume", "12.0"), Ok(twelve));
+    assert_eq!(parse("volume", "64"), Err(InvalidPayload));
+    assert_eq!(parse("volume", "loud"), Err(InvalidPayload));
+}
+
+#[test]
+fn raw_is_passed_through() {
+    let raw = commands::find("raw").unwrap();
+    assert_eq!(raw.parse(b"QIN"), Ok(Action::Raw(b"QIN")));
+    assert!(commands::find("reboot").is_none());
+}
@@ -0,0 +1,210 @@
+use std::net::Ipv4Addr;
+
+use projector_controller::config::{
+    Config, ConfigError, IpConfig, MqttConfig, StaticIpv4, WifiNetwork, MAX_RECORD_SIZE, VERSION,
+};
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 * (crc & 1));
+        }
+    }
+    !crc
+}
+
+/// A record as some firmware version would have written it.
+fn record(version: u16, payload: &str) -> Vec<u8> {
+    let mut record = version.to_le_bytes().to_vec();
+    record.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    record.extend_from_slice(payload.as_bytes());
+    record.extend_from_slice(&crc32(&record).to_le_bytes());
+    record
+}
+
+fn config() -> Config {
+    Config {
+        networks: vec![
+            WifiNetwork {
+                ssid: "chaosdorf".into(),
+                password: "hunter22".into(),
+                priority: 1,
+                bssid: Some([0x24, 0x0a, 0xc4, 0x12, 0xab, 0x0f]),
+            },
+            WifiNetwork {
+                ssid: "chaosdorf-legacy".into(),
+                password: String::new(),
+                priority: 0,
+                bssid: None,
+            },
+        ],
+        mqtt: MqttConfig {
+            host: "mqtt.chaosdorf.space".into(),
+            port: 8883,
+            username: Some("projector".into()),
+            password: Some("secret".into()),
+            client_id: None,
+            tls_server_name: None,
+        },
+        ip: IpConfig {
+            ipv4: Some(StaticIpv4 {
+                address: Ipv4Addr::new(10, 20, 0, 42),
+                prefix_len: 24,
+                gateway: Some(Ipv4Addr::new(10, 20, 0, 1)),
+                dns_servers: vec![Ipv4Addr::new(10, 20, 0, 1)],
+            }),
+            ipv6: true,
+        },
+        device_id: Some("projector-hackcenter".into()),
+        poll_interval_secs: 5,
+    }
+}
+
+#[test]
+fn round_trip() {
+    assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+
+    let encoded = config().encode().unwrap();
+    assert_eq!(encoded[..2], VERSION.to_le_bytes());
+    let (body, crc) = encoded.split_at(encoded.len() - 4);
+    assert_eq!(crc, crc32(body).to_le_bytes());
+    assert_eq!(Config::decode(&encoded), Ok(config()));
+
+    // flash reads come in whole buffers
+    let mut buffer = encoded.clone();
+    buffer.resize(MAX_RECORD_SIZE, 0xff);
+    assert_eq!(Config::decode(&buffer), Ok(config()));
+}
+
+#[test]
+fn missing_fields_take_defaults() {
+    let payload =
+        r#"{"networks":[{"ssid":"a","password":"b"}],"mqtt":{"host":"broker","port":1883}}"#;
+    let config = Config::decode(&record(VERSION, payload)).unwrap();
+    assert_eq!(config.networks[0].priority, 0);
+    assert_eq!(config.networks[0].bssid, None);
+    assert_eq!(config.mqtt.username, None);
+    assert_eq!(config.mqtt.tls_server_name, None);
+    assert_eq!(config.ip, IpConfig::default());
+    assert_eq!(config.device_id, None);
+    assert_eq!(
+        config.poll_interval_secs,
+        Config::default().poll_interval_secs
+    );
+}
+
+#[test]
+fn tls_server_name_follows_the_broker() {
+    let mut mqtt = MqttConfig::default();
+    assert_eq!(
+        mqtt.server_name("mqtt.chaosdorf.space"),
+        Some("mqtt.chaosdorf.space")
+    );
+    // discovered brokers have .local names
+    assert_eq!(mqtt.server_name("mqtt-a.local"), Some("mqtt-a.local"));
+    // certificates are checked against names only
+    assert_eq!(mqtt.server_name("10.20.0.2"), None);
+    assert_eq!(mqtt.server_name("fd00::2"), None);
+
+    mqtt.tls_server_name = Some("mqtt.chaosdorf.space".into());
+    assert_eq!(mqtt.server_name("10.20.0.2"), Some("mqtt.chaosdorf.space"));
+}
+
+#[test]
+fn migrates_single_network() {
+    let payload = r#"{"wifi":{"ssid":"chaosdorf","password":"hunter22"},
+        "mqtt":{"host":"broker","port":1883},"device_id":null,"poll_interval_secs":10}"#;
+    let record = record(1, payload);
+    let config = Config::decode(&record).unwrap();
+    assert_eq!(
+        config.networks,
+        [WifiNetwork {
+            ssid: "chaosdorf".into(),
+            password: "hunter22".into(),
+            priority: 0,
+            bssid: None,
+        }]
+    );
+    assert_eq!(config.mqtt.host, "broker");
+    // stored again in the current format
+    assert_ne!(config.encode().unwrap(), record);
+
+    // nothing set up yet
+    let payload = r#"{"wifi":{"ssid":"","password":""},"mqtt":{"host":"","port":1883}}"#;
+    let config = Config::decode(&self::record(1, payload)).unwrap();
+    assert_eq!(config.networks, []);
+}
+
+#[test]
+fn damaged_records() {
+    let encoded = config().encode().unwrap();
+
+    let mut flipped = encoded.clone();
+    flipped[10] ^= 0x01;
+    assert_eq!(Config::decode(&flipped), Err(ConfigError::Checksum));
+
+    assert_eq!(
+        Config::decode(&encoded[..encoded.len() - 1]),
+        Err(ConfigError::Truncated)
+    );
+    assert_eq!(Config::decode(&[0x01]), Err(ConfigError::Truncated));
+    // erased flash
+    assert_eq!(
+        Config::decode(&[0xff; MAX_RECORD_SIZE]),
+        Err(ConfigError::Truncated)
+    );
+
+    assert_eq!(
+        Config::decode(&record(VERSION, "[]")),
+        Err(ConfigError::Invalid)
+    );
+}
+
+#[test]
+fn newer_versions_are_refused() {
+    let payload = serde_json::to_string(&config()).unwrap();
+    assert_eq!(
+        Config::decode(&record(VERSION + 1, &payload)),
+        Err(ConfigError::UnsupportedVersion(VERSION + 1))
+    );
+    assert_eq!(
+        Config::decode(&record(0, &payload)),
+        Err(ConfigError::UnsupportedVersion(0))
+    );
+}
+
+#[test]
+fn static_addresses() {
+    assert_eq!(
+        StaticIpv4::parse("10.20.0.42/24", "10.20.0.1", "10.20.0.1, 9.9.9.9"),
+        Some(StaticIpv4 {
+            address: Ipv4Addr::new(10, 20, 0, 42),
+            prefix_len: 24,
+            gateway: Some(Ipv4Addr::new(10, 20, 0, 1)),
+            dns_servers: vec![Ipv4Addr::new(10, 20, 0, 1), Ipv4Addr::new(9, 9, 9, 9)],
+        })
+    );
+    let bare = StaticIpv4::parse(" 10.20.0.42/32 ", "", "").unwrap();
+    assert_eq!(bare.gateway, None);
+    assert!(bare.dns_servers.is_empty());
+
+    for (address, gateway, dns_servers) in [
+        ("10.20.0.42", "", ""),
+        ("10.20.0.42/33", "", ""),
+        ("10.20.0/24", "", ""),
+        ("10.20.0.42/24", "router", ""),
+        ("10.20.0.42/24", "", "10.20.0.1;9.9.9.9"),
+        ("10.20.0.42/24", "", "1.1.1.1 8.8.8.8 9.9.9.9 10.0.0.1"),
+    ] {
+        assert_eq!(StaticIpv4::parse(address, gateway, dns_servers), None);
+    }
+}
+
+#[test]
+fn oversized_config() {
+    let mut config = config();
+    config.networks[0].password = "x".repeat(MAX_RECORD_SIZE);
+    assert_eq!(config.encode(), Err(ConfigError::TooLarge));
+}
@@ -0,0 +1,62 @@
+use projector_controller::device::{Device, InvalidDeviceId};
+
+#[test]
+fn id_from_mac() {
+    let mac = [0x24, 0x0a, 0xc4, 0x12, 0xab, 0x0f];
+    let device = Device::from_mac(mac);
+    assert_eq!(device.id(), "projector-controller-12ab0f");
+    assert_eq!(device.mac(), Some(mac));
+    assert_eq!(Device::new(device.id()).unwrap().with_mac(mac), device);
+}
+
+#[test]
+fn valid_ids() {
+    for id in ["a", "projector-hackcenter", "projector_2", &"x".repeat(32)] {
+        assert_eq!(Device::new(id).unwrap().id(), id);
+    }
+}
+
+#[test]
+fn invalid_ids() {
+    for id in [
+        "",
+        "with space",
+        "a/b",
+        "wild+",
+        "hash#",
+        "dots.",
+        &"x".repeat(33),
+    ] {
+        assert_eq!(Device::new(id), Err(InvalidDeviceId), "{id:?}");
+    }
+}
+
+#[test]
+fn topics() {
+    let device = Device::new("projector-hackcenter").unwrap();
+    assert_eq!(
+        device.topic("stat/power"),
+        "projector-hackcenter/stat/power"
+    );
+    assert_eq!(
+        device.availability_topic(),
+        "projector-hackcenter/availability"
+    );
+    assert_eq!(device.unique_id("power"), "projector-hackcenter_power");
+    assert_eq!(
+        device.discovery_topic("switch", "power"),
+        "homeassistant/switch/projector-hackcenter/power/config"
+    );
+}
+
+#[test]
+fn strip_prefix() {
+    let device = Device::new("projector").unwrap();
+    assert_eq!(
+        device.strip_prefix("projector/cmd/power"),
+        Some("cmd/power")
+    );
+    assert_eq!(device.strip_prefix("projector-2/cmd/power"), None);
+    assert_eq!(device.strip_prefix("projectorcmd"), None);
+    assert_eq!(device.strip_prefix("other/cmd/power"), None);
+}
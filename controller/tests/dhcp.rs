@@ -0,0 +1,117 @@
+use std::net::Ipv4Addr;
+
+use projector_controller::dhcp::{DhcpServer, POOL_SIZE};
+
+const ADDRESS: Ipv4Addr = Ipv4Addr::new(192, 168, 4, 1);
+
+fn message(kind: u8, mac: [u8; 6], options: &[u8]) -> Vec<u8> {
+    let mut message = vec![0; 240];
+    message[..4].copy_from_slice(&[1, 1, 6, 0]);
+    message[4..8].copy_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+    message[28..34].copy_from_slice(&mac);
+    message[236..].copy_from_slice(&[99, 130, 83, 99]);
+    message.extend_from_slice(&[53, 1, kind]);
+    message.extend_from_slice(options);
+    message.push(255);
+    message
+}
+
+fn mac(n: u8) -> [u8; 6] {
+    [0x02, 0, 0, 0, 0, n]
+}
+
+/// Options of a reply, by code.
+fn option(reply: &[u8], code: u8) -> Option<Vec<u8>> {
+    let mut options = &reply[240..];
+    while let [found, len, rest @ ..] = options {
+        if *found == 255 {
+            break;
+        }
+        let (value, rest) = rest.split_at(*len as usize);
+        if *found == code {
+            return Some(value.to_vec());
+        }
+        options = rest;
+    }
+    None
+}
+
+fn yiaddr(reply: &[u8]) -> Ipv4Addr {
+    Ipv4Addr::new(reply[16], reply[17], reply[18], reply[19])
+}
+
+#[test]
+fn discover_request_ack() {
+    let mut server = DhcpServer::new(ADDRESS);
+
+    let offer = server.handle(&message(1, mac(1), &[])).unwrap();
+    assert_eq!(offer[0], 2);
+    assert_eq!(offer[4..8], [0xde, 0xad, 0xbe, 0xef]);
+    assert_eq!(offer[28..34], mac(1));
+    assert!(offer.len() >= 300);
+    assert_eq!(yiaddr(&offer), Ipv4Addr::new(192, 168, 4, 2));
+    assert_eq!(option(&offer, 53), Some(vec![2]));
+    assert_eq!(option(&offer, 54), Some(vec![192, 168, 4, 1]));
+    assert_eq!(option(&offer, 1), Some(vec![255, 255, 255, 0]));
+    assert_eq!(option(&offer, 3), Some(vec![192, 168, 4, 1]));
+    assert_eq!(option(&offer, 6), Some(vec![192, 168, 4, 1]));
+    assert_eq!(option(&offer, 114), Some(b"http://192.168.4.1/".to_vec()));
+
+    // the same client gets the same address
+    let again = server.handle(&message(1, mac(1), &[])).unwrap();
+    assert_eq!(yiaddr(&again), Ipv4Addr::new(192, 168, 4, 2));
+    let other = server.handle(&message(1, mac(2), &[])).unwrap();
+    assert_eq!(yiaddr(&other), Ipv4Addr::new(192, 168, 4, 3));
+
+    let request = [50, 4, 192, 168, 4, 2, 54, 4, 192, 168, 4, 1];
+    let ack = server.handle(&message(3, mac(1), &request)).unwrap();
+    assert_eq!(option(&ack, 53), Some(vec![5]));
+    assert_eq!(yiaddr(&ack), Ipv4Addr::new(192, 168, 4, 2));
+}
+
+#[test]
+fn naks_foreign_addresses() {
+    let mut server = DhcpServer::new(ADDRESS);
+    // INIT-REBOOT with the address from another network
+    let nak = server
+        .handle(&message(3, mac(1), &[50, 4, 10, 0, 0, 23]))
+        .unwrap();
+    assert_eq!(option(&nak, 53), Some(vec![6]));
+    assert_eq!(yiaddr(&nak), Ipv4Addr::UNSPECIFIED);
+
+    // the client went with another server
+    server.handle(&message(1, mac(1), &[])).unwrap();
+    let request = [50, 4, 192, 168, 4, 2, 54, 4, 192, 168, 4, 99];
+    assert_eq!(server.handle(&message(3, mac(1), &request)), None);
+}
+
+#[test]
+fn pool_reuses_addresses() {
+    let mut server = DhcpServer::new(ADDRESS);
+    for n in 0..POOL_SIZE as u8 {
+        server.handle(&message(1, mac(n), &[])).unwrap();
+    }
+    // the first lease makes room
+    let offer = server.handle(&message(1, mac(100), &[])).unwrap();
+    assert_eq!(yiaddr(&offer), Ipv4Addr::new(192, 168, 4, 2));
+
+    // released addresses are handed out again
+    assert_eq!(server.handle(&message(7, mac(3), &[])), None);
+    let offer = server.handle(&message(1, mac(101), &[])).unwrap();
+    assert_eq!(yiaddr(&offer), Ipv4Addr::new(192, 168, 4, 5));
+}
+
+#[test]
+fn ignores_other_messages() {
+    let mut server = DhcpServer::new(ADDRESS);
+    let mut reply = message(1, mac(1), &[]);
+    reply[0] = 2;
+    assert_eq!(server.handle(&reply), None);
+
+    let mut bootp = message(1, mac(1), &[]);
+    bootp[236] = 0;
+    assert_eq!(server.handle(&bootp), None);
+
+    assert_eq!(server.handle(&message(8, mac(1), &[])), None);
+    assert_eq!(server.handle(&[1, 1, 6]), None);
+}
@@ -0,0 +1,57 @@
+use std::net::Ipv4Addr;
+
+use projector_controller::dns;
+
+const ADDRESS: Ipv4Addr = Ipv4Addr::new(192, 168, 4, 1);
+
+/// A standard query with recursion desired for `name`.
+fn query(name: &str, qtype: u16) -> Vec<u8> {
+    let mut query = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in name.split('.') {
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&qtype.to_be_bytes());
+    query.extend_from_slice(&1u16.to_be_bytes());
+    query
+}
+
+#[test]
+fn answers_every_name() {
+    let query = query("connectivitycheck.gstatic.com", 1);
+    let reply = dns::catch_all_reply(&query, ADDRESS).unwrap();
+    // id, response + authoritative + recursion desired, 1 question, 1 answer
+    assert_eq!(
+        reply[..12],
+        [0x12, 0x34, 0x85, 0x00, 0, 1, 0, 1, 0, 0, 0, 0]
+    );
+    assert_eq!(reply[12..query.len()], query[12..]);
+    assert_eq!(
+        reply[query.len()..],
+        [0xc0, 12, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 192, 168, 4, 1]
+    );
+}
+
+#[test]
+fn no_records_for_other_types() {
+    let query = query("captive.apple.com", 28);
+    let reply = dns::catch_all_reply(&query, ADDRESS).unwrap();
+    assert_eq!(reply[6..8], [0, 0]);
+    assert_eq!(reply.len(), query.len());
+}
+
+#[test]
+fn ignores_non_queries() {
+    let mut response = query("example.org", 1);
+    response[2] |= 0x80;
+    assert_eq!(dns::catch_all_reply(&response, ADDRESS), None);
+
+    let mut compressed = query("example.org", 1);
+    compressed[12] = 0xc0;
+    assert_eq!(dns::catch_all_reply(&compressed, ADDRESS), None);
+
+    let truncated = query("example.org", 1);
+    assert_eq!(dns::catch_all_reply(&truncated[..15], ADDRESS), None);
+    assert_eq!(dns::catch_all_reply(&[], ADDRESS), None);
+}
@@ -0,0 +1,124 @@
+//! The entity registry fits the fixed buffers it is published from.
+
+use projector_controller::device::{Device, MAX_ID_LEN};
+use projector_controller::entities::{Source, CONFIG_SIZE, ENTITIES};
+use projector_controller::poller::StateUpdate;
+use projector_controller::projector::PowerPhase;
+use projector_protocol::command::{Input, Volume};
+use projector_protocol::query::{
+    ErrorStatus, LampRuntime, PowerState, Temperature, TemperatureSensor,
+};
+
+/// The longest id, with a MAC for the connections block.
+fn largest_device() -> Device {
+    Device::new(&"x".repeat(MAX_ID_LEN))
+        .unwrap()
+        .with_mac([0xff; 6])
+}
+
+#[test]
+fn configs_fit_the_buffer() {
+    let device = largest_device();
+    for entity in ENTITIES {
+        let config = serde_json::to_vec(&entity.config(&device)).unwrap();
+        assert!(
+            config.len() <= CONFIG_SIZE,
+            "{} config is {} bytes",
+            entity.id,
+            config.len()
+        );
+        // serde-json-core, which publishes them, agrees on the size
+        let mut buf = [0u8; CONFIG_SIZE];
+        let used = serde_json_core::to_slice(&entity.config(&device), &mut buf).unwrap();
+        assert_eq!(used, config.len());
+    }
+}
+
+#[test]
+fn configs_match_the_component() {
+    let device = Device::new("projector").unwrap();
+    for entity in ENTITIES {
+        let config = entity.config(&device);
+        assert_eq!(
+            config["unique_id"],
+            format!("projector_{}", entity.id),
+            "{}",
+            entity.id
+        );
+        assert_eq!(
+            config["command_topic"].is_string(),
+            entity.command.is_some()
+        );
+        assert_eq!(config["state_topic"].is_string(), entity.state.is_some());
+        assert_eq!(config["availability_topic"], "projector/availability");
+    }
+    let volume = ENTITIES.iter().find(|e| e.id == "volume").unwrap();
+    assert_eq!(volume.config(&device)["max"], Volume::MAX);
+}
+
+#[test]
+fn longest_states_fit() {
+    let updates = [
+        StateUpdate::Power(PowerState::Warming),
+        StateUpdate::PowerPhase(PowerPhase::Cooling),
+        StateUpdate::Input(*Input::ALL.iter().max_by_key(|i| i.name().len()).unwrap()),
+        StateUpdate::Shutter(false),
+        StateUpdate::Freeze(false),
+        StateUpdate::Volume(Volume::new(Volume::MAX).unwrap()),
+        StateUpdate::LampRuntime(LampRuntime { hours: u32::MAX }),
+        StateUpdate::Temperature(
+            TemperatureSensor::Exhaust,
+            Temperature { celsius: i16::MIN },
+        ),
+        StateUpdate::ErrorStatus(ErrorStatus(u16::MAX)),
+        StateUpdate::Unreachable,
+    ];
+    for update in updates {
+        let shown = ENTITIES
+            .iter()
+            .filter_map(|entity| entity.state?.format(&update))
+            .count();
+        assert!(shown > 0, "{update:?} too long or not shown by any entity");
+    }
+}
+
+#[test]
+fn states_go_to_their_entities() {
+    let power = StateUpdate::Power(PowerState::On);
+    let shown: Vec<&str> = ENTITIES
+        .iter()
+        .filter(|entity| entity.state.and_then(|s| s.format(&power)).is_some())
+        .map(|entity| entity.id)
+        .collect();
+    assert_eq!(shown, ["power", "status"]);
+
+    // power and phase become unknown, the rest keeps its last value
+    let unknown: Vec<&str> = ENTITIES
+        .iter()
+        .filter(|entity| {
+            entity
+                .state
+                .and_then(|s| s.format(&StateUpdate::Unreachable))
+                .is_some_and(|payload| payload == "None")
+        })
+        .map(|entity| entity.id)
+        .collect();
+    assert_eq!(unknown, ["power", "status", "power_state"]);
+
+    let intake = StateUpdate::Temperature(TemperatureSensor::Intake, Temperature { celsius: 25 });
+    assert_eq!(
+        Source::Temperature(TemperatureSensor::Intake)
+            .format(&intake)
+            .unwrap(),
+        "25"
+    );
+    assert!(Source::Temperature(TemperatureSensor::Exhaust)
+        .format(&intake)
+        .is_none());
+    assert_eq!(
+        Source::ErrorStatus
+            .format(&StateUpdate::ErrorStatus(ErrorStatus(0x0004)))
+            .unwrap(),
+        "0004"
+    );
+}
@@ -0,0 +1,173 @@
+use std::net::Ipv4Addr;
+
+use projector_controller::mdns::{self, Found, Responder, Service};
+
+const ADDRESS: Ipv4Addr = Ipv4Addr::new(10, 20, 0, 42);
+
+const SERVICES: [Service; 1] = [Service {
+    kind: "_http._tcp",
+    port: 80,
+    txt: &["path=/"],
+}];
+
+fn responder() -> Responder<'static> {
+    Responder::new("projector-lounge", &SERVICES)
+}
+
+/// The TTL of the only answer in a response to a single question.
+fn ttl(response: &[u8], answer_at: usize) -> u32 {
+    u32::from_be_bytes(response[answer_at + 4..answer_at + 8].try_into().unwrap())
+}
+
+#[test]
+fn answers_for_the_host_name() {
+    let query = mdns::address_query("Projector-Lounge.local", 0);
+    let reply = responder().reply(&query, ADDRESS, false).unwrap();
+    // id 0, response + authoritative, no questions, 1 answer
+    assert_eq!(reply[..12], [0, 0, 0x84, 0, 0, 0, 0, 1, 0, 0, 0, 0]);
+    let name = b"\x10projector-lounge\x05local\x00";
+    assert_eq!(reply[12..12 + name.len()], name[..]);
+    let at = 12 + name.len();
+    // A, IN with cache flush, 120 s, 4 bytes
+    assert_eq!(reply[at..at + 4], [0, 1, 0x80, 1]);
+    assert_eq!(ttl(&reply, at), 120);
+    assert_eq!(reply[at + 8..], [0, 4, 10, 20, 0, 42]);
+    assert_eq!(mdns::addresses(&reply, "projector-lounge.local"), [ADDRESS]);
+}
+
+#[test]
+fn answers_browsing() {
+    let query = mdns::browse_query("_http._tcp", 0);
+    let reply = responder().reply(&query, ADDRESS, false).unwrap();
+    // PTR, then SRV, TXT and A so nobody has to ask again
+    assert_eq!(reply[6..12], [0, 1, 0, 0, 0, 3]);
+    assert_eq!(
+        mdns::parse_browse(&reply, "_http._tcp"),
+        Some(Found {
+            host: "projector-lounge.local".into(),
+            port: 80,
+            addresses: vec![ADDRESS],
+        })
+    );
+    assert_eq!(mdns::parse_browse(&reply, mdns::MQTT), None);
+
+    let mut query = mdns::browse_query("_services._dns-sd._udp", 0);
+    // unicast response requested, answered all the same
+    let len = query.len();
+    query[len - 2] |= 0x80;
+    let reply = responder().reply(&query, ADDRESS, false).unwrap();
+    assert_eq!(reply[6..12], [0, 1, 0, 0, 0, 0]);
+}
+
+#[test]
+fn answers_legacy_queries() {
+    let query = mdns::browse_query("_http._tcp", 0x1234);
+    let reply = responder().reply(&query, ADDRESS, true).unwrap();
+    // id and question back
+    assert_eq!(reply[..2], [0x12, 0x34]);
+    assert_eq!(reply[4..6], [0, 1]);
+    assert_eq!(reply[12..query.len()], query[12..]);
+    // short TTLs and no cache flush bit
+    let at = query.len() + "\x0a_http\x04_tcp\x05local\x00".len();
+    assert_eq!(reply[at..at + 4], [0, 12, 0, 1]);
+    assert_eq!(ttl(&reply, at), 10);
+    assert_eq!(
+        mdns::parse_browse(&reply, "_http._tcp").unwrap().addresses,
+        [ADDRESS]
+    );
+}
+
+#[test]
+fn announces_everything() {
+    let announcement = responder().announcement(ADDRESS);
+    assert_eq!(announcement[4..12], [0, 0, 0, 5, 0, 0, 0, 0]);
+    let found = mdns::parse_browse(&announcement, "_http._tcp").unwrap();
+    assert_eq!(found.port, 80);
+    assert_eq!(found.addresses, [ADDRESS]);
+}
+
+#[test]
+fn ignores_the_rest() {
+    let query = mdns::address_query("other-device.local", 0);
+    assert_eq!(responder().reply(&query, ADDRESS, false), None);
+
+    let response = responder().announcement(ADDRESS);
+    assert_eq!(responder().reply(&response, ADDRESS, false), None);
+
+    let query = mdns::address_query("projector-lounge.local", 0);
+    assert_eq!(responder().reply(&query[..20], ADDRESS, false), None);
+    assert_eq!(responder().reply(&[], ADDRESS, false), None);
+
+    // a pointer to itself
+    let mut looping = query.clone();
+    looping[12..14].copy_from_slice(&[0xc0, 12]);
+    assert_eq!(responder().reply(&looping, ADDRESS, false), None);
+}
+
+/// Labels of a name, uncompressed.
+fn name(name: &str) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    for label in name.split('.') {
+        encoded.push(label.len() as u8);
+        encoded.extend_from_slice(label.as_bytes());
+    }
+    encoded.push(0);
+    encoded
+}
+
+fn record(encoded_name: &[u8], rtype: u16, data: &[u8]) -> Vec<u8> {
+    let mut record = encoded_name.to_vec();
+    record.extend_from_slice(&rtype.to_be_bytes());
+    record.extend_from_slice(&[0, 1, 0, 0, 0, 10]);
+    record.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    record.extend_from_slice(data);
+    record
+}
+
+#[test]
+fn finds_a_broker() {
+    // as a broker's responder sends it: compressed, two brokers, the
+    // second one's records first, two addresses for the first
+    let mut response = vec![0, 7, 0x84, 0, 0, 0, 0, 2, 0, 0, 0, 4];
+    let type_at = response.len();
+    response.extend(record(
+        &name("_mqtt._tcp.local"),
+        12,
+        &name("broker-b._mqtt._tcp.local"),
+    ));
+    let mut instance = name("broker-a");
+    instance.truncate(instance.len() - 1);
+    instance.extend_from_slice(&[0xc0, type_at as u8]);
+    response.extend(record(&[0xc0, type_at as u8], 12, &instance));
+    response.extend(record(
+        &name("broker-b._mqtt._tcp.local"),
+        33,
+        &[[0, 0, 0, 0, 0x07, 0x5b].as_slice(), &name("mqtt-b.local")].concat(),
+    ));
+    let host_at = response.len() + instance.len() + 10 + 6;
+    response.extend(record(
+        &instance,
+        33,
+        &[[0, 0, 0, 0, 0x07, 0x5c].as_slice(), &name("mqtt-a.local")].concat(),
+    ));
+    response.extend(record(&[0xc0, host_at as u8], 1, &[10, 20, 0, 2]));
+    response.extend(record(&name("mqtt-a.local"), 1, &[10, 20, 0, 3]));
+
+    assert_eq!(
+        mdns::parse_browse(&response, mdns::MQTT),
+        Some(Found {
+            host: "mqtt-b.local".into(),
+            port: 1883,
+            addresses: vec![],
+        })
+    );
+    assert_eq!(
+        mdns::addresses(&response, "MQTT-A.local"),
+        [Ipv4Addr::new(10, 20, 0, 2), Ipv4Addr::new(10, 20, 0, 3)]
+    );
+
+    // queries are not responses
+    let query = mdns::browse_query(mdns::MQTT, 7);
+    assert_eq!(mdns::parse_browse(&query, mdns::MQTT), None);
+    assert!(mdns::addresses(&query, "mqtt-a.local").is_empty());
+}
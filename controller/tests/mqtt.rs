@@ -0,0 +1,479 @@
+//! The whole controller against the simulator and an in-memory broker.
+//!
+//! The request queue and state channels are statics, so this file holds a
+//! single test that walks through the steps in order.
+
+use std::convert::Infallible;
+use std::sync::Mutex;
+use std::time::Duration as StdDuration;
+
+use embassy_futures::block_on;
+use embassy_futures::select::{select4, Either4};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{with_timeout, Duration, Timer};
+use projector_controller::device::Device;
+use projector_controller::mqtt::{self, Connector, Message, QoS, Session};
+use projector_controller::poller;
+use projector_controller::projector::{self, ProjectorLink};
+use projector_simulator::pipe::SimPort;
+use projector_simulator::{Config, SharedSimulator, Simulator};
+use serde_json::json;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Published {
+    topic: String,
+    payload: Vec<u8>,
+    qos: QoS,
+    retain: bool,
+    correlation_data: Option<Vec<u8>>,
+}
+
+/// A message from the test, with the MQTT v5 response properties.
+struct Incoming {
+    topic: String,
+    payload: Vec<u8>,
+    response: Option<(String, Vec<u8>)>,
+}
+
+/// Stands in for the broker: records what the controller publishes and
+/// hands it the messages the test injects.
+#[derive(Default)]
+struct Broker {
+    published: Mutex<Vec<Published>>,
+    subscriptions: Mutex<Vec<String>>,
+    disconnected: Mutex<bool>,
+}
+
+static INCOMING: Channel<CriticalSectionRawMutex, Incoming, 4> = Channel::new();
+
+impl Broker {
+    fn published(&self, topic: &str) -> Vec<String> {
+        self.published
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| p.topic == topic)
+            .map(|p| String::from_utf8(p.payload.clone()).unwrap())
+            .collect()
+    }
+
+    /// Payloads of all discovery messages.
+    fn configs(&self) -> Vec<String> {
+        self.published
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| p.topic.starts_with("homeassistant/"))
+            .map(|p| String::from_utf8(p.payload.clone()).unwrap())
+            .collect()
+    }
+
+    /// Wait for the first JSON response on `topic`.
+    async fn response(&self, topic: &str) -> (serde_json::Value, Option<Vec<u8>>) {
+        let response = with_timeout(Duration::from_secs(5), async {
+            loop {
+                let found = self
+                    .published
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .find(|p| p.topic == topic)
+                    .cloned();
+                if let Some(p) = found {
+                    let json = serde_json::from_slice(&p.payload).unwrap();
+                    return (json, p.correlation_data);
+                }
+                Timer::after_millis(10).await;
+            }
+        })
+        .await;
+        response.unwrap_or_else(|_| panic!("no response on {topic}"))
+    }
+
+    fn is_retained(&self, topic: &str) -> bool {
+        self.published
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|p| p.topic == topic && p.retain)
+    }
+
+    /// Every publish on `topic` asked for QoS 1.
+    fn is_acknowledged(&self, topic: &str) -> bool {
+        self.published
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| p.topic == topic)
+            .all(|p| p.qos == QoS::AtLeastOnce)
+    }
+
+    /// Wait until `topic` has seen `payload`.
+    async fn expect(&self, topic: &str, payload: &str) {
+        let seen = with_timeout(Duration::from_secs(5), async {
+            while !self.published(topic).iter().any(|p| p == payload) {
+                Timer::after_millis(10).await;
+            }
+        })
+        .await;
+        assert!(
+            seen.is_ok(),
+            "no {payload} on {topic}, got {:?}",
+            self.published(topic)
+        );
+    }
+
+    /// Wait until the latest payload on `topic` is `payload`.
+    async fn expect_last(&self, topic: &str, payload: &str, within: Duration) {
+        let seen = with_timeout(within, async {
+            while self.published(topic).last().map(String::as_str) != Some(payload) {
+                Timer::after_millis(10).await;
+            }
+        })
+        .await;
+        assert!(
+            seen.is_ok(),
+            "{payload} not last on {topic}, got {:?}",
+            self.published(topic)
+        );
+    }
+}
+
+struct FakeConnector<'a> {
+    broker: &'a Broker,
+}
+
+impl Connector for FakeConnector<'_> {
+    type Error = Infallible;
+    type Session<'a>
+        = FakeSession<'a>
+    where
+        Self: 'a;
+
+    async fn connect(&mut self) -> Result<FakeSession<'_>, Infallible> {
+        Ok(FakeSession {
+            broker: self.broker,
+            current: None,
+        })
+    }
+}
+
+struct FakeSession<'a> {
+    broker: &'a Broker,
+    current: Option<Incoming>,
+}
+
+impl Session for FakeSession<'_> {
+    type Error = Infallible;
+
+    async fn publish(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        qos: QoS,
+        retain: bool,
+    ) -> Result<(), Infallible> {
+        self.broker.published.lock().unwrap().push(Published {
+            topic: topic.into(),
+            payload: payload.into(),
+            qos,
+            retain,
+            correlation_data: None,
+        });
+        Ok(())
+    }
+
+    async fn reply(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        correlation_data: Option<&[u8]>,
+    ) -> Result<(), Infallible> {
+        self.broker.published.lock().unwrap().push(Published {
+            topic: topic.into(),
+            payload: payload.into(),
+            qos: QoS::AtMostOnce,
+            retain: false,
+            correlation_data: correlation_data.map(Into::into),
+        });
+        Ok(())
+    }
+
+    async fn subscribe(&mut self, topics: &[&str]) -> Result<(), Infallible> {
+        let mut subscriptions = self.broker.subscriptions.lock().unwrap();
+        subscriptions.extend(topics.iter().map(|t| t.to_string()));
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<Message<'_>, Infallible> {
+        let incoming = self.current.insert(INCOMING.receive().await);
+        let response = incoming.response.as_ref();
+        Ok(
+            Message::new(&incoming.topic, &incoming.payload).with_response(
+                response.map(|(topic, _)| topic.as_str()),
+                response.map(|(_, correlation)| correlation.as_slice()),
+            ),
+        )
+    }
+
+    async fn ping(&mut self) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<(), Infallible> {
+        *self.broker.disconnected.lock().unwrap() = true;
+        Ok(())
+    }
+}
+
+async fn send(topic: &str, payload: &str) {
+    INCOMING
+        .send(Incoming {
+            topic: topic.into(),
+            payload: payload.into(),
+            response: None,
+        })
+        .await;
+}
+
+/// Send an MQTT v5 request that wants the reply on `response_topic`.
+async fn request(topic: &str, payload: &str, response_topic: &str, correlation: &[u8]) {
+    INCOMING
+        .send(Incoming {
+            topic: topic.into(),
+            payload: payload.into(),
+            response: Some((response_topic.into(), correlation.into())),
+        })
+        .await;
+}
+
+fn simulator() -> SharedSimulator {
+    Simulator::new(Config {
+        warm_up: StdDuration::from_secs(30),
+        ..Config::default()
+    })
+    .shared()
+}
+
+async fn scenario(broker: &Broker, sim: &SharedSimulator) {
+    // discovery and the first poll
+    broker
+        .expect("projector-controller/availability", "online")
+        .await;
+    assert!(broker.is_retained("homeassistant/switch/projector-controller/power/config"));
+    for config in broker.configs() {
+        assert!(
+            config.contains(r#""device":{"identifiers":["projector-controller"]"#),
+            "no device block in {config}"
+        );
+    }
+    assert!(broker.is_retained("homeassistant/select/projector-controller/input/config"));
+    assert!(broker.is_retained("homeassistant/number/projector-controller/volume/config"));
+    broker
+        .expect("projector-controller/stat/intake_temperature", "25")
+        .await;
+    assert!(broker
+        .subscriptions
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|t| t == "projector-controller/cmd/power"));
+    broker
+        .expect("projector-controller/stat/power", "OFF")
+        .await;
+    broker
+        .expect("projector-controller/stat/lamp_hours", "1200")
+        .await;
+    assert!(broker.is_acknowledged("projector-controller/stat/lamp_hours"));
+    assert!(broker.is_acknowledged("projector-controller/availability"));
+    broker
+        .expect("projector-controller/stat/power_state", "off")
+        .await;
+
+    // power on, the command triggers a poll straight away
+    send("projector-controller/cmd/power", "ON").await;
+    broker.expect("projector-controller/stat/power", "ON").await;
+    broker
+        .expect("projector-controller/stat/power_state", "warming")
+        .await;
+    assert!(sim.lock().unwrap().received().iter().any(|r| r == "PON"));
+    broker
+        .expect("projector-controller/result/power", "OK")
+        .await;
+
+    // once warmed up, the picture and sound settings can be changed
+    sim.lock().unwrap().advance(StdDuration::from_secs(30));
+    broker
+        .expect("projector-controller/stat/power_state", "on")
+        .await;
+    send("projector-controller/cmd/input", "HDMI2").await;
+    broker
+        .expect("projector-controller/stat/input", "HDMI2")
+        .await;
+    send("projector-controller/cmd/volume", "20.0").await;
+    broker
+        .expect("projector-controller/stat/volume", "20")
+        .await;
+    send("projector-controller/cmd/shutter", "ON").await;
+    broker
+        .expect("projector-controller/stat/shutter", "ON")
+        .await;
+    broker
+        .expect("projector-controller/stat/freeze", "OFF")
+        .await;
+
+    // out of range and unknown values are dropped
+    send("projector-controller/cmd/volume", "64").await;
+    send("projector-controller/cmd/input", "SCART").await;
+    send("projector-controller/cmd/freeze", "ON").await;
+    broker
+        .expect("projector-controller/stat/freeze", "ON")
+        .await;
+    assert_eq!(broker.published("projector-controller/stat/volume"), ["20"]);
+    assert_eq!(
+        broker.published("projector-controller/result/volume"),
+        ["OK", "INVALID"]
+    );
+    assert_eq!(
+        broker.published("projector-controller/stat/input"),
+        ["HDMI1", "HDMI2"]
+    );
+
+    // raw payloads go straight to the projector, the reply comes back
+    send("projector-controller/cmd/raw", "QIN").await;
+    let (report, _) = broker.response("projector-controller/stat/raw").await;
+    assert_eq!(
+        report,
+        json!({"command": "QIN", "ascii": "HD2", "hex": "484432"})
+    );
+    broker
+        .expect("projector-controller/result/raw", "HD2")
+        .await;
+
+    // the remote's menu keys are buttons in Home Assistant
+    send("projector-controller/cmd/menu", "PRESS").await;
+    broker
+        .expect("projector-controller/result/menu", "OK")
+        .await;
+    assert!(sim.lock().unwrap().received().iter().any(|r| r == "OMN"));
+
+    // scripts get JSON answers, by default on result/json
+    send(
+        "projector-controller/cmd/json",
+        r#"{"id": 7, "command": "volume", "value": 30}"#,
+    )
+    .await;
+    let (response, correlation) = broker.response("projector-controller/result/json").await;
+    assert_eq!(response, json!({"id": 7, "ok": true, "result": "OK"}));
+    assert_eq!(correlation, None);
+    broker
+        .expect("projector-controller/stat/volume", "30")
+        .await;
+
+    // or where an MQTT v5 request asks for them
+    request(
+        "projector-controller/cmd/json",
+        r#"{"id": "lamp", "query": "lamp_hours"}"#,
+        "scripts/lamp",
+        b"req-1",
+    )
+    .await;
+    let (response, correlation) = broker.response("scripts/lamp").await;
+    assert_eq!(
+        response,
+        json!({"id": "lamp", "ok": true, "result": "1200"})
+    );
+    assert_eq!(correlation.as_deref(), Some(&b"req-1"[..]));
+
+    request(
+        "projector-controller/cmd/json",
+        r#"{"id": 8, "command": "self_destruct"}"#,
+        "scripts/unknown",
+        b"req-2",
+    )
+    .await;
+    let (response, _) = broker.response("scripts/unknown").await;
+    assert_eq!(
+        response,
+        json!({"id": 8, "ok": false, "error": "unknown command"})
+    );
+
+    request(
+        "projector-controller/cmd/json",
+        "volume 30",
+        "scripts/garbage",
+        b"req-3",
+    )
+    .await;
+    let (response, _) = broker.response("scripts/garbage").await;
+    assert_eq!(
+        response,
+        json!({"id": null, "ok": false, "error": "invalid request"})
+    );
+
+    // without the projector, polls stop at the power query and after a few
+    // of them power is unknown until it answers again
+    sim.lock().unwrap().set_connected(false);
+    broker
+        .expect_last(
+            "projector-controller/stat/power",
+            "None",
+            Duration::from_secs(15),
+        )
+        .await;
+    broker
+        .expect_last(
+            "projector-controller/stat/power_state",
+            "None",
+            Duration::from_secs(1),
+        )
+        .await;
+    sim.lock().unwrap().set_connected(true);
+    broker
+        .expect_last(
+            "projector-controller/stat/power",
+            "ON",
+            Duration::from_secs(5),
+        )
+        .await;
+    broker
+        .expect_last(
+            "projector-controller/stat/power_state",
+            "on",
+            Duration::from_secs(5),
+        )
+        .await;
+
+    // availability is announced once, after that it is up to the will
+    assert_eq!(
+        broker.published("projector-controller/availability"),
+        ["online"]
+    );
+
+    // shutting down says goodbye, then the MQTT side returns
+    mqtt::SHUTDOWN.signal(());
+    core::future::pending::<()>().await;
+}
+
+#[test]
+fn controller_round_trip() {
+    let broker = Broker::default();
+    let sim = simulator();
+    let device = Device::new("projector-controller").unwrap();
+    let mut connector = FakeConnector { broker: &broker };
+
+    let result = block_on(select4(
+        projector::run(ProjectorLink::new(SimPort::new(sim.clone()))),
+        poller::run(Duration::from_secs(1)),
+        mqtt::run(&mut connector, &mut (), &device),
+        scenario(&broker, &sim),
+    ));
+    assert!(matches!(result, Either4::Third(())));
+    assert_eq!(
+        broker.published("projector-controller/availability"),
+        ["online", "offline"]
+    );
+    assert!(broker.is_retained("projector-controller/availability"));
+    assert!(*broker.disconnected.lock().unwrap());
+}
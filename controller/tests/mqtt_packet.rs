@@ -0,0 +1,155 @@
+use projector_controller::mqtt::QoS;
+use projector_controller::mqtt_packet::{self, Connect, Malformed, Packet, Publish};
+
+#[test]
+fn connects_with_will_and_credentials() {
+    let connect = mqtt_packet::connect(&Connect {
+        client_id: "p",
+        keep_alive_secs: 60,
+        username: Some("u"),
+        password: Some("pw"),
+        will: Some(("p/availability", b"offline")),
+        max_packet_size: 4096,
+    });
+    let mut expected = vec![0x10, 52, 0, 4, b'M', b'Q', b'T', b'T', 5];
+    // user, password, retained QoS 1 will, clean start
+    expected.extend_from_slice(&[0xee, 0, 60]);
+    // maximum packet size
+    expected.extend_from_slice(&[5, 0x27, 0, 0, 0x10, 0]);
+    expected.extend_from_slice(&[0, 1, b'p']);
+    expected.extend_from_slice(&[0, 0, 14]);
+    expected.extend_from_slice(b"p/availability");
+    expected.extend_from_slice(&[0, 7]);
+    expected.extend_from_slice(b"offline");
+    expected.extend_from_slice(&[0, 1, b'u', 0, 2, b'p', b'w']);
+    assert_eq!(connect, expected);
+
+    let bare = mqtt_packet::connect(&Connect {
+        client_id: "p",
+        keep_alive_secs: 60,
+        username: None,
+        password: None,
+        will: None,
+        max_packet_size: 4096,
+    });
+    assert_eq!(bare[9], 0x02);
+}
+
+#[test]
+fn publishes() {
+    let state = mqtt_packet::publish("p/stat/power", b"ON", QoS::AtLeastOnce, true, 0x1234, None);
+    let mut expected = vec![0x33, 19, 0, 12];
+    expected.extend_from_slice(b"p/stat/power");
+    expected.extend_from_slice(&[0x12, 0x34, 0]);
+    expected.extend_from_slice(b"ON");
+    assert_eq!(state, expected);
+
+    let reply = mqtt_packet::publish("r", b"{}", QoS::AtMostOnce, false, 7, Some(b"req"));
+    assert_eq!(
+        reply,
+        [0x30, 12, 0, 1, b'r', 6, 0x09, 0, 3, b'r', b'e', b'q', b'{', b'}']
+    );
+}
+
+#[test]
+fn subscribes_and_acknowledges() {
+    assert_eq!(
+        mqtt_packet::subscribe(2, &["a", "bc"]),
+        [0x82, 12, 0, 2, 0, 0, 1, b'a', 1, 0, 2, b'b', b'c', 1]
+    );
+    assert_eq!(mqtt_packet::puback(0x0102), [0x40, 2, 1, 2]);
+    assert_eq!(mqtt_packet::pingreq(), [0xc0, 0]);
+    assert_eq!(mqtt_packet::disconnect(), [0xe0, 0]);
+}
+
+#[test]
+fn finds_packet_boundaries() {
+    assert_eq!(mqtt_packet::packet_len(&[]), Ok(None));
+    assert_eq!(mqtt_packet::packet_len(&[0x30]), Ok(None));
+    assert_eq!(mqtt_packet::packet_len(&[0xd0, 0, 0x30]), Ok(Some(2)));
+    // 200 bytes take two length bytes
+    assert_eq!(mqtt_packet::packet_len(&[0x30, 0xc8]), Ok(None));
+    assert_eq!(mqtt_packet::packet_len(&[0x30, 0xc8, 0x01]), Ok(Some(203)));
+    assert_eq!(
+        mqtt_packet::packet_len(&[0x30, 0xff, 0xff, 0xff, 0xff, 0x01]),
+        Err(Malformed)
+    );
+}
+
+#[test]
+fn decodes_broker_packets() {
+    assert_eq!(
+        mqtt_packet::decode(&[0x20, 3, 0, 0x87, 0]),
+        Some(Packet::ConnAck { reason: 0x87 })
+    );
+    assert_eq!(
+        mqtt_packet::decode(&[0x40, 2, 0, 5]),
+        Some(Packet::PubAck { id: 5, reason: 0 })
+    );
+    assert_eq!(
+        mqtt_packet::decode(&[0x40, 4, 0, 5, 0x87, 0]),
+        Some(Packet::PubAck {
+            id: 5,
+            reason: 0x87
+        })
+    );
+    assert_eq!(
+        mqtt_packet::decode(&[0x90, 5, 0, 2, 0, 1, 0x80]),
+        Some(Packet::SubAck {
+            id: 2,
+            reasons: &[1, 0x80]
+        })
+    );
+    assert_eq!(mqtt_packet::decode(&[0xd0, 0]), Some(Packet::PingResp));
+    assert_eq!(
+        mqtt_packet::decode(&[0xe0, 1, 0x8e]),
+        Some(Packet::Disconnect { reason: 0x8e })
+    );
+
+    // a request with a response topic and correlation data, and a user
+    // property to skip
+    let mut request = vec![0x32, 0, 0, 5];
+    request.extend_from_slice(b"p/cmd");
+    request.extend_from_slice(&[0, 9, 18, 0x26, 0, 1, b'k', 0, 1, b'v', 0x08, 0, 3]);
+    request.extend_from_slice(b"s/r");
+    request.extend_from_slice(&[0x09, 0, 2, 1, 2]);
+    request.extend_from_slice(b"payload");
+    request[1] = (request.len() - 2) as u8;
+    assert_eq!(
+        mqtt_packet::decode(&request),
+        Some(Packet::Publish(Publish {
+            topic: "p/cmd",
+            payload: b"payload",
+            qos: QoS::AtLeastOnce,
+            retain: false,
+            id: Some(9),
+            response_topic: Some("s/r"),
+            correlation_data: Some(&[1, 2]),
+        }))
+    );
+
+    let mut command = vec![0x31, 0, 0, 5];
+    command.extend_from_slice(b"p/cmd");
+    command.extend_from_slice(&[0]);
+    command.extend_from_slice(b"ON");
+    command[1] = (command.len() - 2) as u8;
+    let Some(Packet::Publish(publish)) = mqtt_packet::decode(&command) else {
+        panic!("not a publish");
+    };
+    assert_eq!(
+        (publish.qos, publish.retain, publish.id),
+        (QoS::AtMostOnce, true, None)
+    );
+    assert_eq!(publish.payload, b"ON");
+}
+
+#[test]
+fn rejects_the_rest() {
+    // truncated, too long, QoS 2, unknown property, sent by clients only
+    assert_eq!(mqtt_packet::decode(&[0x40, 2, 0]), None);
+    assert_eq!(mqtt_packet::decode(&[0xd0, 0, 0]), None);
+    assert_eq!(mqtt_packet::decode(&[0x34, 6, 0, 1, b't', 0, 1, 0]), None);
+    assert_eq!(mqtt_packet::decode(&[0x30, 5, 0, 1, b't', 1, 0x7f]), None);
+    assert_eq!(mqtt_packet::decode(&[0xc0, 0]), None);
+    assert_eq!(mqtt_packet::decode(&[]), None);
+}
@@ -0,0 +1,52 @@
+use projector_controller::entities::StatePayload;
+use projector_controller::outbox::{Outbox, OUTBOX_CAPACITY};
+
+fn payload(text: &str) -> StatePayload {
+    text.try_into().unwrap()
+}
+
+fn drain(outbox: &mut Outbox) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
+    while let Some((topic, payload)) = outbox.front() {
+        entries.push((topic.to_string(), payload.to_string()));
+        outbox.pop();
+    }
+    entries
+}
+
+#[test]
+fn keeps_latest_value_per_topic() {
+    let mut outbox = Outbox::new();
+    outbox.push("p/stat/power".into(), payload("ON"));
+    outbox.push("p/stat/volume".into(), payload("10"));
+    outbox.push("p/stat/power".into(), payload("OFF"));
+    assert_eq!(outbox.len(), 2);
+    assert_eq!(
+        drain(&mut outbox),
+        [
+            ("p/stat/volume".into(), "10".into()),
+            ("p/stat/power".into(), "OFF".into())
+        ]
+    );
+    assert!(outbox.is_empty());
+}
+
+#[test]
+fn front_stays_until_popped() {
+    let mut outbox = Outbox::new();
+    outbox.push("p/stat/input".into(), payload("HDMI1"));
+    assert_eq!(outbox.front().map(|(t, _)| t), Some("p/stat/input"));
+    assert_eq!(outbox.front().map(|(t, _)| t), Some("p/stat/input"));
+    outbox.pop();
+    assert!(outbox.front().is_none());
+}
+
+#[test]
+fn drops_oldest_when_full() {
+    let mut outbox = Outbox::new();
+    for i in 0..=OUTBOX_CAPACITY {
+        outbox.push(format!("p/stat/{i}"), payload("1"));
+    }
+    assert_eq!(outbox.len(), OUTBOX_CAPACITY);
+    assert_eq!(outbox.front().map(|(t, _)| t), Some("p/stat/1"));
+}
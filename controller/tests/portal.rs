@@ -0,0 +1,233 @@
+use std::net::Ipv4Addr;
+
+use projector_controller::config::{Config, WifiNetwork, MAX_NETWORKS};
+use projector_controller::portal::{self, FormError, Network, Parsed, Request};
+
+const ADDRESS: Ipv4Addr = Ipv4Addr::new(192, 168, 4, 1);
+
+fn stored() -> Config {
+    let mut config = Config {
+        networks: vec![
+            WifiNetwork {
+                ssid: "chaosdorf".into(),
+                password: "old password".into(),
+                priority: 3,
+                bssid: Some([2, 0, 0, 0, 0, 1]),
+            },
+            WifiNetwork {
+                ssid: "chaosdorf-legacy".into(),
+                password: "legacy password".into(),
+                priority: 1,
+                bssid: None,
+            },
+        ],
+        ..Config::default()
+    };
+    config.mqtt.host = "mqtt.chaosdorf.space".into();
+    config.mqtt.username = Some("projector".into());
+    config.mqtt.password = Some("secret".into());
+    config
+}
+
+fn request<'a>(method: &'a str, path: &'a str, body: &'a [u8]) -> Request<'a> {
+    Request { method, path, body }
+}
+
+fn text(response: &[u8]) -> String {
+    String::from_utf8(response.to_vec()).unwrap()
+}
+
+#[test]
+fn parses_requests() {
+    let get = b"GET /generate_204?x=1 HTTP/1.1\r\nHost: connectivitycheck.gstatic.com\r\n\r\n";
+    assert_eq!(
+        portal::parse(get),
+        Parsed::Complete(request("GET", "/generate_204", b""))
+    );
+
+    let post = b"POST / HTTP/1.1\r\ncontent-length: 7\r\n\r\nssid=ab";
+    assert_eq!(portal::parse(&post[..20]), Parsed::Incomplete);
+    assert_eq!(portal::parse(&post[..post.len() - 1]), Parsed::Incomplete);
+    assert_eq!(
+        portal::parse(post),
+        Parsed::Complete(request("POST", "/", b"ssid=ab"))
+    );
+
+    assert_eq!(portal::parse(b"hello\r\n\r\n"), Parsed::Invalid);
+    assert_eq!(
+        portal::parse(b"POST / HTTP/1.1\r\nContent-Length: 99999\r\n\r\n"),
+        Parsed::Invalid
+    );
+    // would overflow when added to the header length
+    let huge = format!("POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n", usize::MAX);
+    assert_eq!(portal::parse(huge.as_bytes()), Parsed::Invalid);
+}
+
+#[test]
+fn applies_the_form() {
+    let form = b"ssid=Freifunk+Duesseldorf&password=&broker=10.0.0.5&port=1884\
+        &username=&mqtt_password=&device_id=projector-lounge";
+    let config = portal::apply_form(&stored(), form).unwrap();
+    // ahead of the known networks
+    assert_eq!(
+        config.networks[0],
+        WifiNetwork {
+            ssid: "Freifunk Duesseldorf".into(),
+            password: String::new(),
+            priority: 4,
+            bssid: None,
+        }
+    );
+    assert_eq!(config.networks[1..], stored().networks);
+    assert_eq!(config.mqtt.host, "10.0.0.5");
+    assert_eq!(config.mqtt.port, 1884);
+    assert_eq!(config.mqtt.username, None);
+    assert_eq!(config.mqtt.password, None);
+    assert_eq!(config.mqtt.tls_server_name, None);
+    assert_eq!(config.device_id.as_deref(), Some("projector-lounge"));
+
+    // a broker by address needs the name in its certificate for TLS
+    let form = b"ssid=chaosdorf&broker=10.0.0.5&port=8883&tls_server_name=mqtt.chaosdorf.space";
+    let config = portal::apply_form(&stored(), form).unwrap();
+    assert_eq!(
+        config.mqtt.tls_server_name.as_deref(),
+        Some("mqtt.chaosdorf.space")
+    );
+
+    // same network and user, empty password fields keep the stored ones
+    let form = b"ssid=chaosdorf&password=&broker=mqtt&port=1883&username=projector&device_id=";
+    let config = portal::apply_form(&stored(), form).unwrap();
+    assert_eq!(config.networks.len(), 2);
+    assert_eq!(config.networks[0].password, "old password");
+    assert_eq!(config.networks[0].bssid, None);
+    assert_eq!(config.mqtt.password.as_deref(), Some("secret"));
+    assert_eq!(config.device_id, None);
+
+    let form = b"ssid=chaosdorf-legacy&password=p%40ss+w%C3%B6rd%&broker=mqtt&port=1883";
+    let config = portal::apply_form(&stored(), form).unwrap();
+    assert_eq!(config.networks[0].ssid, "chaosdorf-legacy");
+    assert_eq!(config.networks[0].password, "p@ss w\u{f6}rd%");
+    assert_eq!(config.networks[0].priority, 4);
+    assert_eq!(config.networks[1].ssid, "chaosdorf");
+
+    // no broker, found on the network instead
+    let form = b"ssid=chaosdorf&broker=+&port=1883";
+    let config = portal::apply_form(&stored(), form).unwrap();
+    assert_eq!(config.mqtt.host, "");
+}
+
+#[test]
+fn keeps_the_preferred_networks() {
+    let mut config = stored();
+    for n in 0..MAX_NETWORKS {
+        let form = format!("ssid=network-{}&broker=mqtt&port=1883", n);
+        config = portal::apply_form(&config, form.as_bytes()).unwrap();
+    }
+    let ssids: Vec<_> = config.networks.iter().map(|n| n.ssid.as_str()).collect();
+    assert_eq!(
+        ssids,
+        [
+            "network-4",
+            "network-3",
+            "network-2",
+            "network-1",
+            "network-0"
+        ]
+    );
+}
+
+#[test]
+fn static_address_form() {
+    let form = b"ssid=chaosdorf&broker=mqtt&port=1883&address=10.20.0.42%2F24\
+        &gateway=10.20.0.1&dns_servers=10.20.0.1%2C+9.9.9.9&ipv6=on";
+    let config = portal::apply_form(&stored(), form).unwrap();
+    let ipv4 = config.ip.ipv4.clone().unwrap();
+    assert_eq!(ipv4.address, Ipv4Addr::new(10, 20, 0, 42));
+    assert_eq!(ipv4.prefix_len, 24);
+    assert_eq!(ipv4.gateway, Some(Ipv4Addr::new(10, 20, 0, 1)));
+    assert_eq!(ipv4.dns_servers.len(), 2);
+    assert!(config.ip.ipv6);
+
+    // shown again as entered
+    let (page, _) = portal::handle(&request("GET", "/", b""), &config, &[], ADDRESS);
+    let page = text(&page);
+    assert!(page.contains("value=\"10.20.0.42/24\""));
+    assert!(page.contains("value=\"10.20.0.1, 9.9.9.9\""));
+    assert!(page.contains("type=checkbox checked"));
+
+    // an empty address and no tick go back to DHCP and IPv4 only
+    let form = b"ssid=chaosdorf&broker=mqtt&port=1883&address=&gateway=10.20.0.1";
+    let config = portal::apply_form(&config, form).unwrap();
+    assert_eq!(config.ip.ipv4, None);
+    assert!(!config.ip.ipv6);
+}
+
+#[test]
+fn rejects_bad_forms() {
+    for (form, error) in [
+        (&b"ssid=&broker=mqtt&port=1883"[..], FormError::Ssid),
+        (
+            b"ssid=a&password=short&broker=mqtt&port=1883",
+            FormError::WifiPassword,
+        ),
+        (
+            b"ssid=a&broker=mqtt%3A%2F%2Fmqtt&port=1883",
+            FormError::Broker,
+        ),
+        (b"ssid=a&broker=mqtt&port=0", FormError::Port),
+        (b"ssid=a&broker=mqtt&port=http", FormError::Port),
+        (
+            b"ssid=a&broker=mqtt&port=1883&tls_server_name=mqtt%3A8883",
+            FormError::ServerName,
+        ),
+        (
+            b"ssid=a&broker=mqtt&port=1883&device_id=a%2Fb",
+            FormError::DeviceId,
+        ),
+        (
+            b"ssid=a&broker=mqtt&port=1883&address=10.20.0.42",
+            FormError::Address,
+        ),
+    ] {
+        assert_eq!(portal::apply_form(&stored(), form), Err(error));
+    }
+}
+
+#[test]
+fn serves_the_portal() {
+    let networks = [Network {
+        ssid: "<script>".into(),
+        rssi: -60,
+    }];
+
+    let (response, saved) =
+        portal::handle(&request("GET", "/", b""), &stored(), &networks, ADDRESS);
+    let response = text(&response);
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("value=\"mqtt.chaosdorf.space\""));
+    assert!(response.contains("&lt;script&gt;"));
+    assert!(!response.contains("old password") && !response.contains("secret"));
+    assert_eq!(saved, None);
+
+    let (response, saved) = portal::handle(
+        &request("GET", "/hotspot-detect.html", b""),
+        &stored(),
+        &[],
+        ADDRESS,
+    );
+    let response = text(&response);
+    assert!(response.starts_with("HTTP/1.1 302 Found\r\nLocation: http://192.168.4.1/\r\n"));
+    assert_eq!(saved, None);
+
+    let form = b"ssid=chaosdorf&broker=mqtt&port=1883";
+    let (response, saved) = portal::handle(&request("POST", "/", form), &stored(), &[], ADDRESS);
+    assert!(text(&response).starts_with("HTTP/1.1 200 OK\r\n"));
+    assert_eq!(saved.unwrap().mqtt.host, "mqtt");
+
+    let (response, saved) =
+        portal::handle(&request("POST", "/", b"ssid="), &stored(), &[], ADDRESS);
+    let response = text(&response);
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(response.contains(&FormError::Ssid.to_string()));
+    assert_eq!(saved, None);
+}
@@ -0,0 +1,48 @@
+//! Raw passthrough: whatever the projector answers comes back, unless the
+//! allow/deny lists keep the payload from going out at all.
+
+use embassy_futures::block_on;
+use projector_controller::projector::{ProjectorError, ProjectorLink, RawFilter};
+use projector_simulator::pipe::SimPort;
+use projector_simulator::{Config, SharedSimulator, Simulator};
+
+fn link(filter: RawFilter) -> (ProjectorLink<SimPort>, SharedSimulator) {
+    let sim = Simulator::new(Config::default()).shared();
+    let link = ProjectorLink::new(SimPort::new(sim.clone())).with_raw_filter(filter);
+    (link, sim)
+}
+
+#[test]
+fn replies_come_back_as_is() {
+    let (mut link, _sim) = link(RawFilter::default());
+    assert_eq!(block_on(link.transact_raw(b"QPW")).unwrap(), b"000");
+    assert_eq!(block_on(link.transact_raw(b"Q$L:1")).unwrap(), b"01200");
+    // error replies too, they are what exploring is about
+    assert_eq!(block_on(link.transact_raw(b"XYZ")).unwrap(), b"???");
+    assert_eq!(block_on(link.transact_raw(b"QIN")).unwrap(), b"ER401");
+}
+
+#[test]
+fn denied_codes_are_not_sent() {
+    let (mut link, sim) = link(RawFilter::default().deny("PON, POF"));
+    let result = block_on(link.transact_raw(b"PON"));
+    assert!(matches!(result, Err(ProjectorError::Forbidden { .. })));
+    assert!(sim.lock().unwrap().received().is_empty());
+    assert!(block_on(link.transact_raw(b"QPW")).is_ok());
+}
+
+#[test]
+fn allow_list_by_code() {
+    let filter = RawFilter::default().allow("QPW,IIS");
+    assert!(filter.permits(b"QPW"));
+    assert!(filter.permits(b"IIS:HD1"));
+    assert!(!filter.permits(b"QIN"));
+    assert!(!filter.permits(b"QP"));
+    assert!(!filter.permits(b""));
+
+    let filter = filter.deny("IIS");
+    assert!(filter.permits(b"QPW"));
+    assert!(!filter.permits(b"IIS:HD1"));
+
+    assert!(RawFilter::default().permits(b"anything"));
+}
@@ -0,0 +1,153 @@
+//! A broker that refuses the first connection and later drops the session:
+//! the controller has to come back and announce itself again each time.
+
+use std::sync::Mutex;
+
+use embassy_futures::block_on;
+use embassy_futures::select::{select4, Either4};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use embassy_time::{with_timeout, Duration, Timer};
+use projector_controller::device::Device;
+use projector_controller::mqtt::{self, Connector, Indicator, Message, QoS, Session};
+use projector_controller::poller;
+use projector_controller::projector::{self, ProjectorLink};
+use projector_simulator::pipe::SimPort;
+use projector_simulator::{Config, Simulator};
+
+const DISCOVERY_TOPIC: &str = "homeassistant/switch/projector-controller/power/config";
+
+#[derive(Debug)]
+struct Refused;
+
+/// Everything the controller did, in order.
+static EVENTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Makes the current session fail its next `receive`.
+static DROP_SESSION: Channel<CriticalSectionRawMutex, (), 1> = Channel::new();
+
+fn record(event: impl Into<String>) {
+    EVENTS.lock().unwrap().push(event.into());
+}
+
+fn count(event: &str) -> usize {
+    EVENTS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|e| *e == event)
+        .count()
+}
+
+async fn wait_for(event: &str, times: usize) {
+    let seen = with_timeout(Duration::from_secs(10), async {
+        while count(event) < times {
+            Timer::after_millis(10).await;
+        }
+    })
+    .await;
+    assert!(
+        seen.is_ok(),
+        "{event} not seen {times} times in {:?}",
+        EVENTS.lock().unwrap()
+    );
+}
+
+struct FlakyConnector {
+    attempts: usize,
+}
+
+impl Connector for FlakyConnector {
+    type Error = Refused;
+    type Session<'a> = FlakySession;
+
+    async fn connect(&mut self) -> Result<FlakySession, Refused> {
+        self.attempts += 1;
+        record("connect");
+        if self.attempts == 1 {
+            return Err(Refused);
+        }
+        Ok(FlakySession)
+    }
+}
+
+struct FlakySession;
+
+impl Session for FlakySession {
+    type Error = Refused;
+
+    async fn publish(
+        &mut self,
+        topic: &str,
+        _payload: &[u8],
+        _qos: QoS,
+        _retain: bool,
+    ) -> Result<(), Refused> {
+        record(topic);
+        Ok(())
+    }
+
+    async fn subscribe(&mut self, _topics: &[&str]) -> Result<(), Refused> {
+        record("subscribe");
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<Message<'_>, Refused> {
+        DROP_SESSION.receive().await;
+        Err(Refused)
+    }
+
+    async fn ping(&mut self) -> Result<(), Refused> {
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<(), Refused> {
+        Ok(())
+    }
+}
+
+struct Recorder;
+
+impl Indicator for Recorder {
+    async fn connected(&mut self) {
+        record("connected");
+    }
+
+    async fn disconnected(&mut self) {
+        record("disconnected");
+    }
+}
+
+async fn scenario() {
+    // refused, retried after the first backoff step
+    wait_for("disconnected", 1).await;
+    wait_for("connected", 1).await;
+    assert_eq!(count("connect"), 2);
+    assert_eq!(count(DISCOVERY_TOPIC), 1);
+    assert_eq!(count("subscribe"), 1);
+    wait_for("projector-controller/stat/power", 1).await;
+
+    // lost: a new session announces and subscribes again, and the states
+    // are published again even though nothing changed
+    DROP_SESSION.send(()).await;
+    wait_for("connected", 2).await;
+    assert_eq!(count("disconnected"), 2);
+    assert_eq!(count(DISCOVERY_TOPIC), 2);
+    assert_eq!(count("subscribe"), 2);
+    wait_for("projector-controller/stat/power", 2).await;
+}
+
+#[test]
+fn reconnects_and_announces_again() {
+    let sim = Simulator::new(Config::default()).shared();
+    let device = Device::new("projector-controller").unwrap();
+    let mut connector = FlakyConnector { attempts: 0 };
+
+    let result = block_on(select4(
+        projector::run(ProjectorLink::new(SimPort::new(sim))),
+        poller::run(Duration::from_secs(60)),
+        mqtt::run(&mut connector, &mut Recorder, &device),
+        scenario(),
+    ));
+    assert!(matches!(result, Either4::Fourth(())));
+}
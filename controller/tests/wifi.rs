@@ -0,0 +1,110 @@
+use projector_controller::config::WifiNetwork;
+use projector_controller::wifi::{candidates, parse_bssid, Seen};
+
+fn network(ssid: &str, priority: u8, bssid: Option<[u8; 6]>) -> WifiNetwork {
+    WifiNetwork {
+        ssid: ssid.into(),
+        password: "password".into(),
+        priority,
+        bssid,
+    }
+}
+
+fn ap(ssid: &str, last: u8, rssi: i8) -> Seen<'_> {
+    Seen {
+        ssid,
+        bssid: [2, 0, 0, 0, 0, last],
+        channel: last,
+        rssi,
+    }
+}
+
+/// (ssid, bssid's last byte) in the order they are tried.
+fn order(known: &[WifiNetwork], scan: &[Seen<'_>]) -> Vec<(String, Option<u8>)> {
+    candidates(known, scan)
+        .iter()
+        .map(|c| (c.network.ssid.clone(), c.bssid.map(|b| b[5])))
+        .collect()
+}
+
+#[test]
+fn priority_then_signal() {
+    let known = [
+        network("guest", 0, None),
+        network("chaosdorf", 2, None),
+        network("chaosdorf-5g", 2, None),
+        network("freifunk", 1, None),
+    ];
+    let scan = [
+        ap("guest", 1, -40),
+        ap("chaosdorf", 2, -80),
+        ap("chaosdorf", 3, -60),
+        ap("chaosdorf-5g", 4, -70),
+        ap("freifunk", 5, -30),
+        ap("neighbours", 6, -20),
+    ];
+    assert_eq!(
+        order(&known, &scan),
+        [
+            ("chaosdorf".into(), Some(3)),
+            ("chaosdorf-5g".into(), Some(4)),
+            ("freifunk".into(), Some(5)),
+            ("guest".into(), Some(1)),
+        ]
+    );
+
+    let first = candidates(&known, &scan)[0];
+    assert_eq!(first.channel, Some(3));
+}
+
+#[test]
+fn missed_networks_come_last() {
+    let known = [
+        network("hidden", 5, None),
+        network("guest", 0, None),
+        network("gone", 7, None),
+    ];
+    let scan = [ap("guest", 1, -40)];
+    assert_eq!(
+        order(&known, &scan),
+        [
+            ("guest".into(), Some(1)),
+            ("gone".into(), None),
+            ("hidden".into(), None)
+        ]
+    );
+    assert_eq!(candidates(&known, &[])[0].channel, None);
+}
+
+#[test]
+fn pinned_access_point() {
+    let pinned = [2, 0, 0, 0, 0, 2];
+    let known = [network("chaosdorf", 0, Some(pinned))];
+    // a stronger access point with the same name is not taken
+    let scan = [ap("chaosdorf", 1, -30), ap("chaosdorf", 2, -70)];
+    assert_eq!(order(&known, &scan), [("chaosdorf".into(), Some(2))]);
+    // nor when the pinned one is missing
+    assert_eq!(order(&known, &scan[..1]), [("chaosdorf".into(), Some(2))]);
+    assert_eq!(candidates(&known, &scan[..1])[0].channel, None);
+}
+
+#[test]
+fn bssids() {
+    assert_eq!(
+        parse_bssid("24:0A:c4:12:ab:0f"),
+        Some([0x24, 0x0a, 0xc4, 0x12, 0xab, 0x0f])
+    );
+    assert_eq!(
+        parse_bssid(" 24-0a-c4-12-ab-0f "),
+        Some([0x24, 0x0a, 0xc4, 0x12, 0xab, 0x0f])
+    );
+    for invalid in [
+        "",
+        "24:0a:c4:12:ab",
+        "24:0a:c4:12:ab:0f:00",
+        "24:0a:c4:12:ab:g0",
+        "240a:c4:12:ab:0f:",
+    ] {
+        assert_eq!(parse_bssid(invalid), None, "{}", invalid);
+    }
+}
@@ -1,16 +1,119 @@
 fn main() {
     dotenvy::dotenv().ok();
 
-    let ssid = std::env::var("SSID").expect("SSID not set in .env or elsewhere");
-    let password = std::env::var("PASSWORD").expect("PASSWORD not set in .env or elsewhere");
-    let mqtt_broker =
-        std::env::var("MQTT_BROKER").expect("MQTT_BROKER not set in .env or elsewhere");
     let defmt_log = std::env::var("DEFMT_LOG").unwrap_or_else(|_| "info".to_string());
+    let poll_interval = std::env::var("POLL_INTERVAL_SECS").unwrap_or_else(|_| "10".to_string());
+
+    let tls = std::env::var_os("CARGO_FEATURE_TLS").is_some();
+    let mqtt_port = std::env::var("MQTT_PORT")
+        .unwrap_or_else(|_| if tls { "8883" } else { "1883" }.to_string());
+    mqtt_port
+        .parse::<u16>()
+        .expect("MQTT_PORT is not a port number");
 
-    println!("cargo:rustc-env=SSID={}", ssid);
-    println!("cargo:rustc-env=PASSWORD={}", password);
-    println!("cargo:rustc-env=MQTT_BROKER={}", mqtt_broker);
     println!("cargo:rustc-env=DEFMT_LOG={}", defmt_log);
+    println!("cargo:rustc-env=POLL_INTERVAL_SECS={}", poll_interval);
+    println!("cargo:rustc-env=MQTT_PORT={}", mqtt_port);
+
+    if let Ok(device_id) = std::env::var("DEVICE_ID") {
+        // same rules as projector_controller::device::Device::new
+        let valid = (1..=32).contains(&device_id.len())
+            && device_id
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+        assert!(
+            valid,
+            "DEVICE_ID must be 1 to 32 letters, digits, '-' or '_'"
+        );
+    }
+
+    for suffix in ["", "_2", "_3", "_4"] {
+        if let Ok(bssid) = std::env::var(format!("BSSID{}", suffix)) {
+            // same rules as projector_controller::wifi::parse_bssid
+            let octets: Vec<&str> = bssid.trim().split([':', '-']).collect();
+            let valid = octets.len() == 6
+                && octets
+                    .iter()
+                    .all(|o| o.len() == 2 && o.bytes().all(|b| b.is_ascii_hexdigit()));
+            assert!(valid, "BSSID{} must look like aa:bb:cc:dd:ee:ff", suffix);
+        }
+    }
+
+    // same rules as projector_controller::config::StaticIpv4::parse
+    if let Ok(address) = std::env::var("STATIC_IP") {
+        let valid = address.trim().split_once('/').is_some_and(|(ip, len)| {
+            ip.parse::<std::net::Ipv4Addr>().is_ok() && len.parse::<u8>().is_ok_and(|l| l <= 32)
+        });
+        assert!(valid, "STATIC_IP must look like 10.20.0.42/24");
+    }
+    if let Ok(gateway) = std::env::var("GATEWAY") {
+        let valid =
+            gateway.trim().is_empty() || gateway.trim().parse::<std::net::Ipv4Addr>().is_ok();
+        assert!(valid, "GATEWAY must be an IPv4 address");
+    }
+    if let Ok(servers) = std::env::var("DNS_SERVERS") {
+        let servers: Vec<&str> = servers
+            .split([',', ' '])
+            .filter(|s| !s.is_empty())
+            .collect();
+        let valid = servers.len() <= 3
+            && servers
+                .iter()
+                .all(|s| s.parse::<std::net::Ipv4Addr>().is_ok());
+        assert!(valid, "DNS_SERVERS must be up to three IPv4 addresses");
+    }
+
+    if std::env::var_os("SSID").is_none() {
+        println!(
+            "cargo:warning=SSID not set, the board starts in setup mode unless flash has settings"
+        );
+    }
+    if std::env::var_os("MQTT_BROKER").is_none() {
+        println!(
+            "cargo:warning=MQTT_BROKER not set, the board looks for one by mDNS unless flash has settings"
+        );
+    }
+
+    // first-boot defaults for the settings in flash (see src/config.rs) and
+    // other optional values, read with option_env!
+    for name in [
+        "SSID",
+        "PASSWORD",
+        "BSSID",
+        "SSID_2",
+        "PASSWORD_2",
+        "BSSID_2",
+        "SSID_3",
+        "PASSWORD_3",
+        "BSSID_3",
+        "SSID_4",
+        "PASSWORD_4",
+        "BSSID_4",
+        "MQTT_BROKER",
+        "STATIC_IP",
+        "GATEWAY",
+        "DNS_SERVERS",
+        "IPV6",
+        "DEVICE_ID",
+        "MQTT_CLIENT_ID",
+        "MQTT_USERNAME",
+        "MQTT_PASSWORD",
+        "MQTT_TLS_SERVER_NAME",
+        "RAW_ALLOW",
+        "RAW_DENY",
+    ] {
+        if let Ok(value) = std::env::var(name) {
+            println!("cargo:rustc-env={}={}", name, value);
+        }
+    }
+
+    if tls {
+        // DER, e.g. `openssl x509 -in ca.pem -outform der -out ca.der`
+        let ca_cert = std::env::var("MQTT_CA_CERT").expect("MQTT_CA_CERT not set, needed for tls");
+        let ca_cert = std::fs::canonicalize(&ca_cert)
+            .unwrap_or_else(|e| panic!("MQTT_CA_CERT {}: {}", ca_cert, e));
+        println!("cargo:rustc-env=MQTT_CA_CERT={}", ca_cert.display());
+    }
 
     linker_be_nice();
     // make sure linkall.x is the last linker script (otherwise might cause problems with flip-link)
@@ -0,0 +1,201 @@
+//! Settings in the `config` flash partition (see `partitions.csv`), the
+//! record format is in `projector_controller::config`.
+//!
+//! On first boot, or when the record is unreadable, the values from the
+//! build environment (see `.env.example`) are used and written to flash.
+//! From then on the stored settings win over a rebuild; erase the partition
+//! (`espflash erase-parts --partition-table partitions.csv config`) to go
+//! back to the build-time values.
+
+use core::ops::Range;
+
+use alloc::string::String;
+use defmt::{error, info, warn};
+use embassy_embedded_hal::adapter::BlockingAsync;
+use esp_bootloader_esp_idf::partitions::{self, PARTITION_TABLE_MAX_LEN};
+use esp_storage::FlashStorage;
+use projector_controller::config::{
+    Config, IpConfig, MqttConfig, StaticIpv4, WifiNetwork, MAX_RECORD_SIZE,
+};
+use projector_controller::wifi;
+use sequential_storage::cache::NoCache;
+use sequential_storage::map;
+
+/// Label of the partition in `partitions.csv`.
+const PARTITION: &str = "config";
+
+/// Map key of the one record in the partition.
+const KEY: u8 = 0;
+
+type Flash = BlockingAsync<FlashStorage>;
+
+/// The config partition, found through the partition table.
+pub struct ConfigStore {
+    flash: Flash,
+    range: Range<u32>,
+    /// Room for the record and its key, sequential-storage works in it.
+    buffer: [u8; MAX_RECORD_SIZE + 16],
+}
+
+impl ConfigStore {
+    /// `None` if the flash has no config partition, e.g. when flashed
+    /// without `--partition-table`.
+    pub fn new() -> Option<Self> {
+        let mut flash = FlashStorage::new();
+        let mut table = [0; PARTITION_TABLE_MAX_LEN];
+        let partitions = partitions::read_partition_table(&mut flash, &mut table).ok()?;
+        let partition = partitions
+            .iter()
+            .find(|partition| partition.label_as_str() == PARTITION)?;
+        let start = partition.offset();
+
+        Some(Self {
+            flash: BlockingAsync::new(flash),
+            range: start..start + partition.len(),
+            buffer: [0; MAX_RECORD_SIZE + 16],
+        })
+    }
+
+    /// The stored settings, `None` if there are none or they are unusable.
+    /// A record written by older firmware is stored again in the current
+    /// format.
+    pub async fn load(&mut self) -> Option<Config> {
+        let record = match map::fetch_item::<u8, &[u8], _>(
+            &mut self.flash,
+            self.range.clone(),
+            &mut NoCache::new(),
+            &mut self.buffer,
+            &KEY,
+        )
+        .await
+        {
+            Ok(Some(record)) => record,
+            Ok(None) => return None,
+            Err(e) => {
+                error!("Reading config failed: {}", defmt::Debug2Format(&e));
+                return None;
+            }
+        };
+
+        let config = match Config::decode(record) {
+            Ok(config) => config,
+            Err(e) => {
+                warn!("Stored config unusable: {}", e);
+                return None;
+            }
+        };
+        // a migrated record encodes differently
+        if config.encode().is_ok_and(|current| current != record) {
+            info!("Upgrading stored config");
+            self.save(&config).await;
+        }
+        Some(config)
+    }
+
+    pub async fn save(&mut self, config: &Config) {
+        let record = match config.encode() {
+            Ok(record) => record,
+            Err(e) => {
+                error!("Config not saved: {}", e);
+                return;
+            }
+        };
+        let result = map::store_item::<u8, &[u8], _>(
+            &mut self.flash,
+            self.range.clone(),
+            &mut NoCache::new(),
+            &mut self.buffer,
+            &KEY,
+            &record.as_slice(),
+        )
+        .await;
+        match result {
+            Ok(()) => info!("Config saved"),
+            Err(e) => error!("Saving config failed: {}", defmt::Debug2Format(&e)),
+        }
+    }
+}
+
+/// `SSID`, `PASSWORD` and `BSSID` of the networks from the build
+/// environment, most preferred first.
+const NETWORKS: [(Option<&str>, Option<&str>, Option<&str>); 4] = [
+    (
+        option_env!("SSID"),
+        option_env!("PASSWORD"),
+        option_env!("BSSID"),
+    ),
+    (
+        option_env!("SSID_2"),
+        option_env!("PASSWORD_2"),
+        option_env!("BSSID_2"),
+    ),
+    (
+        option_env!("SSID_3"),
+        option_env!("PASSWORD_3"),
+        option_env!("BSSID_3"),
+    ),
+    (
+        option_env!("SSID_4"),
+        option_env!("PASSWORD_4"),
+        option_env!("BSSID_4"),
+    ),
+];
+
+/// Settings from the build environment, used until flash has some.
+pub fn defaults() -> Config {
+    let owned = |value: Option<&str>| value.map(String::from);
+    let networks = NETWORKS
+        .iter()
+        .zip((0..NETWORKS.len() as u8).rev())
+        .filter_map(|(&(ssid, password, bssid), priority)| {
+            Some(WifiNetwork {
+                ssid: ssid?.into(),
+                password: password.unwrap_or_default().into(),
+                priority,
+                // checked by build.rs
+                bssid: bssid.and_then(wifi::parse_bssid),
+            })
+        })
+        .collect();
+    Config {
+        networks,
+        mqtt: MqttConfig {
+            host: option_env!("MQTT_BROKER").unwrap_or_default().into(),
+            // checked by build.rs
+            port: env!("MQTT_PORT").parse().unwrap(),
+            username: owned(option_env!("MQTT_USERNAME")),
+            password: owned(option_env!("MQTT_PASSWORD")),
+            client_id: owned(option_env!("MQTT_CLIENT_ID")),
+            tls_server_name: owned(option_env!("MQTT_TLS_SERVER_NAME")),
+        },
+        ip: IpConfig {
+            // checked by build.rs
+            ipv4: option_env!("STATIC_IP").and_then(|address| {
+                StaticIpv4::parse(
+                    address,
+                    option_env!("GATEWAY").unwrap_or_default(),
+                    option_env!("DNS_SERVERS").unwrap_or_default(),
+                )
+            }),
+            ipv6: option_env!("IPV6").is_some_and(|ipv6| ipv6 == "true" || ipv6 == "1"),
+        },
+        device_id: owned(option_env!("DEVICE_ID")),
+        poll_interval_secs: env!("POLL_INTERVAL_SECS").parse().unwrap_or(10),
+    }
+}
+
+/// The stored settings, or the build-time ones, which are then stored.
+pub async fn load() -> Config {
+    let Some(mut store) = ConfigStore::new() else {
+        warn!("No config partition, using build-time settings");
+        return defaults();
+    };
+
+    if let Some(config) = store.load().await {
+        return config;
+    }
+    info!("No stored config, using build-time settings");
+    let config = defaults();
+    store.save(&config).await;
+    config
+}
@@ -1,4 +1,3 @@
-use crate::projector::Projector;
 use embassy_sync::{
     blocking_mutex::{self, raw::CriticalSectionRawMutex},
     mutex::Mutex,
@@ -13,9 +12,6 @@ type LedType = Mutex<CriticalSectionRawMutex, Option<Output<'static>>>;
 pub static LED1: LedType = Mutex::new(None);
 pub static LED2: LedType = Mutex::new(None);
 
-pub static PROJECTOR: Mutex<CriticalSectionRawMutex, Option<Projector<'static, Blocking>>> =
-    Mutex::new(None);
-
 pub async fn blink_led2_ms(ms: u64) {
     let mut led2 = LED2.lock().await;
     if let Some(led) = led2.as_mut() {
@@ -10,7 +10,7 @@
 use alloc::string::ToString;
 use defmt::{debug, error, info, warn};
 use embassy_executor::Spawner;
-use embassy_net::StackResources;
+use embassy_net::{Ipv4Cidr, StackResources, StaticConfigV4};
 use embassy_time::{Duration, Timer};
 use esp_hal::config::WatchdogConfig;
 use esp_hal::gpio::{Output, OutputConfig};
@@ -19,14 +19,22 @@ use esp_hal::timer::systimer::SystemTimer;
 use esp_hal::timer::timg::TimerGroup;
 use esp_println as _;
 use esp_wifi::EspWifiController;
+use projector_controller::config::Config;
+use projector_controller::device::Device;
 
-use crate::projector::Projector;
+use crate::projector::{ProjectorLink, RawFilter};
 
+mod config;
 mod io;
 mod log;
+mod mdns;
 mod mqtt;
 mod net;
+mod poller;
+mod portal;
 mod projector;
+#[cfg(feature = "tls")]
+mod tls;
 
 #[panic_handler]
 fn panic(_: &core::panic::PanicInfo) -> ! {
@@ -73,6 +81,8 @@ async fn main(spawner: Spawner) {
 
     esp_alloc::heap_allocator!(size: 64 * 1024);
 
+    let settings = &*mk_static!(Config, config::load().await);
+
     ///////////////////////////////////////////////////////////////////////////
     // PT-AH1000E
     ///////////////////////////////////////////////////////////////////////////
@@ -83,13 +93,19 @@ async fn main(spawner: Spawner) {
     let uart1 = esp_hal::uart::Uart::new(peripherals.UART1, uart_conf)
         .unwrap()
         .with_rx(peripherals.GPIO18)
-        .with_tx(peripherals.GPIO17);
-
-    let projector = Projector::new(uart1);
+        .with_tx(peripherals.GPIO17)
+        .into_async();
 
-    {
-        *(io::PROJECTOR.lock().await) = Some(projector);
+    let mut raw_filter = RawFilter::default();
+    if let Some(codes) = option_env!("RAW_ALLOW") {
+        raw_filter = raw_filter.allow(codes);
     }
+    if let Some(codes) = option_env!("RAW_DENY") {
+        raw_filter = raw_filter.deny(codes);
+    }
+    let projector = ProjectorLink::new(uart1).with_raw_filter(raw_filter);
+
+    spawner.spawn(projector::projector_task(projector)).ok();
 
     // WIFI
     let timer0 = SystemTimer::new(peripherals.SYSTIMER);
@@ -109,20 +125,52 @@ async fn main(spawner: Spawner) {
 
     let wifi_interface = interfaces.sta;
 
-    let config = embassy_net::Config::dhcpv4(Default::default());
+    let mac = wifi_interface.mac_address();
+    let device = &*mk_static!(
+        Device,
+        match settings.device_id.as_deref().map(Device::new) {
+            Some(Ok(device)) => device.with_mac(mac),
+            Some(Err(e)) => {
+                warn!("Stored device id unusable: {}", e);
+                Device::from_mac(mac)
+            }
+            None => Device::from_mac(mac),
+        }
+    );
+    info!("Device id: {}", device.id());
+
+    let config = net::station_config(settings, device);
 
     let seed = (rng.random() as u64) << 32 | rng.random() as u64;
 
     // Init network stack
-    let (stack, runner) = embassy_net::new(
-        wifi_interface,
-        config,
-        mk_static!(StackResources<3>, StackResources::<3>::new()),
+    let (stack, runner) = net::station_stack(wifi_interface, config, seed);
+
+    // the setup access point, only up when the stored network fails
+    let ap_config = embassy_net::Config::ipv4_static(StaticConfigV4 {
+        address: Ipv4Cidr::new(portal::ADDRESS, 24),
+        gateway: None,
+        dns_servers: Default::default(),
+    });
+    let (ap_stack, ap_runner) = embassy_net::new(
+        interfaces.ap,
+        ap_config,
+        mk_static!(
+            StackResources<{ portal::SOCKETS }>,
+            StackResources::<{ portal::SOCKETS }>::new()
+        ),
         seed,
     );
 
-    spawner.spawn(net::connection(controller)).ok();
+    spawner
+        .spawn(net::connection(controller, device, settings))
+        .ok();
     spawner.spawn(net::net_task(runner)).ok();
+    spawner.spawn(net::net_task(ap_runner)).ok();
+    spawner
+        .spawn(portal::portal_task(ap_stack, device, settings))
+        .ok();
+    spawner.spawn(mdns::mdns_task(stack, device)).ok();
 
     loop {
         if stack.is_link_up() {
@@ -164,7 +212,12 @@ async fn main(spawner: Spawner) {
         }
     }
 
-    spawner.spawn(mqtt::mqtt_task(stack)).ok();
+    spawner
+        .spawn(mqtt::mqtt_task(stack, device, settings, rng))
+        .ok();
+    spawner
+        .spawn(poller::poller_task(settings.poll_interval()))
+        .ok();
 
     let _ = spawner;
 
@@ -0,0 +1,178 @@
+//! mDNS on the station network, records and parsing are in
+//! `projector_controller::mdns`: the board answers as `<device id>.local`,
+//! and [`Lookup`] finds `.local` brokers, or any broker advertising
+//! `_mqtt._tcp` when none is configured.
+//!
+//! The setup access point runs its own responder, see `portal`. Nothing
+//! listens for HTTP or PJLink on the station network yet, so the station
+//! advertises no services, only the host name.
+
+use core::net::Ipv4Addr;
+
+use alloc::vec::Vec;
+use defmt::{info, warn};
+use embassy_futures::select::{select, Either};
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{IpEndpoint, Stack};
+use embassy_time::{with_timeout, Duration, Timer};
+use projector_controller::device::Device;
+use projector_controller::mdns::{self, Found, Responder, GROUP, PORT};
+use static_cell::StaticCell;
+
+use crate::portal::UdpBuffers;
+
+/// Sockets on the station interface: the responder and [`Lookup`].
+pub const SOCKETS: usize = 2;
+
+/// How long [`Lookup`] waits for an answer.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+const BUFFER_SIZE: usize = 1024;
+
+/// Answers for the board on the station network, re-announcing it whenever
+/// it gets an address.
+#[embassy_executor::task]
+pub async fn mdns_task(stack: Stack<'static>, device: &'static Device) {
+    static BUFFERS: StaticCell<UdpBuffers> = StaticCell::new();
+
+    let (socket, packet) = BUFFERS.init(UdpBuffers::new()).socket(stack, PORT);
+    // `_http._tcp` and `_pjlink._tcp` follow once something serves them
+    // here
+    let responder = Responder::new(device.id(), &[]);
+    loop {
+        stack.wait_config_up().await;
+        let Some(config) = stack.config_v4() else {
+            stack.wait_config_down().await;
+            continue;
+        };
+        if stack.join_multicast_group(GROUP).is_err() {
+            warn!("Cannot join the mDNS group");
+        }
+        info!("Answering mDNS as {}.local", device.id());
+
+        let address = config.address.address();
+        match select(
+            respond(&socket, &mut *packet, &responder, address),
+            stack.wait_config_down(),
+        )
+        .await
+        {
+            Either::First(never) => match never {},
+            Either::Second(()) => {}
+        }
+    }
+}
+
+/// Announce `responder`'s records for `address` and answer queries for them
+/// on `socket`, bound to [`PORT`] and in [`GROUP`].
+pub async fn respond(
+    socket: &UdpSocket<'_>,
+    packet: &mut [u8],
+    responder: &Responder<'_>,
+    address: Ipv4Addr,
+) -> ! {
+    let group = IpEndpoint::new(GROUP.into(), PORT);
+    // twice, a second apart, as RFC 6762 asks
+    for _ in 0..2 {
+        if socket
+            .send_to(&responder.announcement(address), group)
+            .await
+            .is_err()
+        {
+            warn!("Sending mDNS announcement failed");
+        }
+        Timer::after(Duration::from_secs(1)).await;
+    }
+
+    loop {
+        let Ok((len, meta)) = socket.recv_from(packet).await else {
+            continue;
+        };
+        let legacy = meta.endpoint.port != PORT;
+        if let Some(reply) = responder.reply(&packet[..len], address, legacy) {
+            let to = if legacy { meta.endpoint } else { group };
+            if socket.send_to(&reply, to).await.is_err() {
+                warn!("Sending mDNS reply failed");
+            }
+        }
+    }
+}
+
+/// One-shot mDNS queries, for names the stack's resolver cannot look up.
+pub struct Lookup {
+    rx_meta: [PacketMetadata; 4],
+    rx: [u8; BUFFER_SIZE],
+    tx_meta: [PacketMetadata; 1],
+    tx: [u8; BUFFER_SIZE],
+    packet: [u8; BUFFER_SIZE],
+}
+
+impl Lookup {
+    pub const fn new() -> Self {
+        Self {
+            rx_meta: [PacketMetadata::EMPTY; 4],
+            rx: [0; BUFFER_SIZE],
+            tx_meta: [PacketMetadata::EMPTY; 1],
+            tx: [0; BUFFER_SIZE],
+            packet: [0; BUFFER_SIZE],
+        }
+    }
+
+    /// The first instance of `kind` to answer, with its addresses.
+    pub async fn discover(&mut self, stack: Stack<'static>, kind: &str) -> Option<Found> {
+        let query = mdns::browse_query(kind, 1);
+        let mut found = self
+            .ask(stack, &query, |response| mdns::parse_browse(response, kind))
+            .await?;
+        if found.addresses.is_empty() {
+            found.addresses = self.resolve(stack, &found.host).await;
+        }
+        Some(found)
+    }
+
+    /// The addresses of `host`, a `.local` name, empty if nobody answered.
+    pub async fn resolve(&mut self, stack: Stack<'static>, host: &str) -> Vec<Ipv4Addr> {
+        let query = mdns::address_query(host, 2);
+        self.ask(stack, &query, |response| {
+            Some(mdns::addresses(response, host)).filter(|addresses| !addresses.is_empty())
+        })
+        .await
+        .unwrap_or_default()
+    }
+
+    /// Send `query` from a port of its own, so responders answer here
+    /// directly, and `parse` what comes back until something fits.
+    async fn ask<T>(
+        &mut self,
+        stack: Stack<'static>,
+        query: &[u8],
+        parse: impl Fn(&[u8]) -> Option<T>,
+    ) -> Option<T> {
+        let Self {
+            rx_meta,
+            rx,
+            tx_meta,
+            tx,
+            packet,
+        } = self;
+        let mut socket = UdpSocket::new(stack, rx_meta, rx, tx_meta, tx);
+        // any free port
+        socket.bind(0).ok()?;
+        socket
+            .send_to(query, IpEndpoint::new(GROUP.into(), PORT))
+            .await
+            .ok()?;
+
+        let answer = async {
+            loop {
+                let Ok((len, _)) = socket.recv_from(packet).await else {
+                    continue;
+                };
+                if let Some(answer) = parse(&packet[..len]) {
+                    return answer;
+                }
+            }
+        };
+        with_timeout(QUERY_TIMEOUT, answer).await.ok()
+    }
+}
@@ -1,324 +1,507 @@
-use defmt::{debug, error, info, warn};
-use embassy_futures::select::{select, Either};
-use embassy_net::{tcp::TcpSocket, Stack};
+//! Broker connection over embassy-net, with the packets from
+//! `projector_controller::mqtt_packet`; the application side is in
+//! `projector_controller::mqtt`.
+//!
+//! Broker, port, client id and credentials come from the stored settings
+//! (see `config`), with the `tls` feature the connection is wrapped in TLS
+//! against a pinned CA. Without a broker in the settings, the first one
+//! advertised by mDNS is used.
+
+use core::net::Ipv4Addr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use alloc::collections::VecDeque;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use defmt::{info, warn};
+use embassy_net::dns::{DnsError, DnsQueryType};
+use embassy_net::tcp::{ConnectError, TcpSocket};
+use embassy_net::{IpAddress, Stack};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
-use embassy_sync::mutex::Mutex;
-use embassy_time::{Duration, Timer};
-use esp_hal::xtensa_lx::debug_break;
-use esp_println::println;
-use heapless::Vec;
-use rust_mqtt::{
-    client::{client::MqttClient, client_config::ClientConfig},
-    packet::v5::publish_packet::QualityOfService,
-    utils::rng_generator::CountingRng,
+use embassy_sync::signal::Signal;
+use embassy_time::{with_timeout, Duration};
+use embedded_io_async::{ErrorKind, Read, Write};
+use esp_hal::rng::Rng;
+use projector_controller::config::Config;
+use projector_controller::device::Device;
+use projector_controller::mdns;
+use projector_controller::mqtt::{
+    self, Connector, Indicator, Message, QoS, Session, KEEP_ALIVE, OFFLINE,
 };
-use serde::Serialize;
-use serde_json::json;
-use serde_json_core::to_slice;
+use projector_controller::mqtt_packet::{self, Malformed, Packet, Publish, FAILURE};
+use static_cell::StaticCell;
 
 use crate::io::{self, LED1};
+use crate::mdns::Lookup;
+#[cfg(feature = "tls")]
+use crate::tls::{self, Tls};
+
+/// Socket buffers, and the largest packet the broker may send us.
+const BUFFER_SIZE: usize = 4096;
+
+/// How long [`reboot`] gives the broker connection to say goodbye.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Set while `mqtt_task` runs, nobody to wait for otherwise.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Raised by `mqtt_task` once it is done after [`mqtt::SHUTDOWN`].
+static STOPPED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// What the session talks over.
+#[cfg(not(feature = "tls"))]
+type Link<'a> = TcpSocket<'a>;
+#[cfg(feature = "tls")]
+type Link<'a> = tls::Link<'a>;
+
+#[derive(Debug)]
+pub enum Error {
+    Dns(DnsError),
+    /// The broker name resolved to nothing.
+    NoAddress,
+    /// None configured, and none advertised.
+    NoBroker,
+    Connect(ConnectError),
+    #[cfg(feature = "tls")]
+    Tls(embedded_tls::TlsError),
+    /// Reading or writing the connection failed.
+    Io(ErrorKind),
+    /// The broker closed the connection.
+    Closed,
+    /// Something from the broker that is not MQTT as we know it.
+    Malformed,
+    /// A packet bigger than we told the broker we take.
+    PacketTooLarge,
+    /// CONNACK with this reason code.
+    Refused(u8),
+    /// PUBACK or SUBACK with this reason code.
+    Rejected(u8),
+    /// DISCONNECT from the broker, with this reason code.
+    Disconnected(u8),
+    /// No PINGRESP by the next keep-alive.
+    PingTimeout,
+    /// The broker is an address and no TLS server name is configured to
+    /// check its certificate against.
+    #[cfg(feature = "tls")]
+    NoServerName,
+}
 
-#[derive(Serialize)]
-struct DiscoveryPacket<'a> {
-    unique_id: &'a str,
-    name: &'a str,
-    state_topic: &'a str,
-    command_topic: &'a str,
-    availability_topic: &'a str,
-    payload_on: &'a str,
-    payload_off: &'a str,
-    state_on: &'a str,
-    state_off: &'a str,
-    optimistic: bool,
-    qos: u8,
-    retain: bool,
+/// Socket and MQTT buffers, borrowed by the session while it is open.
+/// Dropping the session drops the socket, so every reconnect starts from a
+/// fresh TCP connection.
+pub struct TcpConnector {
+    stack: Stack<'static>,
+    device: &'static Device,
+    settings: &'static Config,
+    socket_rx: [u8; BUFFER_SIZE],
+    socket_tx: [u8; BUFFER_SIZE],
+    mqtt_rx: [u8; BUFFER_SIZE],
+    lookup: Lookup,
+    #[cfg(feature = "tls")]
+    tls: Tls,
 }
 
-/// send Home Assistant MQTT discovery packets and subscribe to command topics
-async fn homassistant_initialization(
-    client: &mut MqttClient<'static, TcpSocket<'_>, 5, CountingRng>,
-) {
-    // what. in. the. actual. fuck.
-    // why does this need *serde_json_core::heapless::Vec* instead of heapless::Vec??????
-    let mut topics = serde_json_core::heapless::Vec::<&str, 16>::new();
-
-    // Power switch
-    let power = json!({
-        "name": "Projector Power",
-        "unique_id": "projector_power",
-        "command_topic": "projector-controller/cmd/power",
-        "state_topic": "projector-controller/stat/power",
-        "availability_topic": "projector-controller/availability",
-        "payload_on": "ON",
-        "payload_off": "OFF",
-        "state_on": "ON",
-        "state_off": "OFF",
-        "optimistic": false
-    });
-    publish_config(
-        client,
-        "homeassistant/switch/projector_power/config",
-        &power,
-    )
-    .await;
-
-    topics.push("projector-controller/cmd/power").unwrap();
-
-    debug!("Published power config");
-
-    // Projector control buttons (all high-level, no RS232 codes here)
-    let buttons: &[(&str, &str)] = &[
-        ("menu", "Menu"),
-        ("enter", "Enter"),
-        ("up", "Up"),
-        ("down", "Down"),
-        ("left", "Left"),
-        ("right", "Right"),
-        ("back", "Back"),
-    ];
-
-    for (id, name) in buttons {
-        let data = json!({
-            "name": alloc::format!("Projector {}", name), // compile-time friendly
-            "unique_id": alloc::format!("projector_{}", id),
-            "command_topic": alloc::format!("projector-controller/cmd/{}", id),
-            "availability_topic": "projector-controller/availability",
+impl TcpConnector {
+    /// `rng` is only needed for TLS.
+    pub fn new(
+        stack: Stack<'static>,
+        device: &'static Device,
+        settings: &'static Config,
+        rng: Rng,
+    ) -> Self {
+        #[cfg(not(feature = "tls"))]
+        let _ = rng;
+
+        Self {
+            stack,
+            device,
+            settings,
+            socket_rx: [0; BUFFER_SIZE],
+            socket_tx: [0; BUFFER_SIZE],
+            mqtt_rx: [0; BUFFER_SIZE],
+            lookup: Lookup::new(),
+            #[cfg(feature = "tls")]
+            tls: Tls::new(rng),
+        }
+    }
+}
+
+impl TcpConnector {
+    /// The broker's name, addresses and port: from DNS, from mDNS for
+    /// `.local` names, or whatever advertises `_mqtt._tcp` if no broker is
+    /// set.
+    async fn resolve(&mut self) -> Result<(String, Vec<Ipv4Addr>, u16), Error> {
+        let broker = &self.settings.mqtt;
+        if broker.host.is_empty() {
+            let found = self
+                .lookup
+                .discover(self.stack, mdns::MQTT)
+                .await
+                .ok_or(Error::NoBroker)?;
+            info!("Found broker {} by mDNS", found.host.as_str());
+            return Ok((found.host, found.addresses, found.port));
+        }
+        if broker.host.ends_with(".local") {
+            let addresses = self.lookup.resolve(self.stack, &broker.host).await;
+            return Ok((broker.host.clone(), addresses, broker.port));
+        }
+        let addresses = self
+            .stack
+            .dns_query(&broker.host, DnsQueryType::A)
+            .await
+            .map_err(Error::Dns)?
+            .iter()
+            .filter_map(|address| match address {
+                IpAddress::Ipv4(address) => Some(*address),
+                _ => None,
+            })
+            .collect();
+        Ok((broker.host.clone(), addresses, broker.port))
+    }
+}
+
+impl Connector for TcpConnector {
+    type Error = Error;
+    type Session<'a> = MqttSession<'a>;
+
+    async fn connect(&mut self) -> Result<MqttSession<'_>, Error> {
+        // looked up on every attempt, the broker may have moved
+        let (host, addresses, port) = self.resolve().await?;
+        #[cfg(not(feature = "tls"))]
+        let _ = host;
+        #[cfg(feature = "tls")]
+        let server_name = self
+            .settings
+            .mqtt
+            .server_name(&host)
+            .ok_or(Error::NoServerName)?;
+
+        let mut socket = TcpSocket::new(self.stack, &mut self.socket_rx, &mut self.socket_tx);
+        socket.set_timeout(Some(Duration::from_secs(10)));
+        let mut connected = Err(Error::NoAddress);
+        // the next one is tried if a broker has several addresses
+        for address in addresses {
+            connected = socket
+                .connect((address, port))
+                .await
+                .map_err(Error::Connect);
+            if connected.is_ok() {
+                break;
+            }
+            warn!(
+                "Broker unreachable at {}:{}",
+                defmt::Display2Format(&address),
+                port
+            );
+        }
+        connected?;
+        info!("Connected to broker!");
+
+        #[cfg(not(feature = "tls"))]
+        let link = socket;
+        #[cfg(feature = "tls")]
+        let link = self
+            .tls
+            .open(socket, server_name)
+            .await
+            .map_err(Error::Tls)?;
+
+        let broker = &self.settings.mqtt;
+        let availability = self.device.availability_topic();
+        let connect = mqtt_packet::connect(&mqtt_packet::Connect {
+            client_id: broker.client_id.as_deref().unwrap_or(self.device.id()),
+            keep_alive_secs: KEEP_ALIVE.as_secs() as u16,
+            username: broker.username.as_deref(),
+            password: broker.password.as_deref(),
+            will: Some((&availability, OFFLINE)),
+            max_packet_size: BUFFER_SIZE as u32,
         });
 
-        let topic = match *id {
-            "menu" => "homeassistant/button/projector_menu/config",
-            "enter" => "homeassistant/button/projector_enter/config",
-            "up" => "homeassistant/button/projector_up/config",
-            "down" => "homeassistant/button/projector_down/config",
-            "left" => "homeassistant/button/projector_left/config",
-            "right" => "homeassistant/button/projector_right/config",
-            "back" => "homeassistant/button/projector_back/config",
-            _ => continue,
+        let mut session = MqttSession {
+            link,
+            rx: &mut self.mqtt_rx,
+            filled: 0,
+            taken: 0,
+            next_id: 0,
+            pending: VecDeque::new(),
+            current: None,
+            pinged: false,
         };
+        session.send(&connect).await?;
+        loop {
+            if let Some(Ack::Connect { reason }) = session.poll().await? {
+                if reason != 0 {
+                    return Err(Error::Refused(reason));
+                }
+                return Ok(session);
+            }
+        }
+    }
+}
 
-        debug!("Publishing {} config (data: {})", id, data.as_str());
-
-        publish_config(client, topic, &data).await;
+/// A publish taken off the receive buffer.
+struct Received {
+    topic: String,
+    payload: Vec<u8>,
+    response_topic: Option<String>,
+    correlation_data: Option<Vec<u8>>,
+}
 
-        debug!("Published {} config", id);
+impl From<&Publish<'_>> for Received {
+    fn from(publish: &Publish<'_>) -> Self {
+        Self {
+            topic: publish.topic.to_string(),
+            payload: publish.payload.to_vec(),
+            response_topic: publish.response_topic.map(str::to_string),
+            correlation_data: publish.correlation_data.map(<[u8]>::to_vec),
+        }
     }
+}
 
-    topics.push("projector-controller/cmd/menu").unwrap();
-    topics.push("projector-controller/cmd/enter").unwrap();
-    topics.push("projector-controller/cmd/up").unwrap();
-    topics.push("projector-controller/cmd/down").unwrap();
-    topics.push("projector-controller/cmd/left").unwrap();
-    topics.push("projector-controller/cmd/right").unwrap();
-    topics.push("projector-controller/cmd/back").unwrap();
-    topics.push("projector-controller/cmd/raw").unwrap();
-
-    // Binary sensor for actual power state
-    let status = json!({
-        "name": "Projector Status",
-        "unique_id": "projector_status",
-        "state_topic": "projector-controller/stat/status",
-        "payload_on": "ON",
-        "payload_off": "OFF",
-        "availability_topic": "projector-controller/availability"
-    });
-    publish_config(
-        client,
-        "homeassistant/binary_sensor/projector_status/config",
-        &status,
-    )
-    .await;
-
-    debug!("Published status config");
-
-    // Device availability
-    client
-        .send_message(
-            "projector-controller/availability",
-            b"online",
-            QualityOfService::QoS0,
-            true,
-        )
-        .await
-        .unwrap();
-
-    debug!("Published availability online");
-
-    // // actual power state
-    // let mut projector = io::PROJECTOR.lock().await;
-    // let projector = projector.as_mut().unwrap();
-    // let power_state = if projector.is_on().unwrap_or(false) {
-    //     "ON"
-    // } else {
-    //     "OFF"
-    // };
-    //
-    // client
-    //     .send_message(
-    //         "projector-controller/stat/power",
-    //         power_state.as_bytes(),
-    //         QualityOfService::QoS0,
-    //         true,
-    //     )
-    //     .await
-    //     .unwrap();
-    //
-    // debug!("Published initial power state: {}", power_state);
-
-    // Subscribe to command topics
-    client.subscribe_to_topics(&topics).await.unwrap();
-
-    debug!("Subscribed to topics ({=[?]})", &topics);
+/// What a request of ours got back.
+enum Ack {
+    Connect { reason: u8 },
+    Publish { id: u16, reason: u8 },
+    Subscribe { id: u16, reason: u8 },
 }
 
-/// Serialize JSON into fixed buffer and publish (no alloc, no format!)
-async fn publish_config(
-    client: &mut MqttClient<'static, TcpSocket<'_>, 5, CountingRng>,
-    topic: &str,
-    data: &serde_json::Value,
-) {
-    let mut buf = [0u8; 512]; // adjust if JSON grows
-    let used = to_slice(data, &mut buf).unwrap();
-    client
-        .send_message(topic, &buf[..used], QualityOfService::QoS0, true)
-        .await
-        .unwrap();
+/// An MQTT v5 session over [`Link`].
+///
+/// Publishes can turn up at any time, also while a publish or subscribe
+/// waits for its acknowledgement; those are kept in `pending` for
+/// [`Session::receive`], so a command sent during a flush is not lost.
+pub struct MqttSession<'a> {
+    link: Link<'a>,
+    /// Bytes read from `link`, starting with the next packet.
+    rx: &'a mut [u8],
+    /// How much of `rx` holds data.
+    filled: usize,
+    /// Length of the packet at the start of `rx` already handled, dropped
+    /// on the next read.
+    taken: usize,
+    next_id: u16,
+    pending: VecDeque<Received>,
+    /// The message [`Session::receive`] returned last.
+    current: Option<Received>,
+    /// A PINGREQ is still waiting for its PINGRESP.
+    pinged: bool,
 }
 
-#[embassy_executor::task]
-pub async fn mqtt_task(stack: Stack<'static>) {
-    let mut rx_buffer = [0; 4096];
-    let mut tx_buffer = [0; 4096];
+impl MqttSession<'_> {
+    async fn send(&mut self, packet: &[u8]) -> Result<(), Error> {
+        self.link.write_all(packet).await.map_err(io)?;
+        self.link.flush().await.map_err(io)
+    }
 
-    let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+    /// Packet identifiers start at 1.
+    fn next_id(&mut self) -> u16 {
+        self.next_id = self.next_id.checked_add(1).unwrap_or(1);
+        self.next_id
+    }
 
-    socket.set_timeout(Some(embassy_time::Duration::from_secs(10)));
+    /// Read until a whole packet is at the start of `rx`, and return its
+    /// length.
+    async fn next_packet(&mut self) -> Result<usize, Error> {
+        self.rx.copy_within(self.taken..self.filled, 0);
+        self.filled -= self.taken;
+        self.taken = 0;
 
-    let broker_addr = stack
-        .dns_query(env!("MQTT_BROKER"), smoltcp::wire::DnsQueryType::A)
-        .await
-        .unwrap();
-    let broker_endpoint = (broker_addr[0], 1883);
+        loop {
+            let len = mqtt_packet::packet_len(&self.rx[..self.filled])
+                .map_err(|Malformed| Error::Malformed)?;
+            match len {
+                Some(len) if len > self.rx.len() => return Err(Error::PacketTooLarge),
+                Some(len) if len <= self.filled => {
+                    self.taken = len;
+                    return Ok(len);
+                }
+                _ => {}
+            }
+            let read = Read::read(&mut self.link, &mut self.rx[self.filled..])
+                .await
+                .map_err(io)?;
+            if read == 0 {
+                return Err(Error::Closed);
+            }
+            self.filled += read;
+        }
+    }
+
+    /// Handle the next packet from the broker. Publishes are acknowledged
+    /// and queued, and the answers to our requests returned.
+    async fn poll(&mut self) -> Result<Option<Ack>, Error> {
+        let len = self.next_packet().await?;
+        let mut puback = None;
+        let ack = match mqtt_packet::decode(&self.rx[..len]).ok_or(Error::Malformed)? {
+            Packet::Publish(publish) => {
+                self.pending.push_back(Received::from(&publish));
+                puback = publish.id;
+                None
+            }
+            Packet::ConnAck { reason } => Some(Ack::Connect { reason }),
+            Packet::PubAck { id, reason } => Some(Ack::Publish { id, reason }),
+            Packet::SubAck { id, reasons } => Some(Ack::Subscribe {
+                id,
+                // the worst of them
+                reason: reasons.iter().copied().max().unwrap_or(0),
+            }),
+            Packet::PingResp => {
+                self.pinged = false;
+                None
+            }
+            Packet::Disconnect { reason } => return Err(Error::Disconnected(reason)),
+        };
+        if let Some(id) = puback {
+            self.send(&mqtt_packet::puback(id)).await?;
+        }
+        Ok(ack)
+    }
+}
 
-    info!("Connecting to broker...");
+fn io(error: impl embedded_io_async::Error) -> Error {
+    Error::Io(error.kind())
+}
 
-    let r = socket.connect(broker_endpoint).await;
-    if let Err(e) = r {
-        println!("Failed to connect to broker: {:?}", e);
+fn acknowledged(reason: u8) -> Result<(), Error> {
+    if reason >= FAILURE {
+        return Err(Error::Rejected(reason));
+    }
+    Ok(())
+}
 
+impl Session for MqttSession<'_> {
+    type Error = Error;
+
+    async fn publish(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        qos: QoS,
+        retain: bool,
+    ) -> Result<(), Error> {
+        let id = self.next_id();
+        self.send(&mqtt_packet::publish(topic, payload, qos, retain, id, None))
+            .await?;
+        if qos == QoS::AtMostOnce {
+            return Ok(());
+        }
         loop {
-            {
-                let mut led_unlocked = LED1.lock().await;
-                if let Some(pin_ref) = led_unlocked.as_mut() {
-                    pin_ref.toggle();
+            if let Some(Ack::Publish { id: acked, reason }) = self.poll().await? {
+                if acked == id {
+                    return acknowledged(reason);
                 }
             }
-            Timer::after(Duration::from_millis(100)).await;
         }
     }
 
-    info!("Connected to broker!");
-    let mut mqtt_config = ClientConfig::new(
-        rust_mqtt::client::client_config::MqttVersion::MQTTv5,
-        CountingRng(20000),
-    );
-    // mqtt_config
-    //     .add_max_subscribe_qos(rust_mqtt::packet::v5::publish_packet::QualityOfService::QoS0);
-    mqtt_config.add_client_id("projector-controller");
-    // config.add_username(USERNAME);
-    // config.add_password(PASSWORD);
-    // mqtt_config.max_packet_size = 900;
-
-    // this has to be weird and unsafe so client can be passed around (?)
-    static mut TX_BUF: [u8; 4096] = [0; 4096];
-    static mut RX_BUF: [u8; 4096] = [0; 4096];
-
-    let tx_buf = unsafe { &mut TX_BUF };
-    let rx_buf = unsafe { &mut RX_BUF };
-
-    let mut client =
-        MqttClient::<'_, _, 5, _>::new(socket, tx_buf, 4096, rx_buf, 4096, mqtt_config);
-
-    client.connect_to_broker().await.unwrap();
-    info!("Connected to MQTT server!");
-
-    homassistant_initialization(&mut client).await;
-    info!("Sent discovery packet");
-
-    let mut projector = io::PROJECTOR.lock().await;
-    let projector = projector.as_mut().unwrap();
-
-    loop {
-        match select(client.receive_message(), Timer::after_secs(2)).await {
-            Either::First(msg) => {
-                let (topic, data) = msg.unwrap();
-                info!("Received on topic {}: {:?}", topic, data);
-
-                // FIXME: do not block for 20ms lolololol
-                io::blink_led2_ms(20).await;
-
-                match topic {
-                    "projector-controller/cmd/power" => {
-                        let msg = core::str::from_utf8(data).unwrap();
-                        println!("State message: {}", msg);
-
-                        // Clone to break the borrow
-                        let data_owned = data.to_vec();
-
-                        match msg {
-                            "ON" => {
-                                if let Err(_) = projector.power_on() {
-                                    error!("Failed to send power on command");
-                                    continue;
-                                } else {
-                                    info!("Sent power on command");
-                                }
-                            }
-                            "OFF" => {
-                                if let Err(_) = projector.power_off() {
-                                    error!("Failed to send power off command");
-                                    continue;
-                                } else {
-                                    info!("Sent power off command");
-                                }
-                            }
-                            _ => {
-                                warn!("Unknown power command: {}", msg);
-                            }
-                        }
-
-                        client
-                            .send_message(
-                                "projector-controller/stat/power",
-                                &data_owned,
-                                QualityOfService::QoS0,
-                                true,
-                            )
-                            .await
-                            .unwrap();
-
-                        info!("Published state message: {=[?]}", &data_owned);
-                    }
-                    "projector-controller/cmd/raw" => {
-                        projector.send(data).unwrap();
-                    }
-                    _ => {
-                        info!("Unknown topic: {}", topic);
-                    }
+    async fn reply(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        correlation_data: Option<&[u8]>,
+    ) -> Result<(), Error> {
+        // QoS 0 takes no packet id
+        let publish =
+            mqtt_packet::publish(topic, payload, QoS::AtMostOnce, false, 0, correlation_data);
+        self.send(&publish).await
+    }
+
+    async fn subscribe(&mut self, topics: &[&str]) -> Result<(), Error> {
+        let id = self.next_id();
+        self.send(&mqtt_packet::subscribe(id, topics)).await?;
+        loop {
+            if let Some(Ack::Subscribe { id: acked, reason }) = self.poll().await? {
+                if acked == id {
+                    return acknowledged(reason);
                 }
             }
-            Either::Second(()) => {
-                // periodically send availability
-                client
-                    .send_message(
-                        "projector-controller/availability",
-                        b"online",
-                        QualityOfService::QoS0,
-                        true,
-                    )
-                    .await
-                    .unwrap();
-                debug!("Published availability online");
+        }
+    }
+
+    async fn receive(&mut self) -> Result<Message<'_>, Error> {
+        // `mqtt::run` drops this on every other event, whatever was read by
+        // then waits in `pending`
+        loop {
+            if let Some(received) = self.pending.pop_front() {
+                let received = self.current.insert(received);
+                let message = Message::new(&received.topic, &received.payload).with_response(
+                    received.response_topic.as_deref(),
+                    received.correlation_data.as_deref(),
+                );
+                return Ok(message);
             }
+            self.poll().await?;
+        }
+    }
+
+    async fn ping(&mut self) -> Result<(), Error> {
+        // the answer is picked up by `poll`, one keep-alive later it is
+        // overdue
+        if self.pinged {
+            return Err(Error::PingTimeout);
+        }
+        self.pinged = true;
+        self.send(&mqtt_packet::pingreq()).await
+    }
+
+    async fn disconnect(&mut self) -> Result<(), Error> {
+        self.send(&mqtt_packet::disconnect()).await
+    }
+}
+
+/// LED1 lights up while the broker is unreachable, LED2 flashes for every
+/// command received.
+struct Leds;
+
+impl Indicator for Leds {
+    async fn connected(&mut self) {
+        if let Some(led) = LED1.lock().await.as_mut() {
+            led.set_low();
+        }
+    }
+
+    async fn disconnected(&mut self) {
+        if let Some(led) = LED1.lock().await.as_mut() {
+            led.set_high();
+        }
+    }
+
+    async fn activity(&mut self) {
+        // FIXME: do not block for 20ms lolololol
+        io::blink_led2_ms(20).await;
+    }
+}
+
+#[embassy_executor::task]
+pub async fn mqtt_task(
+    stack: Stack<'static>,
+    device: &'static Device,
+    settings: &'static Config,
+    rng: Rng,
+) {
+    // too big for the task arena
+    static CONNECTOR: StaticCell<TcpConnector> = StaticCell::new();
+    let connector = CONNECTOR.init(TcpConnector::new(stack, device, settings, rng));
+
+    RUNNING.store(true, Ordering::Relaxed);
+    mqtt::run(connector, &mut Leds, device).await;
+
+    // only returns on mqtt::SHUTDOWN, once the broker knows we are gone;
+    // `reboot` takes it from here
+    STOPPED.signal(());
+}
+
+/// Restart the board, first publishing `offline` and disconnecting if the
+/// broker is connected. Every restart goes through here.
+pub async fn reboot() -> ! {
+    if RUNNING.load(Ordering::Relaxed) {
+        mqtt::SHUTDOWN.signal(());
+        if with_timeout(SHUTDOWN_TIMEOUT, STOPPED.wait())
+            .await
+            .is_err()
+        {
+            warn!("Broker connection did not shut down in time");
         }
     }
+    esp_hal::system::software_reset()
 }
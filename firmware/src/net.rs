@@ -1,8 +1,13 @@
 use core::net::Ipv4Addr;
 
-use defmt::{debug, error, info};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use defmt::{debug, error, info, warn};
 use embassy_executor::Spawner;
-use embassy_net::{tcp::TcpSocket, Runner, StackResources};
+use embassy_net::{
+    tcp::TcpSocket, ConfigV4, ConfigV6, DhcpConfig, Ipv4Cidr, Runner, Stack, StackResources,
+    StaticConfigV4,
+};
 use embassy_time::{Duration, Timer};
 use esp_alloc as _;
 use esp_backtrace as _;
@@ -10,9 +15,18 @@ use esp_hal::timer::systimer::SystemTimer;
 use esp_hal::{clock::CpuClock, rng::Rng, timer::timg::TimerGroup};
 use esp_wifi::{
     init,
-    wifi::{ClientConfiguration, Configuration, WifiController, WifiDevice, WifiEvent, WifiState},
+    wifi::{
+        AccessPointConfiguration, AccessPointInfo, AuthMethod, ClientConfiguration, Configuration,
+        WifiController, WifiDevice, WifiEvent,
+    },
     EspWifiController,
 };
+use projector_controller::config::Config;
+use projector_controller::device::Device;
+use projector_controller::portal::Network;
+use projector_controller::wifi::{self, Candidate, Seen};
+
+use crate::{mdns, portal};
 
 // When you are okay with using a nightly compiler it's better to use https://docs.rs/static_cell/2.1.0/static_cell/macro.make_static.html
 macro_rules! mk_static {
@@ -24,53 +38,196 @@ macro_rules! mk_static {
     }};
 }
 
-const SSID: &str = env!("SSID");
-const PASSWORD: &str = env!("PASSWORD");
+/// embassy-net's own socket for DNS queries, e.g. the broker name.
+const DNS_SOCKETS: usize = 1;
+
+/// The broker connection. With the `tls` feature TLS runs over this same
+/// socket, so it adds none.
+const BROKER_SOCKETS: usize = 1;
+
+/// The DHCP client, only without a static IPv4 address. SLAAC works
+/// without a socket.
+const DHCP_SOCKETS: usize = 1;
+
+/// Sockets on the station interface with a static address.
+const STATION_SOCKETS: usize = DNS_SOCKETS + BROKER_SOCKETS + mdns::SOCKETS;
+
+/// Access points kept from a scan.
+const SCAN_SIZE: usize = 16;
+
+/// Addressing of the station interface: DHCP announcing the device id as
+/// host name, or the stored static address, and SLAAC if enabled.
+pub fn station_config(settings: &Config, device: &Device) -> embassy_net::Config {
+    let mut config = embassy_net::Config::default();
+    config.ipv4 = match &settings.ip.ipv4 {
+        Some(ipv4) => ConfigV4::Static(StaticConfigV4 {
+            address: Ipv4Cidr::new(ipv4.address, ipv4.prefix_len),
+            gateway: ipv4.gateway,
+            dns_servers: ipv4.dns_servers.iter().copied().collect(),
+        }),
+        None => {
+            let mut dhcp = DhcpConfig::default();
+            // device ids are valid host names and short enough
+            dhcp.hostname = device.id().try_into().ok();
+            ConfigV4::Dhcp(dhcp)
+        }
+    };
+    if settings.ip.ipv6 {
+        config.ipv6 = ConfigV6::Slaac;
+    }
+    config
+}
 
-// connects to the wifi and maintains the connection
+/// The station's network stack, with a socket for the DHCP client only if
+/// `config` asks for DHCP.
+pub fn station_stack(
+    interface: WifiDevice<'static>,
+    config: embassy_net::Config,
+    seed: u64,
+) -> (Stack<'static>, Runner<'static, WifiDevice<'static>>) {
+    // on the heap, so only the resources in use take up room
+    if matches!(config.ipv4, ConfigV4::Dhcp(_)) {
+        let resources = Box::leak(Box::new(
+            StackResources::<{ STATION_SOCKETS + DHCP_SOCKETS }>::new(),
+        ));
+        embassy_net::new(interface, config, resources, seed)
+    } else {
+        let resources = Box::leak(Box::new(StackResources::<STATION_SOCKETS>::new()));
+        embassy_net::new(interface, config, resources, seed)
+    }
+}
+
+// joins the best known network and rejoins after losing it, or opens the
+// setup access point when none of them works out
 #[embassy_executor::task]
-pub async fn connection(mut controller: WifiController<'static>) {
+pub async fn connection(
+    mut controller: WifiController<'static>,
+    device: &'static Device,
+    settings: &'static Config,
+) {
     info!("start connection task");
     // info!("Device capabilities: {:?}", controller.capabilities());
     loop {
-        match esp_wifi::wifi::wifi_state() {
-            WifiState::StaConnected => {
-                // wait until we're no longer connected
-                controller.wait_for_event(WifiEvent::StaDisconnected).await;
-                Timer::after(Duration::from_millis(5000)).await
-            }
-            _ => {}
-        }
         if !matches!(controller.is_started(), Ok(true)) {
-            let client_config = Configuration::Client(ClientConfiguration {
-                ssid: SSID.into(),
-                password: PASSWORD.into(),
-                ..Default::default()
-            });
-            controller.set_configuration(&client_config).unwrap();
+            // every connect brings its own configuration
+            controller
+                .set_configuration(&Configuration::Client(Default::default()))
+                .unwrap();
             info!("Starting wifi");
             controller.start_async().await.unwrap();
             info!("Wifi started!");
+        }
+
+        // scanned again every time, access points come and go
+        info!("Scan");
+        let scan = controller.scan_n_async(SCAN_SIZE).await.unwrap_or_default();
+        for ap in &scan {
+            debug!("{:?}", defmt::Debug2Format(ap));
+        }
+        *portal::NETWORKS.lock().await = networks(&scan);
 
-            info!("Scan");
-            let result = controller.scan_n_async(10).await.unwrap();
-            for ap in result {
-                debug!("{:?}", defmt::Debug2Format(&ap));
+        let seen: Vec<Seen> = scan
+            .iter()
+            .map(|ap| Seen {
+                ssid: ap.ssid.as_str(),
+                bssid: ap.bssid,
+                channel: ap.channel,
+                rssi: ap.signal_strength,
+            })
+            .collect();
+        let mut joined = false;
+        for candidate in wifi::candidates(&settings.networks, &seen) {
+            if join(&mut controller, candidate).await {
+                joined = true;
+                break;
             }
         }
-        info!("About to connect...");
 
+        if !joined {
+            start_setup(&mut controller, device).await;
+            // the portal restarts the board when it is done
+            core::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Try `candidate` up to [`wifi::FAILURES_BEFORE_NEXT`] times. Returns
+/// `true` once a connection was made and lost again.
+async fn join(controller: &mut WifiController<'static>, candidate: Candidate<'_>) -> bool {
+    let network = candidate.network;
+    let client_config = ClientConfiguration {
+        ssid: network.ssid.as_str().into(),
+        password: network.password.as_str().into(),
+        bssid: candidate.bssid,
+        channel: candidate.channel,
+        ..Default::default()
+    };
+    controller
+        .set_configuration(&Configuration::Client(client_config))
+        .unwrap();
+
+    for _ in 0..wifi::FAILURES_BEFORE_NEXT {
+        info!(
+            "Connecting to {} ({})",
+            network.ssid.as_str(),
+            defmt::Debug2Format(&candidate.bssid)
+        );
         match controller.connect_async().await {
-            Ok(_) => info!("Wifi connected!"),
+            Ok(_) => {
+                info!("Wifi connected!");
+                // wait until we're no longer connected
+                controller.wait_for_event(WifiEvent::StaDisconnected).await;
+                warn!("Wifi disconnected");
+                Timer::after(Duration::from_millis(5000)).await;
+                return true;
+            }
             Err(e) => {
                 error!("Failed to connect to wifi: {}", defmt::Debug2Format(&e));
                 Timer::after(Duration::from_millis(5000)).await
             }
         }
     }
+    false
 }
 
-#[embassy_executor::task]
+/// Open the setup access point next to the station and hand over to
+/// `portal::portal_task`.
+async fn start_setup(controller: &mut WifiController<'static>, device: &Device) {
+    warn!(
+        "Cannot join any known network, opening setup access point {}",
+        device.id()
+    );
+    let access_point = AccessPointConfiguration {
+        ssid: device.id().into(),
+        auth_method: AuthMethod::None,
+        ..Default::default()
+    };
+    controller.stop_async().await.ok();
+    controller
+        .set_configuration(&Configuration::Mixed(Default::default(), access_point))
+        .unwrap();
+    controller.start_async().await.unwrap();
+    portal::START.signal(());
+}
+
+/// Scan results for the setup page, strongest first and each name once.
+fn networks(scan: &[AccessPointInfo]) -> Vec<Network> {
+    let mut networks: Vec<Network> = Vec::new();
+    for ap in scan {
+        let known = networks.iter().any(|n| n.ssid == ap.ssid.as_str());
+        if !ap.ssid.is_empty() && !known {
+            networks.push(Network {
+                ssid: ap.ssid.as_str().into(),
+                rssi: ap.signal_strength,
+            });
+        }
+    }
+    networks.sort_by_key(|n| core::cmp::Reverse(n.rssi));
+    networks
+}
+
+// one for the station, one for the setup access point
+#[embassy_executor::task(pool_size = 2)]
 pub async fn net_task(mut runner: Runner<'static, WifiDevice<'static>>) {
     runner.run().await
 }
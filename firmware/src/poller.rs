@@ -0,0 +1,10 @@
+//! Periodically asks the projector what it is doing and reports changes, so
+//! the state topics follow the projector even when the IR remote is used.
+
+use embassy_time::Duration;
+use projector_controller::poller;
+
+#[embassy_executor::task]
+pub async fn poller_task(interval: Duration) {
+    poller::run(interval).await
+}
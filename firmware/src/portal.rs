@@ -0,0 +1,251 @@
+//! Setup mode: when the stored network cannot be joined, `net::connection`
+//! opens an access point named after the device id, and this serves the
+//! setup page from `projector_controller::portal` on it, with DHCP and a DNS
+//! server that sends every name here, and an mDNS responder for phones that
+//! ask for `<device id>.local`. Saving the form stores the settings and
+//! restarts the board.
+//!
+//! The access point is open, anyone in range can re-home the board while it
+//! is in setup.
+
+use core::net::Ipv4Addr;
+
+use alloc::vec::Vec;
+use defmt::{info, warn};
+use embassy_futures::select::{select4, Either4};
+use embassy_net::tcp::TcpSocket;
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{IpEndpoint, Stack};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::mutex::Mutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{with_timeout, Duration, Timer};
+use embedded_io_async::Write;
+use projector_controller::config::Config;
+use projector_controller::device::Device;
+use projector_controller::dhcp::DhcpServer;
+use projector_controller::dns;
+use projector_controller::mdns::{self, Responder, Service};
+use projector_controller::portal::{self, Network, Parsed, MAX_REQUEST_SIZE};
+use static_cell::StaticCell;
+
+use crate::config::ConfigStore;
+use crate::mqtt;
+
+/// The board's address on its access point.
+pub const ADDRESS: Ipv4Addr = Ipv4Addr::new(192, 168, 4, 1);
+
+/// Sockets on the access point: DHCP, DNS, mDNS and HTTP servers, and the
+/// one embassy-net keeps for its own DNS queries.
+pub const SOCKETS: usize = 5;
+
+/// What the access point offers over mDNS.
+const SERVICES: [Service; 1] = [Service {
+    kind: "_http._tcp",
+    port: 80,
+    txt: &["path=/"],
+}];
+
+/// Networks from the last scan, offered on the page.
+pub static NETWORKS: Mutex<CriticalSectionRawMutex, Vec<Network>> = Mutex::new(Vec::new());
+
+/// Raised by `net::connection` once the access point is up.
+pub static START: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Without a visitor for this long, restart and give the stored network
+/// another try, it may just have been down.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+const UDP_BUFFER_SIZE: usize = 1024;
+const TCP_BUFFER_SIZE: usize = 1536;
+
+/// Socket buffers, too big for the task arena.
+struct Buffers {
+    dhcp: UdpBuffers,
+    dns: UdpBuffers,
+    mdns: UdpBuffers,
+    http_rx: [u8; TCP_BUFFER_SIZE],
+    http_tx: [u8; TCP_BUFFER_SIZE],
+    request: [u8; MAX_REQUEST_SIZE],
+}
+
+/// A UDP socket's buffers and one for the packet at hand.
+pub struct UdpBuffers {
+    rx_meta: [PacketMetadata; 4],
+    rx: [u8; UDP_BUFFER_SIZE],
+    tx_meta: [PacketMetadata; 4],
+    tx: [u8; UDP_BUFFER_SIZE],
+    packet: [u8; UDP_BUFFER_SIZE],
+}
+
+impl UdpBuffers {
+    pub const fn new() -> Self {
+        Self {
+            rx_meta: [PacketMetadata::EMPTY; 4],
+            rx: [0; UDP_BUFFER_SIZE],
+            tx_meta: [PacketMetadata::EMPTY; 4],
+            tx: [0; UDP_BUFFER_SIZE],
+            packet: [0; UDP_BUFFER_SIZE],
+        }
+    }
+
+    pub fn socket(&mut self, stack: Stack<'static>, port: u16) -> (UdpSocket<'_>, &mut [u8]) {
+        let mut socket = UdpSocket::new(
+            stack,
+            &mut self.rx_meta,
+            &mut self.rx,
+            &mut self.tx_meta,
+            &mut self.tx,
+        );
+        socket.bind(port).unwrap();
+        (socket, &mut self.packet)
+    }
+}
+
+/// Waits for [`START`], serves the access point at `stack` and restarts
+/// the board once the settings are saved or nobody came by.
+#[embassy_executor::task]
+pub async fn portal_task(
+    stack: Stack<'static>,
+    device: &'static Device,
+    settings: &'static Config,
+) {
+    static BUFFERS: StaticCell<Buffers> = StaticCell::new();
+
+    START.wait().await;
+    info!(
+        "Setup portal on http://{}/",
+        defmt::Display2Format(&ADDRESS)
+    );
+
+    let buffers = BUFFERS.init(Buffers {
+        dhcp: UdpBuffers::new(),
+        dns: UdpBuffers::new(),
+        mdns: UdpBuffers::new(),
+        http_rx: [0; TCP_BUFFER_SIZE],
+        http_tx: [0; TCP_BUFFER_SIZE],
+        request: [0; MAX_REQUEST_SIZE],
+    });
+    let http = Http {
+        stack,
+        settings,
+        rx: &mut buffers.http_rx,
+        tx: &mut buffers.http_tx,
+        request: &mut buffers.request,
+    };
+
+    match select4(
+        dhcp(stack, &mut buffers.dhcp),
+        dns(stack, &mut buffers.dns),
+        mdns(stack, &mut buffers.mdns, device),
+        http.run(),
+    )
+    .await
+    {
+        Either4::First(never) | Either4::Second(never) | Either4::Third(never) => match never {},
+        Either4::Fourth(()) => {}
+    }
+
+    mqtt::reboot().await
+}
+
+async fn dhcp(stack: Stack<'static>, buffers: &mut UdpBuffers) -> ! {
+    let (socket, packet) = buffers.socket(stack, 67);
+    let mut server = DhcpServer::new(ADDRESS);
+    loop {
+        let Ok((len, _)) = socket.recv_from(packet).await else {
+            continue;
+        };
+        if let Some(reply) = server.handle(&packet[..len]) {
+            // clients without an address only hear broadcasts
+            let to = IpEndpoint::new(Ipv4Addr::BROADCAST.into(), 68);
+            if socket.send_to(&reply, to).await.is_err() {
+                warn!("Sending DHCP reply failed");
+            }
+        }
+    }
+}
+
+async fn dns(stack: Stack<'static>, buffers: &mut UdpBuffers) -> ! {
+    let (socket, packet) = buffers.socket(stack, 53);
+    loop {
+        let Ok((len, meta)) = socket.recv_from(packet).await else {
+            continue;
+        };
+        if let Some(reply) = dns::catch_all_reply(&packet[..len], ADDRESS) {
+            if socket.send_to(&reply, meta.endpoint).await.is_err() {
+                warn!("Sending DNS reply failed");
+            }
+        }
+    }
+}
+
+async fn mdns(stack: Stack<'static>, buffers: &mut UdpBuffers, device: &Device) -> ! {
+    if stack.join_multicast_group(mdns::GROUP).is_err() {
+        warn!("Cannot join the mDNS group");
+    }
+    let (socket, packet) = buffers.socket(stack, mdns::PORT);
+    let responder = Responder::new(device.id(), &SERVICES);
+    crate::mdns::respond(&socket, packet, &responder, ADDRESS).await
+}
+
+struct Http<'b> {
+    stack: Stack<'static>,
+    settings: &'static Config,
+    rx: &'b mut [u8],
+    tx: &'b mut [u8],
+    request: &'b mut [u8],
+}
+
+impl Http<'_> {
+    /// Serve one connection at a time until the settings are saved or
+    /// [`IDLE_TIMEOUT`] passes without one.
+    async fn run(mut self) {
+        loop {
+            let mut socket = TcpSocket::new(self.stack, &mut *self.rx, &mut *self.tx);
+            socket.set_timeout(Some(Duration::from_secs(10)));
+            match with_timeout(IDLE_TIMEOUT, socket.accept(80)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => {
+                    warn!("Accept failed: {}", defmt::Debug2Format(&e));
+                    continue;
+                }
+                Err(_) => {
+                    info!("Nobody came to set up, restarting");
+                    return;
+                }
+            }
+
+            let mut len = 0;
+            while portal::parse(&self.request[..len]) == Parsed::Incomplete {
+                match socket.read(&mut self.request[len..]).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => len += n,
+                }
+            }
+            let Parsed::Complete(request) = portal::parse(&self.request[..len]) else {
+                socket.abort();
+                continue;
+            };
+
+            let (response, saved) = {
+                let networks = NETWORKS.lock().await;
+                portal::handle(&request, self.settings, &networks, ADDRESS)
+            };
+            if socket.write_all(&response).await.is_ok() {
+                socket.flush().await.ok();
+            }
+            socket.close();
+
+            if let Some(config) = saved {
+                match ConfigStore::new() {
+                    Some(mut store) => store.save(&config).await,
+                    None => warn!("No config partition, settings not saved"),
+                }
+                // let the page go out before the access point goes away
+                Timer::after(Duration::from_secs(1)).await;
+                return;
+            }
+        }
+    }
+}
@@ -1,120 +1,19 @@
-use alloc::vec::Vec;
-use defmt::debug;
-use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
-use embedded_io::Write;
-use esp_hal::uart::Uart;
-
-#[derive(Debug)]
-pub enum ProjectorError {
-    WriteError,
-    ParseError,
-    ReadError,
-}
-
-/// PT-AH1000E Projector Control via RS232
-pub struct Projector<'a, Dm: esp_hal::DriverMode> {
-    port: Uart<'a, Dm>,
-}
-
-impl<'a, Dm: esp_hal::DriverMode> Projector<'a, Dm> {
-    //! Create a new Projector instance with the given UART port
-    pub fn new(port: Uart<'a, Dm>) -> Self {
-        Self { port }
-    }
-
-    pub fn send(&mut self, data: &[u8]) -> Result<(), ProjectorError> {
-        // convert to 0x02 ...data 0x03
-        let mut framed_data = Vec::with_capacity(data.len() + 2);
-        framed_data.push(0x02); // STX
-        framed_data.extend_from_slice(data);
-        framed_data.push(0x03); // ETX
-
-        // debug!("Sending: {}", framed_data);
-
-        self.port
-            .write(&framed_data)
-            .map_err(|_| ProjectorError::WriteError)
-            .map(|_| ())
-    }
-
-    pub fn receive(&mut self, buffer: &mut [u8]) -> Result<usize, ProjectorError> {
-        let mut count = 0;
-
-        // read until 0x3 or buffer full
-        let mut tmp_buf = [0u8; 1];
-
-        loop {
-            match self.port.read(&mut tmp_buf) {
-                Ok(_) => {
-                    if count >= buffer.len() {
-                        break;
-                    }
-                    buffer[count] = tmp_buf[0];
-                    count += 1;
+//! PT-AH1000E on UART1.
+//!
+//! The request queue, timeouts and power tracking live in
+//! `projector_controller::projector`; this module only plugs in the UART and
+//! runs the queue as an embassy task.
 
-                    // EOL
-                    if tmp_buf[0] == 0x3 {
-                        break;
-                    }
-                }
-                Err(_) => break, // no more data
-            }
-        }
-
-        debug!(
-            "Received: {:?}",
-            core::str::from_utf8(&buffer[..count]).unwrap_or("<invalid utf8>")
-        );
-
-        Ok(count)
-    }
-
-    pub fn power_on(&mut self) -> Result<(), ProjectorError> {
-        self.send(b"PON")
-    }
-
-    pub fn power_off(&mut self) -> Result<(), ProjectorError> {
-        self.send(b"POF")
-    }
-
-    pub fn menu(&mut self) -> Result<(), ProjectorError> {
-        self.send(b"OMN")
-    }
-
-    pub fn enter(&mut self) -> Result<(), ProjectorError> {
-        self.send(b"OEN")
-    }
-
-    pub fn up(&mut self) -> Result<(), ProjectorError> {
-        self.send(b"OBK")
-    }
-
-    pub fn left(&mut self) -> Result<(), ProjectorError> {
-        self.send(b"OCL")
-    }
-
-    pub fn right(&mut self) -> Result<(), ProjectorError> {
-        self.send(b"OCR")
-    }
-
-    pub fn down(&mut self) -> Result<(), ProjectorError> {
-        self.send(b"OCU")
-    }
+use esp_hal::uart::Uart;
+use esp_hal::Async;
+use projector_controller::projector;
 
-    pub fn back(&mut self) -> Result<(), ProjectorError> {
-        self.send(b"OCD")
-    }
+pub use projector_controller::projector::{ProjectorLink, RawFilter};
 
-    pub fn is_on(&mut self) -> Result<bool, ProjectorError> {
-        let mut buffer = [0u8; 16];
-        self.send(b"QPW")?;
-        let len = self.receive(&mut buffer)?;
-        let response = core::str::from_utf8(&buffer[..len]).unwrap_or("");
+pub type UartProjector = ProjectorLink<Uart<'static, Async>>;
 
-        match response.trim() {
-            "000" => Ok(true),
-            "001" => Ok(false),
-            _ => Err(ProjectorError::ParseError),
-        }
-    }
+/// Owns the projector and works through the requests one at a time.
+#[embassy_executor::task]
+pub async fn projector_task(link: UartProjector) {
+    projector::run(link).await
 }
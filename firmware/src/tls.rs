@@ -0,0 +1,117 @@
+//! TLS to the broker with embedded-tls, only built with the `tls` feature.
+//!
+//! The broker's certificate has to chain up to the CA from `MQTT_CA_CERT`,
+//! which is the only trust anchor, and match the server name from the
+//! settings (the broker's name unless one is stored, see
+//! `MqttConfig::server_name`). There is no wall clock on the board, so
+//! validity dates are not checked.
+
+use embassy_net::tcp::TcpSocket;
+use embedded_tls::webpki::CertVerifier;
+use embedded_tls::{
+    Aes128GcmSha256, Certificate, CryptoProvider, TlsClock, TlsConfig, TlsConnection, TlsContext,
+    TlsError, TlsVerifier,
+};
+use esp_hal::rng::Rng;
+use rand_core::{CryptoRng, CryptoRngCore, RngCore};
+
+/// Pinned CA, DER encoded.
+const CA_CERT: &[u8] = include_bytes!(env!("MQTT_CA_CERT"));
+
+/// Largest TLS record we may receive, brokers don't negotiate smaller ones.
+const READ_RECORD_SIZE: usize = 16640;
+const WRITE_RECORD_SIZE: usize = 4096;
+
+/// Largest broker certificate the verifier can take apart.
+const MAX_CERT_SIZE: usize = 4096;
+
+pub type Link<'a> = TlsConnection<'a, TcpSocket<'a>, Aes128GcmSha256>;
+
+/// Record buffers and randomness for one connection at a time.
+pub struct Tls {
+    rng: Rng,
+    read_record: [u8; READ_RECORD_SIZE],
+    write_record: [u8; WRITE_RECORD_SIZE],
+}
+
+impl Tls {
+    pub fn new(rng: Rng) -> Self {
+        Self {
+            rng,
+            read_record: [0; READ_RECORD_SIZE],
+            write_record: [0; WRITE_RECORD_SIZE],
+        }
+    }
+
+    /// Run the handshake over a connected `socket`, expecting a
+    /// certificate for `server_name`.
+    pub async fn open<'a>(
+        &'a mut self,
+        socket: TcpSocket<'a>,
+        server_name: &str,
+    ) -> Result<Link<'a>, TlsError> {
+        let config = TlsConfig::new()
+            .with_server_name(server_name)
+            .with_ca(Certificate::X509(CA_CERT));
+
+        let mut link = TlsConnection::new(socket, &mut self.read_record, &mut self.write_record);
+        let provider = Provider {
+            rng: HardwareRng(self.rng),
+            verifier: CertVerifier::new(),
+        };
+        link.open(TlsContext::new(&config, provider)).await?;
+        Ok(link)
+    }
+}
+
+/// The hardware RNG draws on RF noise, which is cryptographically random
+/// while the radio is running, and it is as long as we are on Wi-Fi.
+struct HardwareRng(Rng);
+
+impl RngCore for HardwareRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.random()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        (u64::from(self.0.random()) << 32) | u64::from(self.0.random())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.read(dest);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for HardwareRng {}
+
+/// No clock, so no expiry checks.
+struct NoClock;
+
+impl TlsClock for NoClock {
+    fn now() -> Option<u64> {
+        None
+    }
+}
+
+struct Provider {
+    rng: HardwareRng,
+    verifier: CertVerifier<Aes128GcmSha256, NoClock, MAX_CERT_SIZE>,
+}
+
+impl CryptoProvider for Provider {
+    type CipherSuite = Aes128GcmSha256;
+    type Signature = &'static [u8];
+
+    fn rng(&mut self) -> impl CryptoRngCore {
+        &mut self.rng
+    }
+
+    fn verifier(&mut self) -> Result<&mut impl TlsVerifier<Self::CipherSuite>, TlsError> {
+        Ok(&mut self.verifier)
+    }
+}
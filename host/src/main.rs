@@ -0,0 +1,160 @@
+//! Projector controller as a Linux program: the firmware's application
+//! logic against an MQTT broker and a serial port, e.g. a USB adapter or the
+//! simulator's PTY.
+//!
+//! ```text
+//! projector-controller --serial PATH [--broker HOST] [--port PORT]
+//!                      [--device-id ID] [--client-id ID] [--username USER]
+//!                      [--poll-interval SECS] [--raw-allow CODES] [--raw-deny CODES]
+//! ```
+//!
+//! The device id (default `projector-controller`) is the topic prefix and,
+//! unless `--client-id` is given, the MQTT client id.
+//!
+//! The broker password is taken from `MQTT_PASSWORD`, so it doesn't show up
+//! in the process list.
+//!
+//! `--raw-allow` and `--raw-deny` take comma separated command codes (`QIN`,
+//! `PON`...) and limit what `cmd/raw` may send.
+//!
+//! TLS is only supported by the firmware so far.
+
+mod mqtt;
+mod serial;
+
+use std::path::PathBuf;
+use std::process::{self, ExitCode};
+use std::{env, thread};
+
+use embassy_futures::select::{select3, Either3};
+use embassy_time::Duration;
+use nix::sys::signal::{SigSet, Signal};
+use projector_controller::device::Device;
+use projector_controller::projector::{ProjectorLink, RawFilter};
+use projector_controller::{poller, projector};
+use rumqttc::v5::MqttOptions;
+
+use crate::mqtt::RumqttConnector;
+use crate::serial::SerialPort;
+
+const USAGE: &str = "usage: projector-controller --serial PATH [--broker HOST] [--port PORT] \
+                     [--device-id ID] [--client-id ID] [--username USER] [--poll-interval SECS] \
+                     [--raw-allow CODES] [--raw-deny CODES]";
+
+struct Args {
+    serial: PathBuf,
+    broker: String,
+    port: u16,
+    device: Device,
+    client_id: Option<String>,
+    username: Option<String>,
+    poll_interval: Duration,
+    raw_filter: RawFilter,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut serial = None;
+    let mut args = Args {
+        serial: PathBuf::new(),
+        broker: "localhost".into(),
+        port: 1883,
+        device: Device::new("projector-controller").unwrap(),
+        client_id: None,
+        username: None,
+        poll_interval: Duration::from_secs(10),
+        raw_filter: RawFilter::default(),
+    };
+
+    let mut it = env::args().skip(1);
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or(format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--serial" => serial = Some(PathBuf::from(value()?)),
+            "--broker" => args.broker = value()?,
+            "--port" => args.port = number(&value()?)?,
+            "--device-id" => {
+                let id = value()?;
+                args.device = Device::new(&id).map_err(|e| format!("{id}: {e}"))?;
+            }
+            "--client-id" => args.client_id = Some(value()?),
+            "--username" => args.username = Some(value()?),
+            "--poll-interval" => args.poll_interval = Duration::from_secs(number(&value()?)?),
+            "--raw-allow" => args.raw_filter = args.raw_filter.allow(&value()?),
+            "--raw-deny" => args.raw_filter = args.raw_filter.deny(&value()?),
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ => return Err(format!("unknown argument {arg}\n{USAGE}")),
+        }
+    }
+
+    args.serial = serial.ok_or(format!("--serial is required\n{USAGE}"))?;
+    Ok(args)
+}
+
+fn number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("not a number: {value}"))
+}
+
+/// Turn SIGINT and SIGTERM into a clean MQTT shutdown, so the broker
+/// publishes `offline` right away instead of after the keep-alive. A second
+/// signal exits straight away.
+fn handle_signals() -> nix::Result<()> {
+    let mut signals = SigSet::empty();
+    signals.add(Signal::SIGINT);
+    signals.add(Signal::SIGTERM);
+    // blocked here, before any other thread exists, so only `wait` sees them
+    signals.thread_block()?;
+
+    thread::spawn(move || {
+        if signals.wait().is_ok() {
+            projector_controller::mqtt::SHUTDOWN.signal(());
+        }
+        let _ = signals.wait();
+        process::exit(1);
+    });
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> ExitCode {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Err(e) = handle_signals() {
+        eprintln!("cannot set up signal handling: {e}");
+        return ExitCode::FAILURE;
+    }
+
+    let port = match SerialPort::open(&args.serial) {
+        Ok(port) => port,
+        Err(e) => {
+            eprintln!("cannot open {}: {e}", args.serial.display());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let client_id = args.client_id.unwrap_or_else(|| args.device.id().into());
+    let mut options = MqttOptions::new(client_id, args.broker, args.port);
+    if let Some(username) = args.username {
+        let password = env::var("MQTT_PASSWORD").unwrap_or_default();
+        options.set_credentials(username, password);
+    }
+    let mut connector = RumqttConnector::new(options, &args.device);
+
+    // the broker connection is retried forever, only a signal ends this
+    let result = select3(
+        projector::run(ProjectorLink::new(port).with_raw_filter(args.raw_filter)),
+        poller::run(args.poll_interval),
+        projector_controller::mqtt::run(&mut connector, &mut (), &args.device),
+    )
+    .await;
+    match result {
+        Either3::Third(()) => ExitCode::SUCCESS,
+    }
+}
@@ -0,0 +1,243 @@
+//! Broker connection over rumqttc.
+
+use std::fmt;
+use std::time::Duration;
+
+use bytes::Bytes;
+use projector_controller::device::Device;
+use projector_controller::mqtt::{self, Connector, Message, Session, KEEP_ALIVE, OFFLINE};
+use rumqttc::v5::mqttbytes::v5::{Filter, LastWill, Packet, Publish, PublishProperties};
+use rumqttc::v5::mqttbytes::QoS;
+use rumqttc::v5::{AsyncClient, ClientError, ConnectionError, Event, EventLoop, MqttOptions};
+use rumqttc::Outgoing;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+/// Requests rumqttc buffers before `publish` has to wait, enough for the
+/// whole discovery burst.
+const REQUEST_CAPACITY: usize = 64;
+
+/// How long a clean disconnect may take to go out.
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+pub enum Error {
+    Connection(ConnectionError),
+    Client(ClientError),
+    /// The event loop stopped without reporting why.
+    Closed,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connection(e) => write!(f, "connection: {e}"),
+            Self::Client(e) => write!(f, "client: {e}"),
+            Self::Closed => f.write_str("event loop stopped"),
+        }
+    }
+}
+
+pub struct RumqttConnector {
+    options: MqttOptions,
+}
+
+impl RumqttConnector {
+    /// Connect with `options`, plus the controller's keep-alive and the
+    /// will on `device`'s availability topic.
+    pub fn new(mut options: MqttOptions, device: &Device) -> Self {
+        // rumqttc sends the pings itself
+        options.set_keep_alive(Duration::from_secs(KEEP_ALIVE.as_secs()));
+        options.set_last_will(LastWill::new(
+            device.availability_topic(),
+            OFFLINE,
+            QoS::AtLeastOnce,
+            true,
+            None,
+        ));
+        Self { options }
+    }
+}
+
+impl Connector for RumqttConnector {
+    type Error = Error;
+    type Session<'a> = RumqttSession;
+
+    async fn connect(&mut self) -> Result<RumqttSession, Error> {
+        let (client, mut eventloop) = AsyncClient::new(self.options.clone(), REQUEST_CAPACITY);
+
+        // the first poll connects, so refusals show up here
+        loop {
+            let event = eventloop.poll().await.map_err(Error::Connection)?;
+            if let Event::Incoming(Packet::ConnAck(_)) = event {
+                break;
+            }
+        }
+
+        let (tx, incoming) = mpsc::unbounded_channel();
+        let (acks_tx, acks) = mpsc::unbounded_channel();
+        let driver = tokio::spawn(drive(eventloop, tx, acks_tx));
+        Ok(RumqttSession {
+            client,
+            incoming,
+            acks,
+            current: None,
+            driver,
+        })
+    }
+}
+
+/// Progress of a QoS 1 publish, by packet id.
+enum Ack {
+    /// Written to the broker.
+    Sent(u16),
+    /// Acknowledged by the broker.
+    Acked(u16),
+}
+
+/// Keep the event loop going on its own task, `EventLoop::poll` must not be
+/// cancelled halfway through like the controller's `select` would. Ends
+/// once a disconnect has gone out.
+async fn drive(
+    mut eventloop: EventLoop,
+    tx: mpsc::UnboundedSender<Result<Publish, ConnectionError>>,
+    acks: mpsc::UnboundedSender<Ack>,
+) {
+    loop {
+        let item = match eventloop.poll().await {
+            Ok(Event::Incoming(Packet::Publish(publish))) => Ok(publish),
+            // QoS 0 publishes go out with packet id 0
+            Ok(Event::Outgoing(Outgoing::Publish(pkid))) if pkid != 0 => {
+                let _ = acks.send(Ack::Sent(pkid));
+                continue;
+            }
+            Ok(Event::Incoming(Packet::PubAck(ack))) => {
+                let _ = acks.send(Ack::Acked(ack.pkid));
+                continue;
+            }
+            Ok(Event::Outgoing(Outgoing::Disconnect)) => return,
+            Ok(_) => continue,
+            Err(e) => Err(e),
+        };
+        let failed = item.is_err();
+        if tx.send(item).is_err() || failed {
+            return;
+        }
+    }
+}
+
+pub struct RumqttSession {
+    client: AsyncClient,
+    incoming: mpsc::UnboundedReceiver<Result<Publish, ConnectionError>>,
+    acks: mpsc::UnboundedReceiver<Ack>,
+    /// Message handed out by the last `receive`.
+    current: Option<Publish>,
+    driver: JoinHandle<()>,
+}
+
+impl Session for RumqttSession {
+    type Error = Error;
+
+    async fn publish(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        qos: mqtt::QoS,
+        retain: bool,
+    ) -> Result<(), Error> {
+        self.client
+            .publish(topic, rumqttc_qos(qos), retain, payload.to_vec())
+            .await
+            .map_err(Error::Client)?;
+        if qos == mqtt::QoS::AtMostOnce {
+            return Ok(());
+        }
+
+        // rumqttc only queued it; it is not delivered, and may not leave the
+        // outbox, until the broker acknowledges it. Publishes go out one at a
+        // time, so the next one written is this one.
+        let mut pkid = None;
+        loop {
+            match self.acks.recv().await {
+                Some(Ack::Sent(sent)) if pkid.is_none() => pkid = Some(sent),
+                Some(Ack::Acked(acked)) if pkid == Some(acked) => return Ok(()),
+                Some(_) => {}
+                // the connection dropped, the driver says why on `incoming`
+                None => return Err(Error::Closed),
+            }
+        }
+    }
+
+    async fn reply(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        correlation_data: Option<&[u8]>,
+    ) -> Result<(), Error> {
+        let properties = PublishProperties {
+            correlation_data: correlation_data.map(Bytes::copy_from_slice),
+            ..Default::default()
+        };
+        self.client
+            .publish_with_properties(topic, QoS::AtMostOnce, false, payload.to_vec(), properties)
+            .await
+            .map_err(Error::Client)
+    }
+
+    async fn subscribe(&mut self, topics: &[&str]) -> Result<(), Error> {
+        let filters = topics.iter().map(|t| Filter::new(*t, QoS::AtLeastOnce));
+        self.client
+            .subscribe_many(filters)
+            .await
+            .map_err(Error::Client)
+    }
+
+    async fn receive(&mut self) -> Result<Message<'_>, Error> {
+        loop {
+            match self.incoming.recv().await {
+                Some(Ok(publish)) if std::str::from_utf8(&publish.topic).is_ok() => {
+                    let publish = self.current.insert(publish);
+                    let properties = publish.properties.as_ref();
+                    let message = Message::new(
+                        std::str::from_utf8(&publish.topic).unwrap(),
+                        &publish.payload,
+                    )
+                    .with_response(
+                        properties.and_then(|p| p.response_topic.as_deref()),
+                        properties.and_then(|p| p.correlation_data.as_deref()),
+                    );
+                    return Ok(message);
+                }
+                Some(Ok(publish)) => log::warn!("Ignoring non UTF-8 topic {:?}", publish.topic),
+                Some(Err(e)) => return Err(Error::Connection(e)),
+                None => return Err(Error::Closed),
+            }
+        }
+    }
+
+    async fn ping(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn disconnect(&mut self) -> Result<(), Error> {
+        self.client.disconnect().await.map_err(Error::Client)?;
+        // give the driver a moment to flush the offline message and the
+        // disconnect before the session is dropped
+        let _ = timeout(DISCONNECT_TIMEOUT, &mut self.driver).await;
+        Ok(())
+    }
+}
+
+impl Drop for RumqttSession {
+    fn drop(&mut self) {
+        self.driver.abort();
+    }
+}
+
+fn rumqttc_qos(qos: mqtt::QoS) -> QoS {
+    match qos {
+        mqtt::QoS::AtMostOnce => QoS::AtMostOnce,
+        mqtt::QoS::AtLeastOnce => QoS::AtLeastOnce,
+    }
+}
@@ -0,0 +1,75 @@
+//! Serial device (or the simulator's PTY) as an `embedded_io_async` port.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::fd::AsFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use nix::fcntl::OFlag;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::sys::termios::{cfmakeraw, cfsetspeed, tcgetattr, tcsetattr, BaudRate, SetArg};
+use tokio::io::unix::AsyncFd;
+
+/// Raw 9600 8N1 port, like UART1 on the board.
+pub struct SerialPort {
+    fd: AsyncFd<File>,
+}
+
+impl SerialPort {
+    /// Open `path`; must be called from within the tokio runtime.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags((OFlag::O_NOCTTY | OFlag::O_NONBLOCK).bits())
+            .open(path)?;
+
+        // STX/ETX are ^B/^C, keep the line discipline away from them
+        let mut termios = tcgetattr(&file)?;
+        cfmakeraw(&mut termios);
+        cfsetspeed(&mut termios, BaudRate::B9600)?;
+        tcsetattr(&file, SetArg::TCSANOW, &termios)?;
+
+        Ok(Self {
+            fd: AsyncFd::new(file)?,
+        })
+    }
+}
+
+impl embedded_io::ErrorType for SerialPort {
+    type Error = io::Error;
+}
+
+impl embedded_io_async::Read for SerialPort {
+    async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let mut guard = self.fd.readable().await?;
+            if let Ok(result) = guard.try_io(|fd| fd.get_ref().read(buf)) {
+                return result;
+            }
+        }
+    }
+}
+
+impl embedded_io_async::Write for SerialPort {
+    async fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        loop {
+            let mut guard = self.fd.writable().await?;
+            if let Ok(result) = guard.try_io(|fd| fd.get_ref().write(buf)) {
+                return result;
+            }
+        }
+    }
+
+    async fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl embedded_io::ReadReady for SerialPort {
+    fn read_ready(&mut self) -> io::Result<bool> {
+        let mut fds = [PollFd::new(self.fd.get_ref().as_fd(), PollFlags::POLLIN)];
+        Ok(poll(&mut fds, PollTimeout::ZERO)? > 0)
+    }
+}
@@ -0,0 +1,138 @@
+//! Projector on a `embedded_io_async` port.
+
+use embedded_io_async::{Read, ReadReady, Write};
+
+use crate::command::{CommandPayload, ProjectorCommand};
+use crate::error::command_name;
+use crate::frame::{self, FrameDecoder, MAX_PAYLOAD_LEN};
+use crate::query::ProjectorQuery;
+use crate::ProjectorError;
+
+/// PT-AH1000E Projector Control via RS232
+pub struct Projector<P> {
+    port: P,
+    decoder: FrameDecoder,
+    /// Last payload sent, errors while receiving are reported against it.
+    last_command: CommandPayload,
+}
+
+impl<P: Read + Write> Projector<P> {
+    /// Create a new Projector instance with the given port
+    pub fn new(port: P) -> Self {
+        Self {
+            port,
+            decoder: FrameDecoder::new(),
+            last_command: CommandPayload::new(),
+        }
+    }
+
+    /// Give the port back.
+    pub fn release(self) -> P {
+        self.port
+    }
+
+    /// Send `data` wrapped in STX/ETX.
+    pub async fn send(&mut self, data: &[u8]) -> Result<(), ProjectorError> {
+        self.last_command = command_name(data);
+
+        let frame = frame::encode(data).map_err(|error| ProjectorError::Framing {
+            command: self.last_command.clone(),
+            error,
+        })?;
+
+        let result = match self.port.write_all(&frame).await {
+            Ok(()) => self.port.flush().await,
+            Err(e) => Err(e),
+        };
+        result.map_err(|_| ProjectorError::WriteError {
+            command: self.last_command.clone(),
+        })
+    }
+
+    /// Read one frame and copy its payload (without STX/ETX) into `buffer`.
+    pub async fn receive(&mut self, buffer: &mut [u8]) -> Result<usize, ProjectorError> {
+        self.decoder.reset();
+
+        let mut byte = [0u8; 1];
+        loop {
+            match self.port.read(&mut byte).await {
+                Ok(0) | Err(_) => {
+                    return Err(ProjectorError::ReadError {
+                        command: self.last_command.clone(),
+                    })
+                }
+                Ok(_) => {}
+            }
+
+            match self.decoder.push(byte[0]) {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(error) => {
+                    return Err(ProjectorError::Framing {
+                        command: self.last_command.clone(),
+                        error,
+                    })
+                }
+            }
+        }
+
+        crate::copy_payload(&self.last_command, self.decoder.payload(), buffer)
+    }
+
+    /// Send `command` and wait for the projector to acknowledge it.
+    pub async fn execute(&mut self, command: ProjectorCommand) -> Result<(), ProjectorError> {
+        let payload = command.encode();
+        self.send(payload.as_bytes()).await?;
+
+        let mut buffer = [0u8; MAX_PAYLOAD_LEN];
+        let len = self.receive(&mut buffer).await?;
+        crate::check_ack(&command, &payload, &buffer[..len])
+    }
+
+    /// Send `query` and parse the reply.
+    pub async fn query<Q: ProjectorQuery>(
+        &mut self,
+        query: Q,
+    ) -> Result<Q::Response, ProjectorError> {
+        let payload = query.encode();
+        self.send(payload.as_bytes()).await?;
+
+        let mut buffer = [0u8; MAX_PAYLOAD_LEN];
+        let len = self.receive(&mut buffer).await?;
+        crate::parse_reply(&query, &payload, &buffer[..len])
+    }
+}
+
+impl<P: Read + Write + ReadReady> Projector<P> {
+    /// Throw away everything already received, e.g. the late reply to a
+    /// transaction that timed out, so it is not taken as the next reply.
+    ///
+    /// Returns the number of bytes discarded.
+    pub async fn drain(&mut self) -> Result<usize, ProjectorError> {
+        self.decoder.reset();
+
+        let mut discarded = 0;
+        let mut buffer = [0u8; 16];
+        loop {
+            let ready = self
+                .port
+                .read_ready()
+                .map_err(|_| ProjectorError::ReadError {
+                    command: self.last_command.clone(),
+                })?;
+            if !ready {
+                return Ok(discarded);
+            }
+
+            match self.port.read(&mut buffer).await {
+                Ok(0) => return Ok(discarded),
+                Ok(n) => discarded += n,
+                Err(_) => {
+                    return Err(ProjectorError::ReadError {
+                        command: self.last_command.clone(),
+                    })
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Projector on a blocking `embedded_io` port.
+
+use embedded_io::{Read, Write};
+
+use crate::command::{CommandPayload, ProjectorCommand};
+use crate::error::command_name;
+use crate::frame::{self, FrameDecoder, MAX_PAYLOAD_LEN};
+use crate::query::ProjectorQuery;
+use crate::ProjectorError;
+
+/// PT-AH1000E Projector Control via RS232
+pub struct Projector<P> {
+    port: P,
+    decoder: FrameDecoder,
+    /// Last payload sent, errors while receiving are reported against it.
+    last_command: CommandPayload,
+}
+
+impl<P: Read + Write> Projector<P> {
+    /// Create a new Projector instance with the given port
+    pub fn new(port: P) -> Self {
+        Self {
+            port,
+            decoder: FrameDecoder::new(),
+            last_command: CommandPayload::new(),
+        }
+    }
+
+    /// Give the port back.
+    pub fn release(self) -> P {
+        self.port
+    }
+
+    /// Send `data` wrapped in STX/ETX.
+    pub fn send(&mut self, data: &[u8]) -> Result<(), ProjectorError> {
+        self.last_command = command_name(data);
+
+        let frame = frame::encode(data).map_err(|error| ProjectorError::Framing {
+            command: self.last_command.clone(),
+            error,
+        })?;
+
+        self.port
+            .write_all(&frame)
+            .and_then(|_| self.port.flush())
+            .map_err(|_| ProjectorError::WriteError {
+                command: self.last_command.clone(),
+            })
+    }
+
+    /// Read one frame and copy its payload (without STX/ETX) into `buffer`.
+    pub fn receive(&mut self, buffer: &mut [u8]) -> Result<usize, ProjectorError> {
+        self.decoder.reset();
+
+        let mut byte = [0u8; 1];
+        loop {
+            match self.port.read(&mut byte) {
+                Ok(0) | Err(_) => {
+                    return Err(ProjectorError::ReadError {
+                        command: self.last_command.clone(),
+                    })
+                }
+                Ok(_) => {}
+            }
+
+            match self.decoder.push(byte[0]) {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(error) => {
+                    return Err(ProjectorError::Framing {
+                        command: self.last_command.clone(),
+                        error,
+                    })
+                }
+            }
+        }
+
+        crate::copy_payload(&self.last_command, self.decoder.payload(), buffer)
+    }
+
+    /// Send `command` and wait for the projector to acknowledge it.
+    pub fn execute(&mut self, command: ProjectorCommand) -> Result<(), ProjectorError> {
+        let payload = command.encode();
+        self.send(payload.as_bytes())?;
+
+        let mut buffer = [0u8; MAX_PAYLOAD_LEN];
+        let len = self.receive(&mut buffer)?;
+        crate::check_ack(&command, &payload, &buffer[..len])
+    }
+
+    /// Send `query` and parse the reply.
+    pub fn query<Q: ProjectorQuery>(&mut self, query: Q) -> Result<Q::Response, ProjectorError> {
+        let payload = query.encode();
+        self.send(payload.as_bytes())?;
+
+        let mut buffer = [0u8; MAX_PAYLOAD_LEN];
+        let len = self.receive(&mut buffer)?;
+        crate::parse_reply(&query, &payload, &buffer[..len])
+    }
+}
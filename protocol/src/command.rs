@@ -0,0 +1,281 @@
+//! Control commands understood by the PT-AH1000E.
+
+use core::fmt::Write;
+use core::str::FromStr;
+
+use heapless::String;
+
+/// Longest encoded command payload.
+pub const MAX_COMMAND_LEN: usize = 16;
+
+/// Encoded command payload, without STX/ETX.
+pub type CommandPayload = String<MAX_COMMAND_LEN>;
+
+/// Declares a parameter enum together with its wire code and a
+/// human-readable name used by MQTT/HTTP front-ends.
+macro_rules! code_enum {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident {
+            $($(#[$vmeta:meta])* $variant:ident = ($code:literal, $label:literal),)*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+        pub enum $name {
+            $($(#[$vmeta])* $variant,)*
+        }
+
+        impl $name {
+            /// Every value, in declaration order.
+            pub const ALL: &'static [Self] = &[$(Self::$variant,)*];
+
+            /// Code used on the wire.
+            pub const fn code(&self) -> &'static str {
+                match self {
+                    $(Self::$variant => $code,)*
+                }
+            }
+
+            /// Name used in front-ends (MQTT payloads, Home Assistant options).
+            pub const fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$variant => $label,)*
+                }
+            }
+
+            /// Look up a value by its wire code.
+            pub fn from_code(code: &str) -> Option<Self> {
+                match code {
+                    $($code => Some(Self::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = CommandError;
+
+            /// Parse a front-end name, ignoring ASCII case.
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Self::ALL
+                    .iter()
+                    .find(|v| v.name().eq_ignore_ascii_case(s))
+                    .copied()
+                    .ok_or(CommandError::UnknownValue)
+            }
+        }
+    };
+}
+
+pub(crate) use code_enum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum CommandError {
+    /// Numeric parameter outside of the range the projector accepts.
+    OutOfRange,
+    /// Name does not match any known value.
+    UnknownValue,
+}
+
+code_enum! {
+    /// Input terminal.
+    pub enum Input {
+        Hdmi1 = ("HD1", "HDMI1"),
+        Hdmi2 = ("HD2", "HDMI2"),
+        Computer1 = ("RG1", "COMPUTER1"),
+        Computer2 = ("RG2", "COMPUTER2"),
+        Video = ("VID", "VIDEO"),
+        DigitalLink = ("DL1", "DIGITAL LINK"),
+        Network = ("NWP", "NETWORK"),
+        MemoryViewer = ("MV1", "MEMORY VIEWER"),
+    }
+}
+
+code_enum! {
+    pub enum PictureMode {
+        Dynamic = ("DYN", "DYNAMIC"),
+        Standard = ("STD", "STANDARD"),
+        Natural = ("NAT", "NATURAL"),
+        Cinema = ("CIN", "CINEMA"),
+        Graphic = ("GRA", "GRAPHIC"),
+        Blackboard = ("BBD", "BLACKBOARD"),
+        Whiteboard = ("WBD", "WHITEBOARD"),
+        Dicom = ("DIC", "DICOM SIM."),
+    }
+}
+
+code_enum! {
+    pub enum Aspect {
+        Auto = ("0", "AUTO"),
+        Default = ("1", "DEFAULT"),
+        Wide = ("2", "16:9"),
+        Native = ("5", "NATIVE"),
+        Full = ("6", "FULL"),
+        HorizontalFit = ("9", "H-FIT"),
+        VerticalFit = ("10", "V-FIT"),
+    }
+}
+
+code_enum! {
+    pub enum TestPattern {
+        Off = ("00", "OFF"),
+        White = ("01", "WHITE"),
+        Yellow = ("02", "YELLOW"),
+        Cyan = ("03", "CYAN"),
+        Green = ("04", "GREEN"),
+        Magenta = ("05", "MAGENTA"),
+        Red = ("06", "RED"),
+        Blue = ("07", "BLUE"),
+        Black = ("08", "BLACK"),
+        Window = ("09", "WINDOW"),
+        ReversedWindow = ("10", "REVERSED WINDOW"),
+        ColorBarVertical = ("21", "COLOR BAR V"),
+        ColorBarHorizontal = ("22", "COLOR BAR H"),
+        CrossHatch = ("51", "CROSS HATCH"),
+    }
+}
+
+/// Audio volume, `0..=63`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Volume(u8);
+
+impl Volume {
+    pub const MAX: u8 = 63;
+
+    pub const fn new(value: u8) -> Result<Self, CommandError> {
+        if value > Self::MAX {
+            return Err(CommandError::OutOfRange);
+        }
+        Ok(Self(value))
+    }
+
+    pub const fn get(&self) -> u8 {
+        self.0
+    }
+}
+
+/// Keystone correction, `-127..=127`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Keystone(i8);
+
+impl Keystone {
+    pub const MAX: i8 = 127;
+
+    pub const fn new(value: i8) -> Result<Self, CommandError> {
+        if value < -Self::MAX {
+            return Err(CommandError::OutOfRange);
+        }
+        Ok(Self(value))
+    }
+
+    pub const fn get(&self) -> i8 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum KeystoneAxis {
+    Vertical,
+    Horizontal,
+}
+
+/// Menu navigation keys of the remote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum MenuKey {
+    Menu,
+    Enter,
+    Back,
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// A control command for the PT-AH1000E.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProjectorCommand {
+    PowerOn,
+    /// Switch to standby.
+    PowerOff,
+    SelectInput(Input),
+    /// Shutter (AV mute) closed when `true`.
+    Shutter(bool),
+    Freeze(bool),
+    PictureMode(PictureMode),
+    Aspect(Aspect),
+    Volume(Volume),
+    Keystone {
+        axis: KeystoneAxis,
+        value: Keystone,
+    },
+    Menu(MenuKey),
+    TestPattern(TestPattern),
+}
+
+impl ProjectorCommand {
+    /// The three-letter command code.
+    pub const fn code(&self) -> &'static str {
+        match self {
+            Self::PowerOn => "PON",
+            Self::PowerOff => "POF",
+            Self::SelectInput(_) => "IIS",
+            Self::Shutter(_) => "OSH",
+            Self::Freeze(_) => "OFZ",
+            Self::PictureMode(_) => "VPM",
+            Self::Aspect(_) => "VSE",
+            Self::Volume(_) => "AVL",
+            Self::Keystone {
+                axis: KeystoneAxis::Vertical,
+                ..
+            } => "KSV",
+            Self::Keystone {
+                axis: KeystoneAxis::Horizontal,
+                ..
+            } => "KSH",
+            Self::Menu(MenuKey::Menu) => "OMN",
+            Self::Menu(MenuKey::Enter) => "OEN",
+            Self::Menu(MenuKey::Back) => "OBK",
+            Self::Menu(MenuKey::Up) => "OCU",
+            Self::Menu(MenuKey::Down) => "OCD",
+            Self::Menu(MenuKey::Left) => "OCL",
+            Self::Menu(MenuKey::Right) => "OCR",
+            Self::TestPattern(_) => "OTS",
+        }
+    }
+
+    /// Wire format without STX/ETX, e.g. `IIS:HD1` or `AVL:020`.
+    pub fn encode(&self) -> CommandPayload {
+        let mut out = CommandPayload::new();
+        // every command fits into MAX_COMMAND_LEN
+        let _ = match self {
+            Self::PowerOn | Self::PowerOff | Self::Menu(_) => {
+                out.push_str(self.code()).map_err(|_| core::fmt::Error)
+            }
+            Self::SelectInput(input) => write!(out, "{}:{}", self.code(), input.code()),
+            Self::Shutter(on) | Self::Freeze(on) => {
+                write!(out, "{}:{}", self.code(), u8::from(*on))
+            }
+            Self::PictureMode(mode) => write!(out, "{}:{}", self.code(), mode.code()),
+            Self::Aspect(aspect) => write!(out, "{}:{}", self.code(), aspect.code()),
+            Self::Volume(volume) => write!(out, "{}:{:03}", self.code(), volume.get()),
+            Self::Keystone { value, .. } => write!(out, "{}:{:+04}", self.code(), value.get()),
+            Self::TestPattern(pattern) => write!(out, "{}:{}", self.code(), pattern.code()),
+        };
+        out
+    }
+
+    /// Whether `reply` is the projector acknowledging this command.
+    ///
+    /// The projector echoes the command code (and usually the parameter).
+    pub fn is_ack(&self, reply: &[u8]) -> bool {
+        reply.starts_with(self.code().as_bytes())
+    }
+}
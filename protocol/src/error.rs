@@ -0,0 +1,126 @@
+use core::fmt;
+
+use crate::command::CommandPayload;
+use crate::frame::FrameError;
+use crate::query::ParseError;
+
+/// Everything that can go wrong talking to the projector.
+///
+/// Each variant carries the payload of the command (or query) it happened
+/// for, truncated to [`crate::command::MAX_COMMAND_LEN`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProjectorError {
+    /// Writing to the port failed.
+    WriteError { command: CommandPayload },
+    /// Reading from the port failed or the port was closed.
+    ReadError { command: CommandPayload },
+    /// No complete reply before the deadline.
+    Timeout { command: CommandPayload },
+    /// Reply was not framed in STX/ETX or too long.
+    Framing {
+        command: CommandPayload,
+        error: FrameError,
+    },
+    /// Reply arrived but could not be parsed.
+    ParseError {
+        command: CommandPayload,
+        error: ParseError,
+    },
+    /// `ER401`: the command cannot be executed in the current state, e.g.
+    /// during lamp warm-up or cool-down.
+    NotExecutable { command: CommandPayload },
+    /// `ER402`: a parameter is out of range.
+    InvalidParameter { command: CommandPayload },
+    /// `???`: the projector does not know the command.
+    UnknownCommand { command: CommandPayload },
+    /// Any other `ERnnn` reply.
+    ErrorReply { command: CommandPayload, code: u16 },
+    /// The projector is warming up or cooling down, the command was not sent.
+    Busy { command: CommandPayload },
+    /// A raw command blocked by the allow/deny lists, it was not sent.
+    Forbidden { command: CommandPayload },
+}
+
+impl ProjectorError {
+    /// Payload of the command this error belongs to.
+    pub fn command(&self) -> &str {
+        match self {
+            Self::WriteError { command }
+            | Self::ReadError { command }
+            | Self::Timeout { command }
+            | Self::Framing { command, .. }
+            | Self::ParseError { command, .. }
+            | Self::NotExecutable { command }
+            | Self::InvalidParameter { command }
+            | Self::UnknownCommand { command }
+            | Self::ErrorReply { command, .. }
+            | Self::Busy { command }
+            | Self::Forbidden { command } => command,
+        }
+    }
+
+    /// The error came from the projector rather than from the link.
+    pub const fn is_error_reply(&self) -> bool {
+        matches!(
+            self,
+            Self::NotExecutable { .. }
+                | Self::InvalidParameter { .. }
+                | Self::UnknownCommand { .. }
+                | Self::ErrorReply { .. }
+        )
+    }
+
+    /// Decode a protocol error reply. Returns `None` if `reply` is not one.
+    pub fn from_reply(command: &[u8], reply: &[u8]) -> Option<Self> {
+        let command = command_name(command);
+        match reply {
+            b"ER401" => Some(Self::NotExecutable { command }),
+            b"ER402" => Some(Self::InvalidParameter { command }),
+            b"???" => Some(Self::UnknownCommand { command }),
+            [b'E', b'R', code @ ..]
+                if !code.is_empty() && code.len() <= 4 && code.iter().all(u8::is_ascii_digit) =>
+            {
+                let code = code
+                    .iter()
+                    .fold(0u16, |acc, d| acc * 10 + u16::from(d - b'0'));
+                Some(Self::ErrorReply { command, code })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ProjectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.command())?;
+        match self {
+            Self::WriteError { .. } => f.write_str("write failed"),
+            Self::ReadError { .. } => f.write_str("read failed"),
+            Self::Timeout { .. } => f.write_str("no reply"),
+            Self::Framing { error, .. } => write!(f, "bad framing ({:?})", error),
+            Self::ParseError { error, .. } => write!(f, "unexpected reply ({:?})", error),
+            Self::NotExecutable { .. } => f.write_str("cannot be executed now (ER401)"),
+            Self::InvalidParameter { .. } => f.write_str("invalid parameter (ER402)"),
+            Self::UnknownCommand { .. } => f.write_str("unknown command"),
+            Self::ErrorReply { code, .. } => write!(f, "error ER{:03}", code),
+            Self::Busy { .. } => f.write_str("projector busy"),
+            Self::Forbidden { .. } => f.write_str("not allowed"),
+        }
+    }
+}
+
+/// Printable copy of a command payload for error reports.
+pub fn command_name(payload: &[u8]) -> CommandPayload {
+    let mut name = CommandPayload::new();
+    for b in payload.iter().take(name.capacity()) {
+        let c = if b.is_ascii_graphic() || *b == b' ' {
+            char::from(*b)
+        } else {
+            '?'
+        };
+        // capacity bounded by take()
+        name.push(c).ok();
+    }
+    name
+}
@@ -0,0 +1,117 @@
+//! STX/ETX framing.
+
+use heapless::Vec;
+
+/// Start of a frame.
+pub const STX: u8 = 0x02;
+/// End of a frame.
+pub const ETX: u8 = 0x03;
+
+/// Longest payload (without STX/ETX) we send or accept.
+///
+/// Panasonic replies are at most a few dozen characters (model name, firmware
+/// version), commands are shorter.
+pub const MAX_PAYLOAD_LEN: usize = 64;
+
+/// A payload wrapped in STX/ETX, ready to be written to the port.
+pub type Frame = Vec<u8, { MAX_PAYLOAD_LEN + 2 }>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FrameError {
+    /// Payload does not fit into [`MAX_PAYLOAD_LEN`] (or the caller's buffer).
+    Overflow,
+    /// A byte other than STX arrived outside of a frame.
+    MissingStx,
+    /// A new STX arrived before the current frame was terminated.
+    MissingEtx,
+    /// The payload contains STX or ETX, which cannot be sent inside a frame.
+    InvalidByte,
+}
+
+/// Wrap `payload` in STX/ETX.
+pub fn encode(payload: &[u8]) -> Result<Frame, FrameError> {
+    if payload.len() > MAX_PAYLOAD_LEN {
+        return Err(FrameError::Overflow);
+    }
+    if payload.iter().any(|b| *b == STX || *b == ETX) {
+        return Err(FrameError::InvalidByte);
+    }
+
+    let mut frame = Frame::new();
+    // capacity checked above
+    frame.push(STX).ok();
+    frame.extend_from_slice(payload).ok();
+    frame.push(ETX).ok();
+    Ok(frame)
+}
+
+/// Byte-at-a-time frame decoder.
+///
+/// Feed every received byte into [`FrameDecoder::push`]; once it returns
+/// `Ok(true)` the payload of the complete frame is available through
+/// [`FrameDecoder::payload`] until the next push.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    payload: Vec<u8, MAX_PAYLOAD_LEN>,
+    in_frame: bool,
+    complete: bool,
+}
+
+impl FrameDecoder {
+    pub const fn new() -> Self {
+        Self {
+            payload: Vec::new(),
+            in_frame: false,
+            complete: false,
+        }
+    }
+
+    /// Discard any partially received frame.
+    pub fn reset(&mut self) {
+        self.payload.clear();
+        self.in_frame = false;
+        self.complete = false;
+    }
+
+    /// Feed one byte. Returns `Ok(true)` when it terminated a frame.
+    ///
+    /// After an error the decoder is reset (or, for [`FrameError::MissingEtx`],
+    /// already inside the new frame), so decoding can simply continue.
+    pub fn push(&mut self, byte: u8) -> Result<bool, FrameError> {
+        if self.complete {
+            self.reset();
+        }
+
+        match (self.in_frame, byte) {
+            (false, STX) => {
+                self.in_frame = true;
+                Ok(false)
+            }
+            (false, _) => Err(FrameError::MissingStx),
+            (true, STX) => {
+                // start over with the new frame
+                self.payload.clear();
+                Err(FrameError::MissingEtx)
+            }
+            (true, ETX) => {
+                self.in_frame = false;
+                self.complete = true;
+                Ok(true)
+            }
+            (true, _) => self.payload.push(byte).map(|_| false).map_err(|_| {
+                self.reset();
+                FrameError::Overflow
+            }),
+        }
+    }
+
+    /// Payload of the last complete frame, empty if there is none.
+    pub fn payload(&self) -> &[u8] {
+        if self.complete {
+            &self.payload
+        } else {
+            &[]
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Panasonic PT-AH1000E RS232 control protocol.
+//!
+//! Every message on the wire is an ASCII payload wrapped in `STX` (0x02) and
+//! `ETX` (0x03). This crate does the framing and reply decoding on top of any
+//! `embedded_io` (blocking) or `embedded_io_async` port, so it can be tested on
+//! the host against an in-memory port and used on the ESP32 with the UART.
+#![no_std]
+
+pub mod asynch;
+pub mod blocking;
+pub mod command;
+pub mod error;
+pub mod frame;
+pub mod power;
+pub mod query;
+
+pub use command::ProjectorCommand;
+pub use error::ProjectorError;
+pub use query::ProjectorQuery;
+
+use command::CommandPayload;
+use frame::FrameError;
+use query::ParseError;
+
+/// Copy a received payload into the caller's buffer.
+fn copy_payload(
+    command: &CommandPayload,
+    payload: &[u8],
+    buffer: &mut [u8],
+) -> Result<usize, ProjectorError> {
+    let dst = buffer
+        .get_mut(..payload.len())
+        .ok_or_else(|| ProjectorError::Framing {
+            command: command.clone(),
+            error: FrameError::Overflow,
+        })?;
+    dst.copy_from_slice(payload);
+    Ok(payload.len())
+}
+
+/// Check that `reply` acknowledges `command`.
+fn check_ack(
+    command: &ProjectorCommand,
+    sent: &CommandPayload,
+    reply: &[u8],
+) -> Result<(), ProjectorError> {
+    if let Some(e) = ProjectorError::from_reply(sent.as_bytes(), reply) {
+        return Err(e);
+    }
+    if command.is_ack(reply) {
+        Ok(())
+    } else {
+        Err(ProjectorError::ParseError {
+            command: sent.clone(),
+            error: ParseError::UnexpectedReply,
+        })
+    }
+}
+
+/// Parse the reply to `query`, decoding error replies first.
+fn parse_reply<Q: ProjectorQuery>(
+    query: &Q,
+    sent: &CommandPayload,
+    reply: &[u8],
+) -> Result<Q::Response, ProjectorError> {
+    if let Some(e) = ProjectorError::from_reply(sent.as_bytes(), reply) {
+        return Err(e);
+    }
+    query
+        .parse(reply)
+        .map_err(|error| ProjectorError::ParseError {
+            command: sent.clone(),
+            error,
+        })
+}
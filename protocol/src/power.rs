@@ -0,0 +1,211 @@
+//! Power lifecycle of the projector.
+//!
+//! The PT-AH1000E answers `ER401` to almost everything while the lamp warms
+//! up or cools down, and simply ignores a power on during cool-down. The
+//! [`PowerStateMachine`] follows the polled power state and decides, before a
+//! command goes out, whether it can be sent now, has to wait for the current
+//! transition to finish, or makes no sense at all.
+
+use crate::query::{ErrorStatus, PowerState};
+use crate::{ProjectorCommand, ProjectorError};
+
+/// Where the projector is in its power lifecycle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerPhase {
+    Off,
+    Warming,
+    On,
+    Cooling,
+    /// In standby because of an error (lamp failure, overheating, ...).
+    StandbyError,
+}
+
+impl PowerPhase {
+    /// Every phase, in declaration order.
+    pub const ALL: &'static [Self] = &[
+        Self::Off,
+        Self::Warming,
+        Self::On,
+        Self::Cooling,
+        Self::StandbyError,
+    ];
+
+    pub fn from_status(power: PowerState, errors: Option<ErrorStatus>) -> Self {
+        match power {
+            PowerState::Standby if errors.is_some_and(|e| e.has_error()) => Self::StandbyError,
+            PowerState::Standby => Self::Off,
+            PowerState::Warming => Self::Warming,
+            PowerState::On => Self::On,
+            PowerState::Cooling => Self::Cooling,
+        }
+    }
+
+    /// Name used in front-ends (MQTT payloads, Home Assistant options).
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Warming => "warming",
+            Self::On => "on",
+            Self::Cooling => "cooling",
+            Self::StandbyError => "error",
+        }
+    }
+
+    /// Lamp is warming up or cooling down.
+    pub const fn is_transition(&self) -> bool {
+        matches!(self, Self::Warming | Self::Cooling)
+    }
+}
+
+/// What to do with a command, see [`PowerStateMachine::submit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Decision {
+    /// Send it now.
+    Send,
+    /// The projector is already where the command would take it.
+    AlreadyDone,
+    /// Held back until the current transition is over; it is handed out again
+    /// by [`PowerStateMachine::observe`], or dropped if the transition ends in
+    /// standby error.
+    Deferred,
+    /// Cannot be executed in the current phase.
+    Rejected(ProjectorError),
+}
+
+#[derive(Debug, Default)]
+pub struct PowerStateMachine {
+    phase: Option<PowerPhase>,
+    errors: Option<ErrorStatus>,
+    deferred: Option<ProjectorCommand>,
+}
+
+impl PowerStateMachine {
+    pub const fn new() -> Self {
+        Self {
+            phase: None,
+            errors: None,
+            deferred: None,
+        }
+    }
+
+    /// Current phase, `None` until the first power state was observed.
+    pub fn phase(&self) -> Option<PowerPhase> {
+        self.phase
+    }
+
+    /// Command waiting for the current transition to end.
+    pub fn deferred(&self) -> Option<ProjectorCommand> {
+        self.deferred
+    }
+
+    /// Record a polled error register; it tells standby from standby error.
+    /// Returns the error a deferred command failed with, as for
+    /// [`observe`](Self::observe).
+    pub fn observe_errors(&mut self, errors: ErrorStatus) -> Option<ProjectorError> {
+        self.errors = Some(errors);
+        if let Some(PowerPhase::Off | PowerPhase::StandbyError) = self.phase {
+            self.phase = Some(PowerPhase::from_status(PowerState::Standby, self.errors));
+        }
+        self.drop_on_error()
+    }
+
+    /// Record a polled power state. Returns the deferred command if it can be
+    /// sent now, or the error it failed with if it never will be: a power on
+    /// that ends up in standby error would otherwise go out whenever the
+    /// error clears, long after anybody asked for it.
+    pub fn observe(
+        &mut self,
+        power: PowerState,
+    ) -> Option<Result<ProjectorCommand, ProjectorError>> {
+        let phase = PowerPhase::from_status(power, self.errors);
+        self.phase = Some(phase);
+        if let Some(e) = self.drop_on_error() {
+            return Some(Err(e));
+        }
+
+        let ready = match self.deferred? {
+            ProjectorCommand::PowerOn => phase == PowerPhase::Off,
+            ProjectorCommand::PowerOff => phase == PowerPhase::On,
+            _ => !phase.is_transition(),
+        };
+        if ready {
+            self.deferred.take().map(Ok)
+        } else {
+            None
+        }
+    }
+
+    /// Decide what to do with `command` in the current phase.
+    pub fn submit(&mut self, command: ProjectorCommand) -> Decision {
+        let Some(phase) = self.phase else {
+            // nothing polled yet, let the projector sort it out
+            return Decision::Send;
+        };
+
+        match (command, phase) {
+            (ProjectorCommand::PowerOn, PowerPhase::Off) => Decision::Send,
+            (ProjectorCommand::PowerOn, PowerPhase::Warming | PowerPhase::On) => {
+                self.cancel(ProjectorCommand::PowerOff);
+                Decision::AlreadyDone
+            }
+            (ProjectorCommand::PowerOn, PowerPhase::Cooling) => self.defer(command),
+
+            (ProjectorCommand::PowerOff, PowerPhase::On) => Decision::Send,
+            (ProjectorCommand::PowerOff, PowerPhase::Warming) => self.defer(command),
+            (
+                ProjectorCommand::PowerOff,
+                PowerPhase::Off | PowerPhase::Cooling | PowerPhase::StandbyError,
+            ) => {
+                self.cancel(ProjectorCommand::PowerOn);
+                Decision::AlreadyDone
+            }
+
+            (_, PowerPhase::StandbyError) => Decision::Rejected(ProjectorError::NotExecutable {
+                command: command.encode(),
+            }),
+            (_, PowerPhase::Warming | PowerPhase::Cooling) => {
+                Decision::Rejected(ProjectorError::Busy {
+                    command: command.encode(),
+                })
+            }
+            (_, PowerPhase::Off | PowerPhase::On) => Decision::Send,
+        }
+    }
+
+    /// Record that `command` was acknowledged, so a command right after it is
+    /// judged against the phase it started rather than the last poll.
+    pub fn sent(&mut self, command: ProjectorCommand) {
+        self.phase = match (command, self.phase) {
+            (ProjectorCommand::PowerOn, Some(PowerPhase::Off)) => Some(PowerPhase::Warming),
+            (ProjectorCommand::PowerOff, Some(PowerPhase::On)) => Some(PowerPhase::Cooling),
+            (_, phase) => phase,
+        };
+    }
+
+    fn defer(&mut self, command: ProjectorCommand) -> Decision {
+        // only the latest power request counts
+        self.deferred = Some(command);
+        Decision::Deferred
+    }
+
+    /// In standby error a deferred power off is done, anything else fails.
+    fn drop_on_error(&mut self) -> Option<ProjectorError> {
+        if self.phase != Some(PowerPhase::StandbyError) {
+            return None;
+        }
+        match self.deferred.take()? {
+            ProjectorCommand::PowerOff => None,
+            command => Some(ProjectorError::NotExecutable {
+                command: command.encode(),
+            }),
+        }
+    }
+
+    fn cancel(&mut self, command: ProjectorCommand) {
+        if self.deferred == Some(command) {
+            self.deferred = None;
+        }
+    }
+}
@@ -0,0 +1,386 @@
+//! Status queries and their typed replies.
+
+use core::fmt::Write;
+use core::str::FromStr;
+
+use heapless::String;
+
+use crate::command::{code_enum, CommandError, CommandPayload, Input, Volume};
+use crate::frame::MAX_PAYLOAD_LEN;
+
+/// Free-form text reply (model name, firmware version).
+pub type Text = String<MAX_PAYLOAD_LEN>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ParseError {
+    /// Reply is not ASCII.
+    NotAscii,
+    /// Reply is not a number (or out of range for the value).
+    InvalidNumber,
+    /// Reply is well-formed but not a value we know.
+    UnknownValue,
+    /// Reply does not belong to the command or query that was sent.
+    UnexpectedReply,
+}
+
+/// A query that can be sent to the projector, with the type of its reply.
+pub trait ProjectorQuery {
+    type Response;
+
+    /// Wire format without STX/ETX, e.g. `QPW` or `Q$L:1`.
+    fn encode(&self) -> CommandPayload;
+
+    /// Parse the reply payload (without STX/ETX).
+    fn parse(&self, reply: &[u8]) -> Result<Self::Response, ParseError>;
+}
+
+fn ascii(reply: &[u8]) -> Result<&str, ParseError> {
+    if !reply.is_ascii() {
+        return Err(ParseError::NotAscii);
+    }
+    core::str::from_utf8(reply).map_err(|_| ParseError::NotAscii)
+}
+
+/// Decimal number, optionally signed. Unlike `str::parse` this insists on at
+/// least one digit and nothing else.
+fn number<T: FromStr>(reply: &[u8]) -> Result<T, ParseError> {
+    let text = ascii(reply)?;
+    let digits = text.strip_prefix(['+', '-']).unwrap_or(text);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(ParseError::InvalidNumber);
+    }
+    text.parse().map_err(|_| ParseError::InvalidNumber)
+}
+
+fn flag(reply: &[u8]) -> Result<bool, ParseError> {
+    match reply {
+        b"0" => Ok(false),
+        b"1" => Ok(true),
+        _ => Err(ParseError::UnknownValue),
+    }
+}
+
+fn text(reply: &[u8]) -> Result<Text, ParseError> {
+    let mut out = Text::new();
+    out.push_str(ascii(reply)?.trim())
+        .map_err(|_| ParseError::UnknownValue)?;
+    Ok(out)
+}
+
+/// Query with a fixed code and no parameter.
+macro_rules! simple_query {
+    ($(#[$meta:meta])* $name:ident, $code:literal, $response:ty, $parse:expr) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+        pub struct $name;
+
+        impl ProjectorQuery for $name {
+            type Response = $response;
+
+            fn encode(&self) -> CommandPayload {
+                let mut out = CommandPayload::new();
+                out.push_str($code).ok();
+                out
+            }
+
+            fn parse(&self, reply: &[u8]) -> Result<Self::Response, ParseError> {
+                $parse(reply)
+            }
+        }
+    };
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum PowerState {
+    Standby,
+    On,
+    /// Lamp warming up after power on, commands are refused.
+    Warming,
+    /// Lamp cooling down after power off, commands are refused.
+    Cooling,
+}
+
+impl PowerState {
+    /// Lamp is lit or about to be.
+    pub const fn is_on(&self) -> bool {
+        matches!(self, Self::On | Self::Warming)
+    }
+}
+
+simple_query!(
+    /// `QPW`: power state.
+    PowerQuery,
+    "QPW",
+    PowerState,
+    |reply: &[u8]| match reply {
+        b"000" => Ok(PowerState::Standby),
+        b"001" => Ok(PowerState::On),
+        b"002" => Ok(PowerState::Warming),
+        b"003" => Ok(PowerState::Cooling),
+        _ => Err(ParseError::UnknownValue),
+    }
+);
+
+simple_query!(
+    /// `QIN`: selected input.
+    InputQuery,
+    "QIN",
+    Input,
+    |reply: &[u8]| Input::from_code(ascii(reply)?).ok_or(ParseError::UnknownValue)
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Lamp {
+    Lamp1,
+    Lamp2,
+}
+
+/// Operating hours of a lamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LampRuntime {
+    pub hours: u32,
+}
+
+/// `Q$L:n`: runtime of lamp `n`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LampRuntimeQuery(pub Lamp);
+
+impl ProjectorQuery for LampRuntimeQuery {
+    type Response = LampRuntime;
+
+    fn encode(&self) -> CommandPayload {
+        let n = match self.0 {
+            Lamp::Lamp1 => 1,
+            Lamp::Lamp2 => 2,
+        };
+        let mut out = CommandPayload::new();
+        write!(out, "Q$L:{}", n).ok();
+        out
+    }
+
+    fn parse(&self, reply: &[u8]) -> Result<Self::Response, ParseError> {
+        number(reply).map(|hours| LampRuntime { hours })
+    }
+}
+
+code_enum! {
+    pub enum LampMode {
+        Normal = ("0", "NORMAL"),
+        Eco = ("1", "ECO"),
+    }
+}
+
+simple_query!(
+    /// `QLM`: lamp power mode.
+    LampModeQuery,
+    "QLM",
+    LampMode,
+    |reply: &[u8]| LampMode::from_code(ascii(reply)?).ok_or(ParseError::UnknownValue)
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TemperatureSensor {
+    Intake,
+    Exhaust,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Temperature {
+    pub celsius: i16,
+}
+
+/// `QTM:n`: temperature at the intake (0) or exhaust (1) sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TemperatureQuery(pub TemperatureSensor);
+
+impl ProjectorQuery for TemperatureQuery {
+    type Response = Temperature;
+
+    fn encode(&self) -> CommandPayload {
+        let n = match self.0 {
+            TemperatureSensor::Intake => 0,
+            TemperatureSensor::Exhaust => 1,
+        };
+        let mut out = CommandPayload::new();
+        write!(out, "QTM:{}", n).ok();
+        out
+    }
+
+    fn parse(&self, reply: &[u8]) -> Result<Self::Response, ParseError> {
+        number(reply).map(|celsius| Temperature { celsius })
+    }
+}
+
+simple_query!(
+    /// `QSH`: shutter (AV mute), `true` when closed.
+    ShutterQuery,
+    "QSH",
+    bool,
+    flag
+);
+
+simple_query!(
+    /// `QFZ`: freeze, `true` when frozen.
+    FreezeQuery,
+    "QFZ",
+    bool,
+    flag
+);
+
+simple_query!(
+    /// `QAV`: audio volume.
+    VolumeQuery,
+    "QAV",
+    Volume,
+    |reply: &[u8]| Volume::new(number(reply)?).map_err(|_: CommandError| ParseError::InvalidNumber)
+);
+
+/// Error and warning register, reported as four hex digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ErrorStatus(pub u16);
+
+impl ErrorStatus {
+    pub const INTAKE_TEMPERATURE: u16 = 0x0001;
+    pub const EXHAUST_TEMPERATURE: u16 = 0x0002;
+    pub const LAMP_FAILURE: u16 = 0x0004;
+    pub const FAN: u16 = 0x0008;
+    pub const COVER_OPEN: u16 = 0x0010;
+    pub const POWER_SUPPLY: u16 = 0x0020;
+    /// Temperature is getting high, but the projector keeps running.
+    pub const TEMPERATURE_WARNING: u16 = 0x0100;
+    pub const LAMP_REPLACE: u16 = 0x0200;
+    pub const FILTER: u16 = 0x0400;
+
+    const ERRORS: u16 = Self::INTAKE_TEMPERATURE
+        | Self::EXHAUST_TEMPERATURE
+        | Self::LAMP_FAILURE
+        | Self::FAN
+        | Self::COVER_OPEN
+        | Self::POWER_SUPPLY;
+
+    pub const fn contains(&self, flag: u16) -> bool {
+        self.0 & flag == flag
+    }
+
+    /// Any flag that makes the projector shut down.
+    pub const fn has_error(&self) -> bool {
+        self.0 & Self::ERRORS != 0
+    }
+
+    pub const fn has_warning(&self) -> bool {
+        self.0 & !Self::ERRORS != 0
+    }
+}
+
+simple_query!(
+    /// `QER`: error and warning register.
+    ErrorStatusQuery,
+    "QER",
+    ErrorStatus,
+    |reply: &[u8]| {
+        let text = ascii(reply)?;
+        if text.len() != 4 {
+            return Err(ParseError::InvalidNumber);
+        }
+        u16::from_str_radix(text, 16)
+            .map(ErrorStatus)
+            .map_err(|_| ParseError::InvalidNumber)
+    }
+);
+
+simple_query!(
+    /// `QID`: model name, e.g. `PT-AH1000E`.
+    ModelNameQuery,
+    "QID",
+    Text,
+    text
+);
+
+simple_query!(
+    /// `QVR`: main firmware version.
+    FirmwareVersionQuery,
+    "QVR",
+    Text,
+    text
+);
+
+/// A query that can be passed around as a [`Query`] value and get its typed
+/// reply back out of the [`QueryResponse`].
+pub trait IntoQuery: ProjectorQuery + Into<Query> {
+    fn from_response(response: QueryResponse) -> Option<Self::Response>;
+}
+
+macro_rules! any_query {
+    ($($variant:ident($query:ty),)*) => {
+        /// Any of the queries above, e.g. for sending them through a channel.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+        pub enum Query {
+            $($variant($query),)*
+        }
+
+        /// Reply to a [`Query`].
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        #[cfg_attr(feature = "defmt", derive(defmt::Format))]
+        pub enum QueryResponse {
+            $($variant(<$query as ProjectorQuery>::Response),)*
+        }
+
+        impl ProjectorQuery for Query {
+            type Response = QueryResponse;
+
+            fn encode(&self) -> CommandPayload {
+                match self {
+                    $(Self::$variant(query) => query.encode(),)*
+                }
+            }
+
+            fn parse(&self, reply: &[u8]) -> Result<Self::Response, ParseError> {
+                match self {
+                    $(Self::$variant(query) => query.parse(reply).map(QueryResponse::$variant),)*
+                }
+            }
+        }
+
+        $(
+            impl From<$query> for Query {
+                fn from(query: $query) -> Self {
+                    Self::$variant(query)
+                }
+            }
+
+            impl IntoQuery for $query {
+                fn from_response(response: QueryResponse) -> Option<Self::Response> {
+                    match response {
+                        QueryResponse::$variant(response) => Some(response),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+any_query! {
+    Power(PowerQuery),
+    Input(InputQuery),
+    LampRuntime(LampRuntimeQuery),
+    LampMode(LampModeQuery),
+    Temperature(TemperatureQuery),
+    Shutter(ShutterQuery),
+    Freeze(FreezeQuery),
+    Volume(VolumeQuery),
+    ErrorStatus(ErrorStatusQuery),
+    ModelName(ModelNameQuery),
+    FirmwareVersion(FirmwareVersionQuery),
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::{cmd, framed, MockPort};
+use embassy_futures::block_on;
+use projector_protocol::command::MenuKey;
+use projector_protocol::frame::FrameError;
+use projector_protocol::query::{Lamp, LampRuntime, LampRuntimeQuery};
+use projector_protocol::{asynch::Projector, ProjectorCommand, ProjectorError};
+
+#[test]
+fn send_frames_payload() {
+    let mut projector = Projector::new(MockPort::new());
+    block_on(projector.send(b"POF")).unwrap();
+    assert_eq!(projector.release().written, framed(b"POF"));
+}
+
+#[test]
+fn execute_sends_command_and_reads_ack() {
+    let mut port = MockPort::new();
+    port.reply(b"OMN");
+    let mut projector = Projector::new(port);
+
+    block_on(projector.execute(ProjectorCommand::Menu(MenuKey::Menu))).unwrap();
+    assert_eq!(projector.release().written, framed(b"OMN"));
+}
+
+#[test]
+fn send_reports_write_error() {
+    let mut port = MockPort::new();
+    port.fail_writes = true;
+    let mut projector = Projector::new(port);
+    assert_eq!(
+        block_on(projector.send(b"PON")),
+        Err(ProjectorError::WriteError {
+            command: cmd("PON")
+        })
+    );
+}
+
+#[test]
+fn receive_strips_framing() {
+    let mut port = MockPort::new();
+    port.reply(b"OMN");
+    let mut projector = Projector::new(port);
+
+    let mut buf = [0u8; 16];
+    let len = block_on(projector.receive(&mut buf)).unwrap();
+    assert_eq!(&buf[..len], b"OMN");
+}
+
+#[test]
+fn receive_rejects_garbage_before_frame() {
+    let mut port = MockPort::new();
+    port.feed(b"x").reply(b"001");
+    let mut projector = Projector::new(port);
+
+    let mut buf = [0u8; 16];
+    assert_eq!(
+        block_on(projector.receive(&mut buf)),
+        Err(ProjectorError::Framing {
+            command: cmd(""),
+            error: FrameError::MissingStx,
+        })
+    );
+}
+
+#[test]
+fn query_parses_reply() {
+    let mut port = MockPort::new();
+    port.reply(b"0123");
+    let mut projector = Projector::new(port);
+
+    assert_eq!(
+        block_on(projector.query(LampRuntimeQuery(Lamp::Lamp1))),
+        Ok(LampRuntime { hours: 123 })
+    );
+    assert_eq!(projector.release().written, framed(b"Q$L:1"));
+}
+
+#[test]
+fn execute_decodes_error_replies() {
+    let mut port = MockPort::new();
+    port.reply(b"ER401");
+    let mut projector = Projector::new(port);
+
+    assert_eq!(
+        block_on(projector.execute(ProjectorCommand::PowerOff)),
+        Err(ProjectorError::NotExecutable {
+            command: cmd("POF")
+        })
+    );
+}
+
+#[test]
+fn drain_discards_stale_reply() {
+    let mut port = MockPort::new();
+    port.reply(b"PON").feed(b"\x02QP");
+    let mut projector = Projector::new(port);
+
+    assert_eq!(block_on(projector.drain()), Ok(8));
+
+    let mut port = projector.release();
+    assert_eq!(port.pending(), 0);
+
+    port.reply(b"001");
+    let mut projector = Projector::new(port);
+    assert_eq!(block_on(projector.drain()), Ok(5));
+    assert_eq!(block_on(projector.drain()), Ok(0));
+}
@@ -0,0 +1,198 @@
+mod common;
+
+use common::{cmd, framed, MockPort};
+use projector_protocol::command::{Input, Volume};
+use projector_protocol::frame::FrameError;
+use projector_protocol::query::{InputQuery, ParseError, PowerQuery, PowerState};
+use projector_protocol::{blocking::Projector, ProjectorCommand, ProjectorError};
+
+#[test]
+fn send_frames_payload() {
+    let mut projector = Projector::new(MockPort::new());
+    projector.send(b"PON").unwrap();
+    assert_eq!(projector.release().written, framed(b"PON"));
+}
+
+#[test]
+fn send_reports_write_error() {
+    let mut port = MockPort::new();
+    port.fail_writes = true;
+    let mut projector = Projector::new(port);
+    assert_eq!(
+        projector.send(b"PON"),
+        Err(ProjectorError::WriteError {
+            command: cmd("PON")
+        })
+    );
+}
+
+#[test]
+fn execute_sends_command_and_reads_ack() {
+    let mut port = MockPort::new();
+    port.reply(b"PON").reply(b"IIS:HD2");
+    let mut projector = Projector::new(port);
+
+    projector.execute(ProjectorCommand::PowerOn).unwrap();
+    projector
+        .execute(ProjectorCommand::SelectInput(Input::Hdmi2))
+        .unwrap();
+
+    let port = projector.release();
+    assert_eq!(port.written, [framed(b"PON"), framed(b"IIS:HD2")].concat());
+    assert_eq!(port.pending(), 0);
+}
+
+#[test]
+fn execute_rejects_reply_for_other_command() {
+    let mut port = MockPort::new();
+    port.reply(b"POF");
+    let mut projector = Projector::new(port);
+
+    assert_eq!(
+        projector.execute(ProjectorCommand::PowerOn),
+        Err(ProjectorError::ParseError {
+            command: cmd("PON"),
+            error: ParseError::UnexpectedReply,
+        })
+    );
+}
+
+#[test]
+fn receive_strips_framing() {
+    let mut port = MockPort::new();
+    port.reply(b"PON");
+    let mut projector = Projector::new(port);
+
+    let mut buf = [0u8; 16];
+    let len = projector.receive(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"PON");
+}
+
+#[test]
+fn receive_reads_only_one_frame() {
+    let mut port = MockPort::new();
+    port.reply(b"PON").reply(b"001");
+    let mut projector = Projector::new(port);
+
+    let mut buf = [0u8; 16];
+    let len = projector.receive(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"PON");
+    let len = projector.receive(&mut buf).unwrap();
+    assert_eq!(&buf[..len], b"001");
+}
+
+#[test]
+fn receive_fails_on_truncated_frame() {
+    let mut port = MockPort::new();
+    port.feed(b"\x02PO");
+    let mut projector = Projector::new(port);
+
+    let mut buf = [0u8; 16];
+    assert_eq!(
+        projector.receive(&mut buf),
+        Err(ProjectorError::ReadError { command: cmd("") })
+    );
+}
+
+#[test]
+fn receive_fails_when_buffer_too_small() {
+    let mut port = MockPort::new();
+    port.reply(b"PT-AH1000E");
+    let mut projector = Projector::new(port);
+
+    projector.send(b"QID").unwrap();
+    let mut buf = [0u8; 4];
+    assert_eq!(
+        projector.receive(&mut buf),
+        Err(ProjectorError::Framing {
+            command: cmd("QID"),
+            error: FrameError::Overflow,
+        })
+    );
+}
+
+#[test]
+fn query_parses_reply() {
+    let mut port = MockPort::new();
+    port.reply(b"001").reply(b"000");
+    let mut projector = Projector::new(port);
+
+    assert_eq!(projector.query(PowerQuery), Ok(PowerState::On));
+    assert_eq!(projector.query(PowerQuery), Ok(PowerState::Standby));
+    assert_eq!(
+        projector.release().written,
+        [framed(b"QPW"), framed(b"QPW")].concat()
+    );
+}
+
+#[test]
+fn query_reports_parse_error() {
+    let mut port = MockPort::new();
+    port.reply(b"XYZ");
+    let mut projector = Projector::new(port);
+
+    assert_eq!(
+        projector.query(InputQuery),
+        Err(ProjectorError::ParseError {
+            command: cmd("QIN"),
+            error: ParseError::UnknownValue,
+        })
+    );
+}
+
+#[test]
+fn execute_decodes_error_replies() {
+    let mut port = MockPort::new();
+    port.reply(b"ER401").reply(b"ER402").reply(b"???");
+    let mut projector = Projector::new(port);
+
+    assert_eq!(
+        projector.execute(ProjectorCommand::Freeze(true)),
+        Err(ProjectorError::NotExecutable {
+            command: cmd("OFZ:1")
+        })
+    );
+    assert_eq!(
+        projector.execute(ProjectorCommand::Volume(Volume::new(63).unwrap())),
+        Err(ProjectorError::InvalidParameter {
+            command: cmd("AVL:063")
+        })
+    );
+    assert_eq!(
+        projector.execute(ProjectorCommand::PowerOn),
+        Err(ProjectorError::UnknownCommand {
+            command: cmd("PON")
+        })
+    );
+}
+
+#[test]
+fn query_decodes_error_replies() {
+    let mut port = MockPort::new();
+    port.reply(b"ER401");
+    let mut projector = Projector::new(port);
+
+    assert_eq!(
+        projector.query(InputQuery),
+        Err(ProjectorError::NotExecutable {
+            command: cmd("QIN")
+        })
+    );
+}
+
+#[test]
+fn receive_reports_framing_errors() {
+    let mut port = MockPort::new();
+    port.feed(b"PON\x03");
+    let mut projector = Projector::new(port);
+
+    projector.send(b"PON").unwrap();
+    let mut buf = [0u8; 16];
+    assert_eq!(
+        projector.receive(&mut buf),
+        Err(ProjectorError::Framing {
+            command: cmd("PON"),
+            error: FrameError::MissingStx,
+        })
+    );
+}
@@ -0,0 +1,141 @@
+use projector_protocol::command::{
+    Aspect, CommandError, Input, Keystone, KeystoneAxis, MenuKey, PictureMode, TestPattern, Volume,
+};
+use projector_protocol::ProjectorCommand;
+
+fn encoded(command: ProjectorCommand) -> String {
+    command.encode().as_str().to_owned()
+}
+
+#[test]
+fn power() {
+    assert_eq!(encoded(ProjectorCommand::PowerOn), "PON");
+    assert_eq!(encoded(ProjectorCommand::PowerOff), "POF");
+}
+
+#[test]
+fn input_select() {
+    assert_eq!(
+        encoded(ProjectorCommand::SelectInput(Input::Hdmi1)),
+        "IIS:HD1"
+    );
+    assert_eq!(
+        encoded(ProjectorCommand::SelectInput(Input::Computer2)),
+        "IIS:RG2"
+    );
+}
+
+#[test]
+fn toggles() {
+    assert_eq!(encoded(ProjectorCommand::Shutter(true)), "OSH:1");
+    assert_eq!(encoded(ProjectorCommand::Shutter(false)), "OSH:0");
+    assert_eq!(encoded(ProjectorCommand::Freeze(true)), "OFZ:1");
+    assert_eq!(encoded(ProjectorCommand::Freeze(false)), "OFZ:0");
+}
+
+#[test]
+fn picture_and_aspect() {
+    assert_eq!(
+        encoded(ProjectorCommand::PictureMode(PictureMode::Cinema)),
+        "VPM:CIN"
+    );
+    assert_eq!(encoded(ProjectorCommand::Aspect(Aspect::Wide)), "VSE:2");
+    assert_eq!(
+        encoded(ProjectorCommand::Aspect(Aspect::VerticalFit)),
+        "VSE:10"
+    );
+}
+
+#[test]
+fn volume_is_zero_padded() {
+    let volume = |v| ProjectorCommand::Volume(Volume::new(v).unwrap());
+    assert_eq!(encoded(volume(0)), "AVL:000");
+    assert_eq!(encoded(volume(7)), "AVL:007");
+    assert_eq!(encoded(volume(63)), "AVL:063");
+}
+
+#[test]
+fn volume_range_is_validated() {
+    assert_eq!(Volume::new(64), Err(CommandError::OutOfRange));
+}
+
+#[test]
+fn keystone_is_signed() {
+    let keystone = |axis, v| ProjectorCommand::Keystone {
+        axis,
+        value: Keystone::new(v).unwrap(),
+    };
+    assert_eq!(encoded(keystone(KeystoneAxis::Vertical, 5)), "KSV:+005");
+    assert_eq!(encoded(keystone(KeystoneAxis::Horizontal, -12)), "KSH:-012");
+    assert_eq!(encoded(keystone(KeystoneAxis::Vertical, 0)), "KSV:+000");
+    assert_eq!(Keystone::new(i8::MIN), Err(CommandError::OutOfRange));
+}
+
+#[test]
+fn menu_navigation() {
+    let codes: Vec<_> = [
+        MenuKey::Menu,
+        MenuKey::Enter,
+        MenuKey::Back,
+        MenuKey::Up,
+        MenuKey::Down,
+        MenuKey::Left,
+        MenuKey::Right,
+    ]
+    .into_iter()
+    .map(|k| encoded(ProjectorCommand::Menu(k)))
+    .collect();
+    assert_eq!(codes, ["OMN", "OEN", "OBK", "OCU", "OCD", "OCL", "OCR"]);
+}
+
+#[test]
+fn test_pattern() {
+    assert_eq!(
+        encoded(ProjectorCommand::TestPattern(TestPattern::Off)),
+        "OTS:00"
+    );
+    assert_eq!(
+        encoded(ProjectorCommand::TestPattern(TestPattern::CrossHatch)),
+        "OTS:51"
+    );
+}
+
+#[test]
+fn longest_encoding_fits() {
+    let longest = ProjectorCommand::Keystone {
+        axis: KeystoneAxis::Horizontal,
+        value: Keystone::new(-Keystone::MAX).unwrap(),
+    };
+    assert_eq!(encoded(longest), "KSH:-127");
+}
+
+#[test]
+fn parameter_names_round_trip() {
+    for input in Input::ALL {
+        assert_eq!(input.name().parse::<Input>(), Ok(*input));
+        assert_eq!(Input::from_code(input.code()), Some(*input));
+    }
+    for mode in PictureMode::ALL {
+        assert_eq!(mode.name().parse::<PictureMode>(), Ok(*mode));
+    }
+    for aspect in Aspect::ALL {
+        assert_eq!(aspect.name().parse::<Aspect>(), Ok(*aspect));
+    }
+    for pattern in TestPattern::ALL {
+        assert_eq!(pattern.name().parse::<TestPattern>(), Ok(*pattern));
+    }
+}
+
+#[test]
+fn parameter_names_ignore_case() {
+    assert_eq!("hdmi1".parse::<Input>(), Ok(Input::Hdmi1));
+    assert_eq!("HDMI3".parse::<Input>(), Err(CommandError::UnknownValue));
+}
+
+#[test]
+fn ack_matches_command_code() {
+    let command = ProjectorCommand::SelectInput(Input::Video);
+    assert!(command.is_ack(b"IIS:VID"));
+    assert!(command.is_ack(b"IIS"));
+    assert!(!command.is_ack(b"ER401"));
+}
@@ -0,0 +1,113 @@
+//! In-memory serial port for driving the projector without hardware.
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+
+use projector_protocol::command::CommandPayload;
+use projector_protocol::frame::{ETX, STX};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MockError;
+
+impl embedded_io::Error for MockError {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        embedded_io::ErrorKind::Other
+    }
+}
+
+/// Bytes queued with [`MockPort::reply`] are handed out by `read`, everything
+/// written ends up in [`MockPort::written`]. An empty receive queue reads as
+/// end of stream.
+#[derive(Debug, Default)]
+pub struct MockPort {
+    rx: VecDeque<u8>,
+    pub written: Vec<u8>,
+    pub fail_writes: bool,
+}
+
+impl MockPort {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue raw bytes for the projector side to "send".
+    pub fn feed(&mut self, bytes: &[u8]) -> &mut Self {
+        self.rx.extend(bytes);
+        self
+    }
+
+    /// Queue a framed reply.
+    pub fn reply(&mut self, payload: &[u8]) -> &mut Self {
+        self.rx.push_back(STX);
+        self.rx.extend(payload);
+        self.rx.push_back(ETX);
+        self
+    }
+
+    pub fn pending(&self) -> usize {
+        self.rx.len()
+    }
+}
+
+impl embedded_io::ErrorType for MockPort {
+    type Error = MockError;
+}
+
+impl embedded_io::Read for MockPort {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let n = buf.len().min(self.rx.len());
+        for (dst, src) in buf.iter_mut().zip(self.rx.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl embedded_io::Write for MockPort {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if self.fail_writes {
+            return Err(MockError);
+        }
+        self.written.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl embedded_io::ReadReady for MockPort {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.rx.is_empty())
+    }
+}
+
+impl embedded_io_async::Read for MockPort {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        embedded_io::Read::read(self, buf)
+    }
+}
+
+impl embedded_io_async::Write for MockPort {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        embedded_io::Write::write(self, buf)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// `payload` wrapped in STX/ETX, for comparing against what was written.
+pub fn framed(payload: &[u8]) -> Vec<u8> {
+    let mut v = vec![STX];
+    v.extend_from_slice(payload);
+    v.push(ETX);
+    v
+}
+
+/// Command payload as carried by `ProjectorError`.
+pub fn cmd(payload: &str) -> CommandPayload {
+    payload.try_into().unwrap()
+}
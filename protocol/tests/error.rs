@@ -0,0 +1,105 @@
+mod common;
+
+use common::cmd;
+use projector_protocol::error::command_name;
+use projector_protocol::frame::FrameError;
+use projector_protocol::query::ParseError;
+use projector_protocol::ProjectorError;
+
+#[test]
+fn known_error_replies() {
+    assert_eq!(
+        ProjectorError::from_reply(b"PON", b"ER401"),
+        Some(ProjectorError::NotExecutable {
+            command: cmd("PON")
+        })
+    );
+    assert_eq!(
+        ProjectorError::from_reply(b"AVL:099", b"ER402"),
+        Some(ProjectorError::InvalidParameter {
+            command: cmd("AVL:099")
+        })
+    );
+    assert_eq!(
+        ProjectorError::from_reply(b"XYZ", b"???"),
+        Some(ProjectorError::UnknownCommand {
+            command: cmd("XYZ")
+        })
+    );
+}
+
+#[test]
+fn other_error_codes() {
+    assert_eq!(
+        ProjectorError::from_reply(b"PON", b"ER500"),
+        Some(ProjectorError::ErrorReply {
+            command: cmd("PON"),
+            code: 500
+        })
+    );
+    assert_eq!(ProjectorError::from_reply(b"PON", b"ER"), None);
+    assert_eq!(ProjectorError::from_reply(b"PON", b"ERROR"), None);
+}
+
+#[test]
+fn regular_replies_are_not_errors() {
+    assert_eq!(ProjectorError::from_reply(b"PON", b"PON"), None);
+    assert_eq!(ProjectorError::from_reply(b"QPW", b"001"), None);
+}
+
+#[test]
+fn error_reply_classification() {
+    assert!(ProjectorError::from_reply(b"PON", b"ER401")
+        .unwrap()
+        .is_error_reply());
+    assert!(!ProjectorError::Timeout {
+        command: cmd("PON")
+    }
+    .is_error_reply());
+}
+
+#[test]
+fn command_name_is_printable_and_bounded() {
+    assert_eq!(command_name(b"IIS:HD1"), "IIS:HD1");
+    assert_eq!(command_name(b"P\x00N"), "P?N");
+    assert_eq!(command_name(&[b'A'; 40]).len(), 16);
+}
+
+#[test]
+fn display_names_command() {
+    let text = |e: ProjectorError| e.to_string();
+
+    assert_eq!(
+        text(ProjectorError::NotExecutable {
+            command: cmd("PON")
+        }),
+        "PON: cannot be executed now (ER401)"
+    );
+    assert_eq!(
+        text(ProjectorError::Framing {
+            command: cmd("QPW"),
+            error: FrameError::MissingEtx,
+        }),
+        "QPW: bad framing (MissingEtx)"
+    );
+    assert_eq!(
+        text(ProjectorError::ParseError {
+            command: cmd("QIN"),
+            error: ParseError::UnknownValue,
+        }),
+        "QIN: unexpected reply (UnknownValue)"
+    );
+    assert_eq!(
+        text(ProjectorError::ErrorReply {
+            command: cmd("POF"),
+            code: 7
+        }),
+        "POF: error ER007"
+    );
+    assert_eq!(
+        text(ProjectorError::Forbidden {
+            command: cmd("PON")
+        }),
+        "PON: not allowed"
+    );
+}
@@ -0,0 +1,79 @@
+use projector_protocol::frame::{encode, FrameDecoder, FrameError, ETX, MAX_PAYLOAD_LEN, STX};
+
+#[test]
+fn encode_wraps_payload() {
+    assert_eq!(encode(b"PON").unwrap().as_slice(), b"\x02PON\x03");
+    assert_eq!(encode(b"").unwrap().as_slice(), b"\x02\x03");
+}
+
+#[test]
+fn encode_rejects_oversized_payload() {
+    let payload = [b'A'; MAX_PAYLOAD_LEN + 1];
+    assert_eq!(encode(&payload), Err(FrameError::Overflow));
+    assert!(encode(&payload[..MAX_PAYLOAD_LEN]).is_ok());
+}
+
+#[test]
+fn encode_rejects_control_bytes_in_payload() {
+    assert_eq!(encode(&[b'P', STX, b'N']), Err(FrameError::InvalidByte));
+    assert_eq!(encode(&[b'P', ETX, b'N']), Err(FrameError::InvalidByte));
+}
+
+fn decode_all(decoder: &mut FrameDecoder, bytes: &[u8]) -> Result<bool, FrameError> {
+    let mut done = false;
+    for b in bytes {
+        done = decoder.push(*b)?;
+    }
+    Ok(done)
+}
+
+#[test]
+fn decoder_returns_payload() {
+    let mut decoder = FrameDecoder::new();
+    assert_eq!(decode_all(&mut decoder, b"\x02QPW\x03"), Ok(true));
+    assert_eq!(decoder.payload(), b"QPW");
+}
+
+#[test]
+fn decoder_has_no_payload_until_etx() {
+    let mut decoder = FrameDecoder::new();
+    assert_eq!(decode_all(&mut decoder, b"\x02001"), Ok(false));
+    assert_eq!(decoder.payload(), b"");
+}
+
+#[test]
+fn decoder_handles_back_to_back_frames() {
+    let mut decoder = FrameDecoder::new();
+    assert_eq!(decode_all(&mut decoder, b"\x02PON\x03"), Ok(true));
+    assert_eq!(decode_all(&mut decoder, b"\x02001\x03"), Ok(true));
+    assert_eq!(decoder.payload(), b"001");
+}
+
+#[test]
+fn decoder_rejects_bytes_outside_frame() {
+    let mut decoder = FrameDecoder::new();
+    assert_eq!(decoder.push(b'X'), Err(FrameError::MissingStx));
+    assert_eq!(decode_all(&mut decoder, b"\x02OK\x03"), Ok(true));
+    assert_eq!(decoder.payload(), b"OK");
+}
+
+#[test]
+fn decoder_restarts_on_unterminated_frame() {
+    let mut decoder = FrameDecoder::new();
+    assert_eq!(decode_all(&mut decoder, b"\x02PO"), Ok(false));
+    assert_eq!(decoder.push(STX), Err(FrameError::MissingEtx));
+    assert_eq!(decode_all(&mut decoder, b"001\x03"), Ok(true));
+    assert_eq!(decoder.payload(), b"001");
+}
+
+#[test]
+fn decoder_reports_overflow() {
+    let mut decoder = FrameDecoder::new();
+    decoder.push(STX).unwrap();
+    for _ in 0..MAX_PAYLOAD_LEN {
+        assert_eq!(decoder.push(b'A'), Ok(false));
+    }
+    assert_eq!(decoder.push(b'A'), Err(FrameError::Overflow));
+    // the decoder starts over after an overflow
+    assert_eq!(decoder.push(b'A'), Err(FrameError::MissingStx));
+}
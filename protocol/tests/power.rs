@@ -0,0 +1,270 @@
+mod common;
+
+use std::time::Duration;
+
+use common::cmd;
+use projector_protocol::blocking::Projector;
+use projector_protocol::command::Input;
+use projector_protocol::power::{Decision, PowerPhase, PowerStateMachine};
+use projector_protocol::query::{ErrorStatus, ErrorStatusQuery, PowerQuery, PowerState};
+use projector_protocol::{ProjectorCommand, ProjectorError};
+use projector_simulator::pipe::SimPort;
+use projector_simulator::{Config, SharedSimulator, Simulator};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+const WARM_UP_TICKS: u32 = 3;
+const COOL_DOWN_TICKS: u32 = 5;
+
+/// Controller side: the state machine in front of a simulated projector
+/// that takes a few poll intervals to warm up and cool down.
+struct Harness {
+    machine: PowerStateMachine,
+    projector: Projector<SimPort>,
+    sim: SharedSimulator,
+    /// Deferred commands the state machine gave up on.
+    dropped: Vec<ProjectorError>,
+}
+
+impl Harness {
+    fn new(power: PowerState) -> Self {
+        let mut sim = Simulator::new(Config {
+            warm_up: POLL_INTERVAL * WARM_UP_TICKS,
+            cool_down: POLL_INTERVAL * COOL_DOWN_TICKS,
+            ..Config::default()
+        });
+        sim.set_power(power);
+        let sim = sim.shared();
+
+        let mut harness = Self {
+            machine: PowerStateMachine::new(),
+            projector: Projector::new(SimPort::new(sim.clone())),
+            sim,
+            dropped: Vec::new(),
+        };
+        harness.poll();
+        harness
+    }
+
+    fn submit(&mut self, command: ProjectorCommand) -> Decision {
+        let decision = self.machine.submit(command);
+        if decision == Decision::Send {
+            self.send(command);
+        }
+        decision
+    }
+
+    fn send(&mut self, command: ProjectorCommand) {
+        self.projector
+            .execute(command)
+            .expect("state machine sent a command the projector refuses");
+        self.machine.sent(command);
+    }
+
+    /// Power state, then the error register, as the poller does.
+    fn poll(&mut self) {
+        let power = self.projector.query(PowerQuery).unwrap();
+        match self.machine.observe(power) {
+            Some(Ok(command)) => self.send(command),
+            Some(Err(e)) => self.dropped.push(e),
+            None => {}
+        }
+        let errors = self.projector.query(ErrorStatusQuery).unwrap();
+        self.dropped.extend(self.machine.observe_errors(errors));
+    }
+
+    /// Let `ticks` poll intervals pass.
+    fn run(&mut self, ticks: u32) {
+        for _ in 0..ticks {
+            self.sim.lock().unwrap().advance(POLL_INTERVAL);
+            self.poll();
+        }
+    }
+
+    /// Commands the projector received, leaving out the polling.
+    fn received(&self) -> Vec<String> {
+        let sim = self.sim.lock().unwrap();
+        sim.received()
+            .iter()
+            .filter(|r| !r.starts_with('Q'))
+            .cloned()
+            .collect()
+    }
+}
+
+#[test]
+fn power_on_from_standby() {
+    let mut h = Harness::new(PowerState::Standby);
+    assert_eq!(h.machine.phase(), Some(PowerPhase::Off));
+
+    assert_eq!(h.submit(ProjectorCommand::PowerOn), Decision::Send);
+    assert_eq!(h.machine.phase(), Some(PowerPhase::Warming));
+
+    h.run(WARM_UP_TICKS);
+    assert_eq!(h.machine.phase(), Some(PowerPhase::On));
+}
+
+#[test]
+fn power_on_during_cool_down_is_queued_until_standby() {
+    let mut h = Harness::new(PowerState::On);
+    assert_eq!(h.submit(ProjectorCommand::PowerOff), Decision::Send);
+    h.run(1);
+    assert_eq!(h.machine.phase(), Some(PowerPhase::Cooling));
+
+    assert_eq!(h.submit(ProjectorCommand::PowerOn), Decision::Deferred);
+    assert_eq!(h.machine.deferred(), Some(ProjectorCommand::PowerOn));
+    assert_eq!(h.received(), ["POF"]);
+
+    h.run(COOL_DOWN_TICKS);
+    assert_eq!(h.received(), ["POF", "PON"]);
+    assert_eq!(h.machine.deferred(), None);
+
+    h.run(WARM_UP_TICKS);
+    assert_eq!(h.machine.phase(), Some(PowerPhase::On));
+}
+
+#[test]
+fn power_on_deferred_into_standby_error_is_dropped() {
+    let mut h = Harness::new(PowerState::On);
+    h.submit(ProjectorCommand::PowerOff);
+    h.run(1);
+    assert_eq!(h.submit(ProjectorCommand::PowerOn), Decision::Deferred);
+
+    // the lamp fails while cooling down
+    h.sim
+        .lock()
+        .unwrap()
+        .set_error_status(ErrorStatus::LAMP_FAILURE);
+    h.run(COOL_DOWN_TICKS);
+    assert_eq!(h.machine.phase(), Some(PowerPhase::StandbyError));
+    assert_eq!(h.machine.deferred(), None);
+    assert_eq!(
+        h.dropped,
+        [ProjectorError::NotExecutable {
+            command: cmd("PON")
+        }]
+    );
+
+    // lamp replaced, the projector stays off
+    h.sim.lock().unwrap().set_error_status(0);
+    h.run(2);
+    assert_eq!(h.machine.phase(), Some(PowerPhase::Off));
+    assert_eq!(h.received(), ["POF"]);
+}
+
+#[test]
+fn power_off_during_warm_up_is_queued_until_on() {
+    let mut h = Harness::new(PowerState::Standby);
+    h.submit(ProjectorCommand::PowerOn);
+
+    assert_eq!(h.submit(ProjectorCommand::PowerOff), Decision::Deferred);
+    h.run(WARM_UP_TICKS);
+    assert_eq!(h.machine.phase(), Some(PowerPhase::Cooling));
+
+    h.run(COOL_DOWN_TICKS);
+    assert_eq!(h.machine.phase(), Some(PowerPhase::Off));
+    assert_eq!(h.received(), ["PON", "POF"]);
+}
+
+#[test]
+fn opposite_command_cancels_deferred() {
+    let mut h = Harness::new(PowerState::On);
+    h.submit(ProjectorCommand::PowerOff);
+
+    assert_eq!(h.submit(ProjectorCommand::PowerOn), Decision::Deferred);
+    assert_eq!(h.submit(ProjectorCommand::PowerOff), Decision::AlreadyDone);
+    assert_eq!(h.machine.deferred(), None);
+
+    h.run(COOL_DOWN_TICKS + WARM_UP_TICKS);
+    assert_eq!(h.machine.phase(), Some(PowerPhase::Off));
+    assert_eq!(h.received(), ["POF"]);
+}
+
+#[test]
+fn redundant_power_commands_are_not_sent() {
+    let mut h = Harness::new(PowerState::On);
+    assert_eq!(h.submit(ProjectorCommand::PowerOn), Decision::AlreadyDone);
+
+    let mut h = Harness::new(PowerState::Standby);
+    assert_eq!(h.submit(ProjectorCommand::PowerOff), Decision::AlreadyDone);
+    assert_eq!(h.submit(ProjectorCommand::PowerOn), Decision::Send);
+    // optimistic transition, no poll in between
+    assert_eq!(h.submit(ProjectorCommand::PowerOn), Decision::AlreadyDone);
+    assert_eq!(h.received(), ["PON"]);
+}
+
+#[test]
+fn other_commands_are_rejected_during_transitions() {
+    let mut h = Harness::new(PowerState::Standby);
+    h.submit(ProjectorCommand::PowerOn);
+
+    let select = ProjectorCommand::SelectInput(Input::Hdmi1);
+    assert_eq!(
+        h.submit(select),
+        Decision::Rejected(ProjectorError::Busy {
+            command: cmd("IIS:HD1")
+        })
+    );
+
+    h.run(WARM_UP_TICKS);
+    assert_eq!(h.submit(select), Decision::Send);
+}
+
+#[test]
+fn standby_error() {
+    let mut machine = PowerStateMachine::new();
+    machine.observe(PowerState::Standby);
+    machine.observe_errors(ErrorStatus(ErrorStatus::LAMP_FAILURE));
+    assert_eq!(machine.phase(), Some(PowerPhase::StandbyError));
+
+    assert_eq!(
+        machine.submit(ProjectorCommand::Freeze(true)),
+        Decision::Rejected(ProjectorError::NotExecutable {
+            command: cmd("OFZ:1")
+        })
+    );
+    assert_eq!(
+        machine.submit(ProjectorCommand::PowerOff),
+        Decision::AlreadyDone
+    );
+
+    // warnings alone are no reason to stay down
+    machine.observe_errors(ErrorStatus(ErrorStatus::FILTER));
+    assert_eq!(machine.phase(), Some(PowerPhase::Off));
+    assert_eq!(machine.submit(ProjectorCommand::PowerOn), Decision::Send);
+}
+
+#[test]
+fn unknown_phase_sends_everything() {
+    let mut machine = PowerStateMachine::new();
+    assert_eq!(machine.phase(), None);
+    assert_eq!(machine.submit(ProjectorCommand::PowerOff), Decision::Send);
+    assert_eq!(machine.submit(ProjectorCommand::PowerOn), Decision::Send);
+}
+
+#[test]
+fn phase_from_status() {
+    let error = Some(ErrorStatus(ErrorStatus::FAN));
+    assert_eq!(
+        PowerPhase::from_status(PowerState::Standby, None),
+        PowerPhase::Off
+    );
+    assert_eq!(
+        PowerPhase::from_status(PowerState::Standby, error),
+        PowerPhase::StandbyError
+    );
+    // an error while running is the projector's business until it shuts down
+    assert_eq!(
+        PowerPhase::from_status(PowerState::On, error),
+        PowerPhase::On
+    );
+    assert_eq!(
+        PowerPhase::from_status(PowerState::Cooling, None),
+        PowerPhase::Cooling
+    );
+}
+
+#[test]
+fn phase_names() {
+    let names: Vec<_> = PowerPhase::ALL.iter().map(|p| p.name()).collect();
+    assert_eq!(names, ["off", "warming", "on", "cooling", "error"]);
+}
@@ -0,0 +1,157 @@
+use projector_protocol::command::{Input, Volume};
+use projector_protocol::query::{
+    ErrorStatus, ErrorStatusQuery, FirmwareVersionQuery, FreezeQuery, InputQuery, IntoQuery, Lamp,
+    LampMode, LampModeQuery, LampRuntime, LampRuntimeQuery, ModelNameQuery, ParseError, PowerQuery,
+    PowerState, Query, QueryResponse, ShutterQuery, Temperature, TemperatureQuery,
+    TemperatureSensor, VolumeQuery,
+};
+use projector_protocol::ProjectorQuery;
+
+fn encoded<Q: ProjectorQuery>(query: Q) -> String {
+    query.encode().as_str().to_owned()
+}
+
+#[test]
+fn query_codes() {
+    assert_eq!(encoded(PowerQuery), "QPW");
+    assert_eq!(encoded(InputQuery), "QIN");
+    assert_eq!(encoded(LampRuntimeQuery(Lamp::Lamp1)), "Q$L:1");
+    assert_eq!(encoded(LampRuntimeQuery(Lamp::Lamp2)), "Q$L:2");
+    assert_eq!(encoded(LampModeQuery), "QLM");
+    assert_eq!(
+        encoded(TemperatureQuery(TemperatureSensor::Intake)),
+        "QTM:0"
+    );
+    assert_eq!(
+        encoded(TemperatureQuery(TemperatureSensor::Exhaust)),
+        "QTM:1"
+    );
+    assert_eq!(encoded(ShutterQuery), "QSH");
+    assert_eq!(encoded(FreezeQuery), "QFZ");
+    assert_eq!(encoded(VolumeQuery), "QAV");
+    assert_eq!(encoded(ErrorStatusQuery), "QER");
+    assert_eq!(encoded(ModelNameQuery), "QID");
+    assert_eq!(encoded(FirmwareVersionQuery), "QVR");
+}
+
+#[test]
+fn power_state() {
+    assert_eq!(PowerQuery.parse(b"000"), Ok(PowerState::Standby));
+    assert_eq!(PowerQuery.parse(b"001"), Ok(PowerState::On));
+    assert_eq!(PowerQuery.parse(b"002"), Ok(PowerState::Warming));
+    assert_eq!(PowerQuery.parse(b"003"), Ok(PowerState::Cooling));
+    assert_eq!(PowerQuery.parse(b"004"), Err(ParseError::UnknownValue));
+    assert_eq!(PowerQuery.parse(b""), Err(ParseError::UnknownValue));
+
+    assert!(PowerState::Warming.is_on());
+    assert!(!PowerState::Cooling.is_on());
+}
+
+#[test]
+fn input() {
+    assert_eq!(InputQuery.parse(b"HD2"), Ok(Input::Hdmi2));
+    assert_eq!(InputQuery.parse(b"RG1"), Ok(Input::Computer1));
+    assert_eq!(InputQuery.parse(b"hd2"), Err(ParseError::UnknownValue));
+    assert_eq!(InputQuery.parse(&[0xff]), Err(ParseError::NotAscii));
+}
+
+#[test]
+fn lamp_runtime() {
+    let query = LampRuntimeQuery(Lamp::Lamp1);
+    assert_eq!(query.parse(b"00000"), Ok(LampRuntime { hours: 0 }));
+    assert_eq!(query.parse(b"02150"), Ok(LampRuntime { hours: 2150 }));
+    assert_eq!(query.parse(b""), Err(ParseError::InvalidNumber));
+    assert_eq!(query.parse(b"12h"), Err(ParseError::InvalidNumber));
+    assert_eq!(query.parse(b"-1"), Err(ParseError::InvalidNumber));
+}
+
+#[test]
+fn lamp_mode() {
+    assert_eq!(LampModeQuery.parse(b"0"), Ok(LampMode::Normal));
+    assert_eq!(LampModeQuery.parse(b"1"), Ok(LampMode::Eco));
+    assert_eq!(LampModeQuery.parse(b"2"), Err(ParseError::UnknownValue));
+}
+
+#[test]
+fn temperature() {
+    let query = TemperatureQuery(TemperatureSensor::Exhaust);
+    assert_eq!(query.parse(b"+045"), Ok(Temperature { celsius: 45 }));
+    assert_eq!(query.parse(b"-005"), Ok(Temperature { celsius: -5 }));
+    assert_eq!(query.parse(b"031"), Ok(Temperature { celsius: 31 }));
+    assert_eq!(query.parse(b"+"), Err(ParseError::InvalidNumber));
+}
+
+#[test]
+fn toggles() {
+    assert_eq!(ShutterQuery.parse(b"1"), Ok(true));
+    assert_eq!(ShutterQuery.parse(b"0"), Ok(false));
+    assert_eq!(FreezeQuery.parse(b"1"), Ok(true));
+    assert_eq!(FreezeQuery.parse(b"01"), Err(ParseError::UnknownValue));
+}
+
+#[test]
+fn volume() {
+    assert_eq!(VolumeQuery.parse(b"020"), Ok(Volume::new(20).unwrap()));
+    assert_eq!(VolumeQuery.parse(b"064"), Err(ParseError::InvalidNumber));
+}
+
+#[test]
+fn error_status() {
+    assert_eq!(ErrorStatusQuery.parse(b"0000"), Ok(ErrorStatus(0)));
+
+    let status = ErrorStatusQuery.parse(b"0204").unwrap();
+    assert!(status.contains(ErrorStatus::LAMP_FAILURE));
+    assert!(status.contains(ErrorStatus::LAMP_REPLACE));
+    assert!(status.has_error());
+    assert!(status.has_warning());
+
+    let status = ErrorStatusQuery.parse(b"0400").unwrap();
+    assert!(!status.has_error());
+    assert!(status.has_warning());
+
+    assert_eq!(
+        ErrorStatusQuery.parse(b"04"),
+        Err(ParseError::InvalidNumber)
+    );
+    assert_eq!(
+        ErrorStatusQuery.parse(b"zzzz"),
+        Err(ParseError::InvalidNumber)
+    );
+}
+
+#[test]
+fn text_replies() {
+    assert_eq!(ModelNameQuery.parse(b"PT-AH1000E").unwrap(), "PT-AH1000E");
+    assert_eq!(FirmwareVersionQuery.parse(b"1.02 ").unwrap(), "1.02");
+}
+
+#[test]
+fn any_query_delegates() {
+    let query = Query::from(TemperatureQuery(TemperatureSensor::Intake));
+    assert_eq!(encoded(query), "QTM:0");
+    assert_eq!(
+        query.parse(b"+021"),
+        Ok(QueryResponse::Temperature(Temperature { celsius: 21 }))
+    );
+    assert_eq!(
+        Query::from(InputQuery).parse(b"XXX"),
+        Err(ParseError::UnknownValue)
+    );
+}
+
+#[test]
+fn typed_response_from_any() {
+    assert_eq!(
+        ShutterQuery::from_response(QueryResponse::Shutter(true)),
+        Some(true)
+    );
+    // same response type, different query
+    assert_eq!(
+        FreezeQuery::from_response(QueryResponse::Shutter(true)),
+        None
+    );
+    assert_eq!(
+        PowerQuery::from_response(QueryResponse::Power(PowerState::On)),
+        Some(PowerState::On)
+    );
+}
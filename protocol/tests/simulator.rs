@@ -0,0 +1,216 @@
+mod common;
+
+use std::time::Duration;
+
+use common::cmd;
+use embassy_futures::block_on;
+use projector_protocol::blocking::Projector;
+use projector_protocol::command::{Input, Volume};
+use projector_protocol::query::{
+    ErrorStatus, ErrorStatusQuery, InputQuery, Lamp, LampRuntime, LampRuntimeQuery, ModelNameQuery,
+    PowerQuery, PowerState, VolumeQuery,
+};
+use projector_protocol::{asynch, ProjectorCommand, ProjectorError};
+use projector_simulator::pipe::SimPort;
+use projector_simulator::{Config, Fault, SharedSimulator, Simulator};
+
+const WARM_UP: Duration = Duration::from_secs(30);
+const COOL_DOWN: Duration = Duration::from_secs(60);
+
+fn simulator() -> SharedSimulator {
+    Simulator::new(Config {
+        warm_up: WARM_UP,
+        cool_down: COOL_DOWN,
+        lamp_hours: 100,
+        ..Config::default()
+    })
+    .shared()
+}
+
+fn connect() -> (Projector<SimPort>, SharedSimulator) {
+    let sim = simulator();
+    (Projector::new(SimPort::new(sim.clone())), sim)
+}
+
+fn advance(sim: &SharedSimulator, elapsed: Duration) {
+    sim.lock().unwrap().advance(elapsed);
+}
+
+#[test]
+fn power_cycle() {
+    let (mut projector, sim) = connect();
+    assert_eq!(projector.query(PowerQuery), Ok(PowerState::Standby));
+
+    projector.execute(ProjectorCommand::PowerOn).unwrap();
+    assert_eq!(projector.query(PowerQuery), Ok(PowerState::Warming));
+
+    advance(&sim, WARM_UP);
+    assert_eq!(projector.query(PowerQuery), Ok(PowerState::On));
+
+    projector.execute(ProjectorCommand::PowerOff).unwrap();
+    assert_eq!(projector.query(PowerQuery), Ok(PowerState::Cooling));
+    advance(&sim, COOL_DOWN - Duration::from_secs(1));
+    assert_eq!(projector.query(PowerQuery), Ok(PowerState::Cooling));
+    advance(&sim, Duration::from_secs(1));
+    assert_eq!(projector.query(PowerQuery), Ok(PowerState::Standby));
+
+    assert_eq!(
+        sim.lock().unwrap().received(),
+        ["QPW", "PON", "QPW", "QPW", "POF", "QPW", "QPW", "QPW"]
+    );
+}
+
+#[test]
+fn commands_are_refused_until_warmed_up() {
+    let (mut projector, sim) = connect();
+    let select = ProjectorCommand::SelectInput(Input::Hdmi2);
+
+    assert_eq!(
+        projector.execute(select),
+        Err(ProjectorError::NotExecutable {
+            command: cmd("IIS:HD2")
+        })
+    );
+    projector.execute(ProjectorCommand::PowerOn).unwrap();
+    assert_eq!(
+        projector.execute(ProjectorCommand::PowerOff),
+        Err(ProjectorError::NotExecutable {
+            command: cmd("POF")
+        })
+    );
+
+    advance(&sim, WARM_UP);
+    projector.execute(select).unwrap();
+    assert_eq!(projector.query(InputQuery), Ok(Input::Hdmi2));
+
+    let volume = Volume::new(42).unwrap();
+    projector.execute(ProjectorCommand::Volume(volume)).unwrap();
+    assert_eq!(projector.query(VolumeQuery), Ok(volume));
+}
+
+#[test]
+fn bad_parameters_and_unknown_commands() {
+    let (mut projector, sim) = connect();
+    sim.lock().unwrap().set_power(PowerState::On);
+
+    let mut buffer = [0u8; 16];
+    projector.send(b"IIS:XXX").unwrap();
+    let len = projector.receive(&mut buffer).unwrap();
+    assert_eq!(&buffer[..len], b"ER402");
+
+    projector.send(b"ZZZ").unwrap();
+    let len = projector.receive(&mut buffer).unwrap();
+    assert_eq!(&buffer[..len], b"???");
+}
+
+#[test]
+fn lamp_hours_accrue_while_lit() {
+    let (mut projector, sim) = connect();
+    let lamp = LampRuntimeQuery(Lamp::Lamp1);
+    assert_eq!(projector.query(lamp), Ok(LampRuntime { hours: 100 }));
+
+    advance(&sim, Duration::from_secs(5 * 3600));
+    assert_eq!(projector.query(lamp), Ok(LampRuntime { hours: 100 }));
+
+    projector.execute(ProjectorCommand::PowerOn).unwrap();
+    advance(&sim, Duration::from_secs(2 * 3600));
+    assert_eq!(projector.query(lamp), Ok(LampRuntime { hours: 102 }));
+}
+
+#[test]
+fn error_shuts_the_lamp_down() {
+    let (mut projector, sim) = connect();
+    sim.lock().unwrap().set_power(PowerState::On);
+
+    sim.lock()
+        .unwrap()
+        .set_error_status(ErrorStatus::FAN | ErrorStatus::FILTER);
+    assert_eq!(projector.query(PowerQuery), Ok(PowerState::Cooling));
+    assert_eq!(
+        projector.query(ErrorStatusQuery),
+        Ok(ErrorStatus(ErrorStatus::FAN | ErrorStatus::FILTER))
+    );
+
+    advance(&sim, COOL_DOWN);
+    assert_eq!(
+        projector.execute(ProjectorCommand::PowerOn),
+        Err(ProjectorError::NotExecutable {
+            command: cmd("PON")
+        })
+    );
+
+    // warnings do not keep it down
+    sim.lock().unwrap().set_error_status(ErrorStatus::FILTER);
+    projector.execute(ProjectorCommand::PowerOn).unwrap();
+}
+
+#[test]
+fn injected_faults() {
+    let (mut projector, sim) = connect();
+    {
+        let mut sim = sim.lock().unwrap();
+        sim.inject(Fault::ErrorReply(401));
+        sim.inject(Fault::NoReply);
+        sim.inject(Fault::Reply(b"???".to_vec()));
+        sim.inject(Fault::Truncated);
+    }
+
+    assert_eq!(
+        projector.query(PowerQuery),
+        Err(ProjectorError::NotExecutable {
+            command: cmd("QPW")
+        })
+    );
+    assert_eq!(
+        projector.query(PowerQuery),
+        Err(ProjectorError::ReadError {
+            command: cmd("QPW")
+        })
+    );
+    assert_eq!(
+        projector.query(ModelNameQuery),
+        Err(ProjectorError::UnknownCommand {
+            command: cmd("QID")
+        })
+    );
+    // reply without ETX runs into end of stream
+    assert_eq!(
+        projector.query(PowerQuery),
+        Err(ProjectorError::ReadError {
+            command: cmd("QPW")
+        })
+    );
+    assert_eq!(projector.query(PowerQuery), Ok(PowerState::Standby));
+}
+
+#[test]
+fn disconnected_projector_is_silent() {
+    let (mut projector, sim) = connect();
+    sim.lock().unwrap().set_connected(false);
+    assert_eq!(
+        projector.query(PowerQuery),
+        Err(ProjectorError::ReadError {
+            command: cmd("QPW")
+        })
+    );
+    assert!(sim.lock().unwrap().received().is_empty());
+
+    sim.lock().unwrap().set_connected(true);
+    assert_eq!(projector.query(PowerQuery), Ok(PowerState::Standby));
+}
+
+#[test]
+fn async_projector() {
+    let sim = simulator();
+    let mut projector = asynch::Projector::new(SimPort::new(sim.clone()));
+
+    block_on(async {
+        projector.execute(ProjectorCommand::PowerOn).await.unwrap();
+        advance(&sim, WARM_UP);
+        projector
+            .execute(ProjectorCommand::Freeze(true))
+            .await
+            .unwrap();
+    });
+    assert!(sim.lock().unwrap().settings().freeze);
+}
@@ -0,0 +1,428 @@
+//! Simulated Panasonic PT-AH1000E for testing controllers without hardware.
+//!
+//! [`Simulator`] answers request payloads the way the projector does,
+//! including the warm-up and cool-down periods during which it refuses
+//! commands. Time only moves when [`Simulator::advance`] is called, so tests
+//! are deterministic; [`pty::PtyServer`] advances it with the wall clock.
+//!
+//! Hand [`pipe::SimPort`] to a `Projector` for in-memory tests, or run the
+//! `projector-simulator` binary to get a pseudo terminal standing in for the
+//! serial cable.
+
+pub mod pipe;
+pub mod pty;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use projector_protocol::command::{Aspect, Input, Keystone, PictureMode, TestPattern, Volume};
+use projector_protocol::frame::{FrameDecoder, ETX, STX};
+use projector_protocol::query::{ErrorStatus, LampMode, PowerState};
+
+/// Simulator shared between the port handed to the controller and the test
+/// driving it.
+pub type SharedSimulator = Arc<Mutex<Simulator>>;
+
+const NOT_EXECUTABLE: &str = "ER401";
+const INVALID_PARAMETER: &str = "ER402";
+const UNKNOWN_COMMAND: &str = "???";
+
+/// Every command and query code the simulator knows.
+const CODES: &[&str] = &[
+    "PON", "POF", "IIS", "OSH", "OFZ", "VPM", "VSE", "AVL", "KSV", "KSH", "OMN", "OEN", "OBK",
+    "OCU", "OCD", "OCL", "OCR", "OTS", "QPW", "QIN", "Q$L", "QLM", "QTM", "QSH", "QFZ", "QAV",
+    "QER", "QID", "QVR",
+];
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Time from `PON` until the projector reports on.
+    pub warm_up: Duration,
+    /// Time from `POF`, or a fatal error, until the projector is in standby.
+    pub cool_down: Duration,
+    /// Lamp hours before the simulation starts.
+    pub lamp_hours: u32,
+    /// Reply to `QID`.
+    pub model: String,
+    /// Reply to `QVR`.
+    pub firmware: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            warm_up: Duration::from_secs(30),
+            cool_down: Duration::from_secs(90),
+            lamp_hours: 1200,
+            model: "PT-AH1000E".into(),
+            firmware: "1.00".into(),
+        }
+    }
+}
+
+/// One-shot misbehaviour, applied to the next request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+    /// Swallow the request without answering.
+    NoReply,
+    /// Answer with `ERnnn` instead of the real reply.
+    ErrorReply(u16),
+    /// Answer with this payload instead of the real reply.
+    Reply(Vec<u8>),
+    /// Send the real reply but drop its ETX.
+    Truncated,
+}
+
+/// Picture and audio settings, only changeable while the projector is on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    pub input: Input,
+    pub shutter: bool,
+    pub freeze: bool,
+    pub volume: Volume,
+    pub picture_mode: PictureMode,
+    pub aspect: Aspect,
+    pub keystone_vertical: Keystone,
+    pub keystone_horizontal: Keystone,
+    pub test_pattern: TestPattern,
+    pub lamp_mode: LampMode,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            input: Input::Hdmi1,
+            shutter: false,
+            freeze: false,
+            volume: Volume::new(20).unwrap(),
+            picture_mode: PictureMode::Standard,
+            aspect: Aspect::Auto,
+            keystone_vertical: Keystone::new(0).unwrap(),
+            keystone_horizontal: Keystone::new(0).unwrap(),
+            test_pattern: TestPattern::Off,
+            lamp_mode: LampMode::Normal,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Simulator {
+    config: Config,
+    settings: Settings,
+    power: PowerState,
+    /// Time left until the current warm-up or cool-down ends.
+    transition: Duration,
+    /// Time the lamp was lit since the simulation started.
+    lamp_time: Duration,
+    errors: ErrorStatus,
+    connected: bool,
+    faults: VecDeque<Fault>,
+    truncate_reply: bool,
+    decoder: FrameDecoder,
+    received: Vec<String>,
+}
+
+impl Simulator {
+    /// A projector in standby.
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            settings: Settings::default(),
+            power: PowerState::Standby,
+            transition: Duration::ZERO,
+            lamp_time: Duration::ZERO,
+            errors: ErrorStatus::default(),
+            connected: true,
+            faults: VecDeque::new(),
+            truncate_reply: false,
+            decoder: FrameDecoder::new(),
+            received: Vec::new(),
+        }
+    }
+
+    /// Wrap the simulator for sharing with a [`pipe::SimPort`].
+    pub fn shared(self) -> SharedSimulator {
+        Arc::new(Mutex::new(self))
+    }
+
+    pub fn power(&self) -> PowerState {
+        self.power
+    }
+
+    /// Jump to `power` without going through the protocol. Warm-up and
+    /// cool-down start from the beginning.
+    pub fn set_power(&mut self, power: PowerState) {
+        let duration = match power {
+            PowerState::Warming => self.config.warm_up,
+            PowerState::Cooling => self.config.cool_down,
+            PowerState::Standby | PowerState::On => Duration::ZERO,
+        };
+        self.start(power, duration);
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
+
+    pub fn lamp_hours(&self) -> u32 {
+        self.config.lamp_hours + (self.lamp_time.as_secs() / 3600) as u32
+    }
+
+    pub fn error_status(&self) -> ErrorStatus {
+        self.errors
+    }
+
+    /// Set the error register. Like the real projector, an error (as opposed
+    /// to a warning) shuts the lamp down and keeps it from being switched on.
+    pub fn set_error_status(&mut self, status: u16) {
+        self.errors = ErrorStatus(status);
+        if self.errors.has_error() && self.power.is_on() {
+            self.start(PowerState::Cooling, self.config.cool_down);
+        }
+    }
+
+    /// Misbehave on a coming request. Faults are used up in the order they
+    /// were injected, one per request.
+    pub fn inject(&mut self, fault: Fault) {
+        self.faults.push_back(fault);
+    }
+
+    /// Stop reacting to anything, as if the cable was pulled.
+    pub fn set_connected(&mut self, connected: bool) {
+        self.connected = connected;
+    }
+
+    /// Payloads received so far, oldest first.
+    pub fn received(&self) -> &[String] {
+        &self.received
+    }
+
+    /// Payloads received since the last call.
+    pub fn take_received(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.received)
+    }
+
+    /// Let `elapsed` pass, finishing warm-up or cool-down when due.
+    pub fn advance(&mut self, elapsed: Duration) {
+        let mut left = elapsed;
+        while !left.is_zero() {
+            let step = if self.transition.is_zero() {
+                left
+            } else {
+                self.transition.min(left)
+            };
+            if self.power.is_on() {
+                self.lamp_time += step;
+            }
+            left -= step;
+
+            if !self.transition.is_zero() {
+                self.transition -= step;
+                if self.transition.is_zero() {
+                    self.finish_transition();
+                }
+            }
+        }
+    }
+
+    /// Process bytes received on the serial line and return the bytes sent
+    /// back. Anything outside a well-formed frame is ignored.
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        if !self.connected {
+            return out;
+        }
+
+        for &byte in bytes {
+            if self.decoder.push(byte) != Ok(true) {
+                continue;
+            }
+            let request = self.decoder.payload().to_vec();
+            if let Some(reply) = self.handle(&request) {
+                out.push(STX);
+                out.extend_from_slice(&reply);
+                if !std::mem::take(&mut self.truncate_reply) {
+                    out.push(ETX);
+                }
+            }
+        }
+        out
+    }
+
+    /// Reply payload for a request payload, `None` if the projector stays
+    /// silent.
+    pub fn handle(&mut self, request: &[u8]) -> Option<Vec<u8>> {
+        let request = String::from_utf8_lossy(request).into_owned();
+        self.received.push(request.clone());
+
+        match self.faults.pop_front() {
+            Some(Fault::NoReply) => return None,
+            Some(Fault::ErrorReply(code)) => return Some(format!("ER{code:03}").into_bytes()),
+            Some(Fault::Reply(reply)) => return Some(reply),
+            Some(Fault::Truncated) => self.truncate_reply = true,
+            None => {}
+        }
+
+        Some(self.reply(&request).into_bytes())
+    }
+
+    fn reply(&mut self, request: &str) -> String {
+        let (code, param) = match request.split_once(':') {
+            Some((code, param)) => (code, Some(param)),
+            None => (request, None),
+        };
+        if !CODES.contains(&code) {
+            return UNKNOWN_COMMAND.into();
+        }
+
+        let settings = &self.settings;
+        match (code, param) {
+            ("PON", None) => self.power_on(),
+            ("POF", None) => self.power_off(),
+            ("QPW", None) => match self.power {
+                PowerState::Standby => "000",
+                PowerState::On => "001",
+                PowerState::Warming => "002",
+                PowerState::Cooling => "003",
+            }
+            .into(),
+            ("QER", None) => format!("{:04X}", self.errors.0),
+            ("QID", None) => self.config.model.clone(),
+            ("QVR", None) => self.config.firmware.clone(),
+            ("QLM", None) => settings.lamp_mode.code().into(),
+            ("Q$L", Some("1")) => format!("{:05}", self.lamp_hours()),
+            // single lamp model
+            ("Q$L", Some("2")) => "00000".into(),
+            ("QTM", Some("0")) => "+025".into(),
+            ("QTM", Some("1")) => match self.power {
+                PowerState::Standby => "+027",
+                PowerState::Warming | PowerState::Cooling => "+038",
+                PowerState::On => "+046",
+            }
+            .into(),
+            ("PON" | "POF" | "QPW" | "QER" | "QID" | "QVR" | "QLM" | "Q$L" | "QTM", _) => {
+                INVALID_PARAMETER.into()
+            }
+
+            // everything else needs the lamp lit
+            _ if self.power != PowerState::On => NOT_EXECUTABLE.into(),
+
+            ("QIN", None) => settings.input.code().into(),
+            ("QSH", None) => flag(settings.shutter).into(),
+            ("QFZ", None) => flag(settings.freeze).into(),
+            ("QAV", None) => format!("{:03}", settings.volume.get()),
+            ("IIS", Some(p)) => self.set(request, Input::from_code(p), |s, v| s.input = v),
+            ("OSH", Some(p)) => self.set(request, parse_flag(p), |s, v| s.shutter = v),
+            ("OFZ", Some(p)) => self.set(request, parse_flag(p), |s, v| s.freeze = v),
+            ("VPM", Some(p)) => self.set(request, PictureMode::from_code(p), |s, v| {
+                s.picture_mode = v
+            }),
+            ("VSE", Some(p)) => self.set(request, Aspect::from_code(p), |s, v| s.aspect = v),
+            ("AVL", Some(p)) => self.set(request, parse_volume(p), |s, v| s.volume = v),
+            ("KSV", Some(p)) => {
+                self.set(request, parse_keystone(p), |s, v| s.keystone_vertical = v)
+            }
+            ("KSH", Some(p)) => {
+                self.set(request, parse_keystone(p), |s, v| s.keystone_horizontal = v)
+            }
+            ("OTS", Some(p)) => self.set(request, TestPattern::from_code(p), |s, v| {
+                s.test_pattern = v
+            }),
+            ("OMN" | "OEN" | "OBK" | "OCU" | "OCD" | "OCL" | "OCR", None) => request.into(),
+            _ => INVALID_PARAMETER.into(),
+        }
+    }
+
+    fn power_on(&mut self) -> String {
+        match self.power {
+            PowerState::Standby if self.errors.has_error() => NOT_EXECUTABLE,
+            PowerState::Standby => {
+                self.start(PowerState::Warming, self.config.warm_up);
+                "PON"
+            }
+            PowerState::On => "PON",
+            PowerState::Warming | PowerState::Cooling => NOT_EXECUTABLE,
+        }
+        .into()
+    }
+
+    fn power_off(&mut self) -> String {
+        match self.power {
+            PowerState::On => {
+                self.start(PowerState::Cooling, self.config.cool_down);
+                "POF"
+            }
+            PowerState::Standby => "POF",
+            PowerState::Warming | PowerState::Cooling => NOT_EXECUTABLE,
+        }
+        .into()
+    }
+
+    /// Apply a parsed parameter and echo the request, or refuse it.
+    fn set<T>(
+        &mut self,
+        request: &str,
+        value: Option<T>,
+        apply: impl FnOnce(&mut Settings, T),
+    ) -> String {
+        match value {
+            Some(value) => {
+                apply(&mut self.settings, value);
+                request.into()
+            }
+            None => INVALID_PARAMETER.into(),
+        }
+    }
+
+    fn start(&mut self, power: PowerState, duration: Duration) {
+        self.power = power;
+        self.transition = duration;
+        if duration.is_zero() {
+            self.finish_transition();
+        }
+    }
+
+    fn finish_transition(&mut self) {
+        self.transition = Duration::ZERO;
+        self.power = match self.power {
+            PowerState::Warming => PowerState::On,
+            PowerState::Cooling => PowerState::Standby,
+            other => other,
+        };
+    }
+}
+
+fn flag(value: bool) -> &'static str {
+    if value {
+        "1"
+    } else {
+        "0"
+    }
+}
+
+fn parse_flag(param: &str) -> Option<bool> {
+    match param {
+        "0" => Some(false),
+        "1" => Some(true),
+        _ => None,
+    }
+}
+
+fn parse_volume(param: &str) -> Option<Volume> {
+    if param.len() != 3 {
+        return None;
+    }
+    Volume::new(param.parse().ok()?).ok()
+}
+
+fn parse_keystone(param: &str) -> Option<Keystone> {
+    if param.len() != 4 || !param.starts_with(['+', '-']) {
+        return None;
+    }
+    Keystone::new(param.parse().ok()?).ok()
+}
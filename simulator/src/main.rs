@@ -0,0 +1,85 @@
+//! Runs a simulated projector on a pseudo terminal.
+//!
+//! ```text
+//! projector-simulator [--link PATH] [--warm-up SECS] [--cool-down SECS] [--lamp-hours N]
+//! ```
+
+use std::process::ExitCode;
+use std::time::Duration;
+use std::{env, fs};
+
+use projector_simulator::pty::PtyServer;
+use projector_simulator::{Config, Simulator};
+
+const USAGE: &str =
+    "usage: projector-simulator [--link PATH] [--warm-up SECS] [--cool-down SECS] [--lamp-hours N]";
+
+struct Args {
+    config: Config,
+    /// Symlink to create pointing at the PTY, so clients get a stable path.
+    link: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        config: Config::default(),
+        link: None,
+    };
+
+    let mut it = env::args().skip(1);
+    while let Some(arg) = it.next() {
+        let mut value = || it.next().ok_or(format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--link" => args.link = Some(value()?),
+            "--warm-up" => args.config.warm_up = Duration::from_secs(number(&value()?)?),
+            "--cool-down" => args.config.cool_down = Duration::from_secs(number(&value()?)?),
+            "--lamp-hours" => args.config.lamp_hours = number(&value()?)?,
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ => return Err(format!("unknown argument {arg}\n{USAGE}")),
+        }
+    }
+    Ok(args)
+}
+
+fn number<T: std::str::FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("not a number: {value}"))
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{message}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let sim = Simulator::new(args.config).shared();
+    let mut server = match PtyServer::open(sim.clone()) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("cannot open PTY: {e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Some(link) = &args.link {
+        let _ = fs::remove_file(link);
+        if let Err(e) = std::os::unix::fs::symlink(server.path(), link) {
+            eprintln!("cannot link {link}: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
+    println!("{}", server.path().display());
+
+    loop {
+        if let Err(e) = server.serve_once() {
+            eprintln!("PTY failed: {e}");
+            return ExitCode::FAILURE;
+        }
+        let mut sim = sim.lock().unwrap();
+        for request in sim.take_received() {
+            eprintln!("<- {request} (power {:?})", sim.power());
+        }
+    }
+}
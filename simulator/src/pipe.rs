@@ -0,0 +1,83 @@
+//! In-memory serial port wired to a simulator.
+
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::MutexGuard;
+
+use crate::{SharedSimulator, Simulator};
+
+/// Port for a blocking or async `Projector`. Everything written goes
+/// straight to the simulator, its replies are handed out by `read`. With no
+/// reply pending `read` returns end of stream instead of blocking.
+#[derive(Debug)]
+pub struct SimPort {
+    sim: SharedSimulator,
+    rx: VecDeque<u8>,
+}
+
+impl SimPort {
+    pub fn new(sim: SharedSimulator) -> Self {
+        Self {
+            sim,
+            rx: VecDeque::new(),
+        }
+    }
+
+    /// The simulator on the other end.
+    pub fn simulator(&self) -> MutexGuard<'_, Simulator> {
+        self.sim.lock().unwrap()
+    }
+
+    /// Bytes sent by the simulator and not read yet.
+    pub fn pending(&self) -> usize {
+        self.rx.len()
+    }
+}
+
+impl embedded_io::ErrorType for SimPort {
+    type Error = Infallible;
+}
+
+impl embedded_io::Read for SimPort {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let n = buf.len().min(self.rx.len());
+        for (dst, src) in buf.iter_mut().zip(self.rx.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl embedded_io::Write for SimPort {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let reply = self.sim.lock().unwrap().feed(buf);
+        self.rx.extend(reply);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl embedded_io::ReadReady for SimPort {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.rx.is_empty())
+    }
+}
+
+impl embedded_io_async::Read for SimPort {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        embedded_io::Read::read(self, buf)
+    }
+}
+
+impl embedded_io_async::Write for SimPort {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        embedded_io::Write::write(self, buf)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+//! Pseudo terminal standing in for the serial cable.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::OwnedFd;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use nix::pty::openpty;
+use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg};
+use nix::unistd::ttyname;
+
+use crate::SharedSimulator;
+
+/// Serves a simulator on the master side of a PTY. Controllers open
+/// [`PtyServer::path`] like any serial device; baud rate settings are
+/// accepted and ignored.
+pub struct PtyServer {
+    sim: SharedSimulator,
+    master: File,
+    /// Kept open so the line settings survive clients coming and going.
+    _slave: OwnedFd,
+    path: PathBuf,
+    last_advance: Instant,
+}
+
+impl PtyServer {
+    pub fn open(sim: SharedSimulator) -> io::Result<Self> {
+        let pty = openpty(None, None)?;
+
+        // STX/ETX are ^B/^C, keep the line discipline away from them
+        let mut termios = tcgetattr(&pty.slave)?;
+        cfmakeraw(&mut termios);
+        tcsetattr(&pty.slave, SetArg::TCSANOW, &termios)?;
+
+        let path = ttyname(&pty.slave)?;
+        Ok(Self {
+            sim,
+            master: File::from(pty.master),
+            _slave: pty.slave,
+            path,
+            last_advance: Instant::now(),
+        })
+    }
+
+    /// Device path of the slave side, e.g. `/dev/pts/3`.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Wait for bytes from the controller and answer them. Simulated time
+    /// catches up with the wall clock first.
+    pub fn serve_once(&mut self) -> io::Result<()> {
+        let mut buf = [0u8; 256];
+        let n = self.master.read(&mut buf)?;
+
+        let reply = {
+            let mut sim = self.sim.lock().unwrap();
+            let now = Instant::now();
+            sim.advance(now - self.last_advance);
+            self.last_advance = now;
+            sim.feed(&buf[..n])
+        };
+        self.master.write_all(&reply)
+    }
+
+    /// Serve until the PTY fails.
+    pub fn run(&mut self) -> io::Result<()> {
+        loop {
+            self.serve_once()?;
+        }
+    }
+}
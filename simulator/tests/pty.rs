@@ -0,0 +1,33 @@
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::thread;
+
+use projector_protocol::query::PowerState;
+use projector_simulator::pty::PtyServer;
+use projector_simulator::{Config, Simulator};
+
+#[test]
+fn serves_frames_over_pty() {
+    let sim = Simulator::new(Config::default()).shared();
+    sim.lock().unwrap().set_power(PowerState::On);
+    let mut server = PtyServer::open(sim.clone()).unwrap();
+    let path = server.path().to_owned();
+    thread::spawn(move || server.run());
+
+    let mut port = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .unwrap();
+    port.write_all(b"\x02IIS:RG1\x03\x02QIN\x03").unwrap();
+
+    let expected = b"\x02IIS:RG1\x03\x02RG1\x03";
+    let mut reply = Vec::new();
+    let mut buf = [0u8; 32];
+    while reply.len() < expected.len() {
+        let n = port.read(&mut buf).unwrap();
+        reply.extend_from_slice(&buf[..n]);
+    }
+    assert_eq!(reply, expected);
+    assert_eq!(sim.lock().unwrap().received(), ["IIS:RG1", "QIN"]);
+}